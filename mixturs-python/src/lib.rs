@@ -1,4 +1,5 @@
 use pyo3::prelude::*;
+use pyo3::exceptions::PyValueError;
 use numpy::nalgebra::{U1, Dynamic};
 use numpy::{Ix2, PyArray, PyReadonlyArrayDyn, ToPyArray};
 use mixturs::{NIW};
@@ -57,7 +58,7 @@ impl Model {
         x: PyReadonlyArrayDyn<f64>,
         fit_options: &FitOptions,
         y: Option<PyReadonlyArrayDyn<usize>>,
-    ) {
+    ) -> PyResult<()> {
         let x = x.try_as_matrix::<Dynamic, Dynamic, Dynamic, Dynamic>().unwrap().clone_owned();
         let y = y.map(|y| y.try_as_matrix::<Dynamic, U1, U1, Dynamic>().unwrap().transpose());
 
@@ -79,7 +80,12 @@ impl Model {
             x,
             &fit_options.inner,
             Some(callback),
-        );
+        ).map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(())
+    }
+
+    pub fn dim(&self) -> usize {
+        self.inner.dim()
     }
 
     pub fn predict<'py>(
@@ -154,6 +160,8 @@ pyacessors! {
         get_set(argmax_sample_stop, set_argmax_sample_stop, usize)
         get_set(iter_split_stop, set_iter_split_stop, usize)
         get_set(workers, set_workers, i32)
+        get_set(tol, set_tol, f64)
+        get_set(patience, set_patience, usize)
     }
 }
 
@@ -187,6 +195,30 @@ impl ModelOptions {
     pub fn set_outlier_removal(&mut self, outlier_removal: Option<OutlierRemoval>) {
         self.inner.outlier = outlier_removal.map(|o| o.inner);
     }
+
+    pub fn weight_pseudo_counts(&self) -> Option<Vec<f64>> {
+        self.inner.weight_pseudo_counts.clone()
+    }
+
+    pub fn set_weight_pseudo_counts(&mut self, weight_pseudo_counts: Option<Vec<f64>>) {
+        self.inner.weight_pseudo_counts = weight_pseudo_counts;
+    }
+
+    pub fn outlier_min_density(&self) -> Option<f64> {
+        self.inner.outlier_min_density
+    }
+
+    pub fn set_outlier_min_density(&mut self, outlier_min_density: Option<f64>) {
+        self.inner.outlier_min_density = outlier_min_density;
+    }
+
+    pub fn merge_threshold(&self) -> Option<f64> {
+        self.inner.merge_threshold
+    }
+
+    pub fn set_merge_threshold(&mut self, merge_threshold: Option<f64>) {
+        self.inner.merge_threshold = merge_threshold;
+    }
 }
 
 pyacessors! {
@@ -194,7 +226,10 @@ pyacessors! {
         get_set(alpha, set_alpha, f64)
         get_set(dim, set_dim, usize)
         get_set(burnout_period, set_burnout_period, usize)
+        get_set(split_warmup_iters, set_split_warmup_iters, usize)
         get_set(hard_assignment, set_hard_assignment, bool)
+        get_set(split_merge_temperature, set_split_merge_temperature, f64)
+        get_set(min_cluster_size, set_min_cluster_size, usize)
     }
 }
 
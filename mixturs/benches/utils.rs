@@ -4,7 +4,10 @@ use rand::distributions::{Standard, Distribution, WeightedIndex};
 use rand::Rng;
 use rand::SeedableRng;
 use rand::rngs::SmallRng;
-use mixturs::utils::{col_broadcast_add, replacement_sampling_weighted, reservoir_sampling_weighted, unique_with_indices};
+use mixturs::utils::{
+    col_broadcast_add, col_normalize_log_weights, col_normalize_log_weights_batch, replacement_sampling_weighted,
+    row_normalize_log_weights, row_normalize_log_weights_batch, reservoir_sampling_weighted, unique_with_indices,
+};
 
 fn bench_unique_with_indices(c: &mut Criterion) {
     let values: Vec<i32> = rand::thread_rng().sample_iter(Standard).take(10000).collect();
@@ -53,9 +56,19 @@ fn bench_broadcast(c: &mut Criterion) {
     c.bench_function("col_broadcast_add", |bh| bh.iter(|| col_broadcast_add(mat.clone(), &vec)));
 }
 
+fn bench_normalize_log_weights(c: &mut Criterion) {
+    let weights = DMatrix::<f64>::new_random(32, 10000);
+
+    c.bench_function("col_normalize_log_weights", |bh| bh.iter(|| col_normalize_log_weights(weights.clone())));
+    c.bench_function("col_normalize_log_weights_batch", |bh| bh.iter(|| col_normalize_log_weights_batch(weights.clone())));
+    c.bench_function("row_normalize_log_weights", |bh| bh.iter(|| row_normalize_log_weights(weights.clone())));
+    c.bench_function("row_normalize_log_weights_batch", |bh| bh.iter(|| row_normalize_log_weights_batch(weights.clone())));
+}
+
 criterion_group!(
     utils,
     bench_unique_with_indices,
     bench_sampling,
     bench_broadcast,
+    bench_normalize_log_weights,
 );
\ No newline at end of file
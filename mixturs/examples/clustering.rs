@@ -40,5 +40,5 @@ fn main() {
         x.clone_owned(),
         &fit_options,
         Some(callback),
-    );
+    ).unwrap();
 }
\ No newline at end of file
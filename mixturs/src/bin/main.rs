@@ -161,7 +161,7 @@ fn try_main() -> Result<(), Box<dyn std::error::Error>> {
         data.clone_owned(),
         &fit_options,
         Some(callback),
-    );
+    )?;
 
     let params = model.params();
     let mut colors = Vec::new();
@@ -163,4 +163,45 @@ impl<P: ThinParams> Callback<P> for MonitoringCallback<P> {
             println!("Run iteration {} in {:.2?}; {}", i, elapsed, measures);
         }
     }
-}
\ No newline at end of file
+}
+
+/// Callback invoked with a shared reference to the live model parameters every `every` iterations, e.g. to log
+/// progress or save intermediate models. It is called during the `every`-th, `2 * every`-th, ... iteration
+/// (0-based iterations `every - 1`, `2 * every - 1`, ...) once the clusters are updated, before any
+/// split/merge proposals are applied.
+///
+/// # Examples
+///
+/// ```
+/// use nalgebra::DMatrix;
+/// use mixturs::{FitOptions, Model, ModelOptions, NIW};
+/// use mixturs::callback::CheckpointCallback;
+/// use mixturs::state::GlobalState;
+///
+/// let x = DMatrix::new_random(2, 100);
+/// let mut model = Model::from_options(ModelOptions::<NIW>::default(2));
+///
+/// let mut n_clusters = Vec::new();
+/// let callback = CheckpointCallback::new(10, |_, state: &GlobalState<NIW>| n_clusters.push(state.clusters.len()));
+/// model.fit(x, &FitOptions::default(), Some(callback)).unwrap();
+/// assert_eq!(n_clusters.len(), 10);
+/// ```
+pub struct CheckpointCallback<F> {
+    every: usize,
+    f: F,
+}
+
+impl<F> CheckpointCallback<F> {
+    pub fn new(every: usize, f: F) -> Self {
+        assert!(every > 0, "Checkpoint interval must be positive");
+        Self { every, f }
+    }
+}
+
+impl<P: ThinParams, F: FnMut(usize, &P) + Send + Sync> Callback<P> for CheckpointCallback<F> {
+    fn during_step(&mut self, i: usize, params: &P) {
+        if (i + 1) % self.every == 0 {
+            (self.f)(i, params);
+        }
+    }
+}
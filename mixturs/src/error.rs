@@ -0,0 +1,66 @@
+use std::fmt;
+
+/// Errors raised while fitting a model.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// Mixture weights or the concentration parameters producing them are negative, non-finite or all zero.
+    DegenerateWeights,
+    /// A prior scale matrix or cluster covariance is not symmetric positive definite.
+    SingularCovariance,
+    /// There is no (non-outlier) cluster to fit.
+    EmptyCluster,
+    /// The dimensionality of the data does not match the model.
+    DimensionMismatch { expected: usize, got: usize },
+    /// The data does not contain any points.
+    EmptyData,
+    /// The data contains a NaN or infinite value.
+    NonFiniteData { feature: usize, point: usize },
+    /// The data or options are invalid for another reason.
+    InvalidData(String),
+    /// The number of covariances or weights does not match the number of components.
+    ComponentCountMismatch { expected: usize, got: usize },
+    /// The covariance of the given component is not symmetric positive definite.
+    NotPositiveDefinite { component: usize },
+    /// The weights of a point (column) sampled between the clusters are all zero, i.e. every cluster has
+    /// zero likelihood.
+    ZeroWeights { point: usize },
+    /// A weight of a point (column) sampled between the clusters is NaN or infinite.
+    InvalidWeight { point: usize, value: f64 },
+    /// Reading the data failed.
+    Io(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::DegenerateWeights => write!(f, "mixture weights are degenerate (negative, non-finite or all zero)"),
+            Error::SingularCovariance => write!(f, "covariance matrix is not positive definite"),
+            Error::EmptyCluster => write!(f, "at least one non-empty cluster is required"),
+            Error::DimensionMismatch { expected, got } => {
+                write!(f, "data dimension mismatch: expected {}, got {}", expected, got)
+            }
+            Error::EmptyData => write!(f, "data does not contain any points"),
+            Error::NonFiniteData { feature, point } => {
+                write!(f, "non-finite value at feature {} of point {}", feature, point)
+            }
+            Error::InvalidData(reason) => write!(f, "invalid data: {}", reason),
+            Error::ComponentCountMismatch { expected, got } => {
+                write!(f, "component count mismatch: expected {}, got {}", expected, got)
+            }
+            Error::NotPositiveDefinite { component } => {
+                write!(f, "covariance of component {} is not positive definite", component)
+            }
+            Error::ZeroWeights { point } => write!(f, "weights of point {} sum to zero", point),
+            Error::InvalidWeight { point, value } => write!(f, "point {} has an invalid weight {}", point, value),
+            Error::Io(reason) => write!(f, "io error: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e.to_string())
+    }
+}
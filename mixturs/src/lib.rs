@@ -1,20 +1,23 @@
 extern crate core;
 
 pub mod utils;
+pub mod error;
 pub mod model;
 pub mod metrics;
 pub mod stats;
 pub mod state;
 pub mod params;
+pub mod testing;
 #[cfg(not(tarpaulin_include))]
 pub mod callback;
 #[cfg(not(tarpaulin_include))]
 #[cfg(feature = "plot")]
 pub mod plotting;
 
-pub use model::Model;
-pub use params::{FitOptions, ModelOptions};
+pub use error::Error;
+pub use model::{Model, ModelF32, FitResult, Assignments, StopReason};
+pub use params::{FitOptions, FitOptionsBuilder, ModelOptions, ModelOptionsBuilder};
 pub use callback::MonitoringCallback;
 pub use metrics::{NMI, AIC, BIC};
-pub use stats::{NIW};
+pub use stats::{NIW, DiagNIW, AcceptanceStats, SplitMergeEvent, SplitMergeKind};
 
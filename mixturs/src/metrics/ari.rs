@@ -0,0 +1,64 @@
+use std::hash::Hash;
+use crate::metrics::contingency_matrix;
+
+fn n_pairs(n: usize) -> f64 {
+    (n * n.saturating_sub(1)) as f64 / 2.0
+}
+
+/// Computes the Adjusted Rand Index between two clusterings. The Rand Index counts the pairs of points that are
+/// assigned consistently (same or different cluster) in both clusterings; the adjusted index corrects it for
+/// chance, so random labelings score close to 0 and identical clusterings (up to a permutation) score 1.
+///
+/// # Arguments:
+///
+/// * `labels_true`: The true labels of the data.
+/// * `labels_pred`: The predicted labels
+///
+/// # Example:
+/// ```
+/// use statrs::assert_almost_eq;
+/// use mixturs::metrics::adjusted_rand_score;
+///
+/// let labels_true = vec![0, 0, 1, 1];
+/// let labels_pred = vec![1, 1, 0, 0];
+///
+/// assert_almost_eq!(adjusted_rand_score(&labels_true, &labels_pred), 1.0, 1e-12);
+/// ```
+pub fn adjusted_rand_score<T: Copy + Hash + Eq + Ord>(
+    labels_true: &[T],
+    labels_pred: &[T],
+) -> f64 {
+    assert_eq!(labels_true.len(), labels_pred.len(), "Label vectors must have the same length");
+
+    let contingency = contingency_matrix(labels_true, labels_pred);
+    let n_cols = contingency.first().map_or(0, |row| row.len());
+    let row_pairs: f64 = contingency.iter().map(|row| n_pairs(row.iter().sum())).sum();
+    let col_pairs: f64 = (0..n_cols).map(|c| n_pairs(contingency.iter().map(|row| row[c]).sum())).sum();
+    let index: f64 = contingency.iter().flatten().map(|&n| n_pairs(n)).sum();
+
+    let expected = row_pairs * col_pairs / n_pairs(labels_true.len()).max(1.0);
+    let max_index = (row_pairs + col_pairs) / 2.0;
+    if max_index == expected {
+        // Both clusterings are trivial (a single cluster or all singletons)
+        return 1.0;
+    }
+
+    (index - expected) / (max_index - expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use statrs::assert_almost_eq;
+    use super::*;
+
+    #[test]
+    fn test_adjusted_rand_score() {
+        // Reference value from sklearn.metrics.adjusted_rand_score
+        let labels_true = vec![0, 0, 1, 1];
+        let labels_pred = vec![0, 0, 1, 2];
+        assert_almost_eq!(adjusted_rand_score(&labels_true, &labels_pred), 0.5714285714285715, 1e-12);
+
+        let labels_pred = vec![0, 1, 0, 1];
+        assert!(adjusted_rand_score(&labels_true, &labels_pred) < 0.0);
+    }
+}
@@ -0,0 +1,115 @@
+use nalgebra::{DMatrix, DVector};
+
+/// Smallest predicted probability used in the logarithm, so zero-probability entries have a finite cost.
+const PROB_EPSILON: f64 = 1e-12;
+
+/// Computes the mean cross-entropy between predicted and true soft labels, `-1/n sum_i sum_k t_ik ln(p_ik)`.
+/// Each row holds the class probabilities of a sample, so both matrices must be row-normalized.
+/// Predicted probabilities are clamped to a small epsilon to handle zero-probability entries.
+///
+/// # Arguments:
+///
+/// * `pred_proba`: The predicted class probabilities. (n_samples, n_classes)
+/// * `true_proba`: The true class probabilities. (n_samples, n_classes)
+///
+/// # Returns:
+///
+/// The mean cross-entropy (in nats). It is minimal (the mean entropy of the true labels) when the
+/// predictions match the true probabilities.
+///
+/// # Example:
+/// ```
+/// use nalgebra::DMatrix;
+/// use statrs::assert_almost_eq;
+/// use mixturs::metrics::soft_cross_entropy;
+///
+/// let proba = DMatrix::from_row_slice(2, 2, &[1.0, 0.0, 0.5, 0.5]);
+/// assert_almost_eq!(soft_cross_entropy(&proba, &proba), 0.5 * 2.0f64.ln(), 1e-9);
+/// ```
+pub fn soft_cross_entropy(
+    pred_proba: &DMatrix<f64>,
+    true_proba: &DMatrix<f64>,
+) -> f64 {
+    assert_eq!(pred_proba.shape(), true_proba.shape(), "Probability matrices must have the same shape");
+    for proba in [pred_proba, true_proba] {
+        assert!(
+            proba.row_iter().all(|row| (row.sum() - 1.0).abs() < 1e-6),
+            "Probability matrices must be row-normalized"
+        );
+    }
+
+    let total: f64 = pred_proba.iter().zip(true_proba.iter())
+        .map(|(p, t)| -t * p.max(PROB_EPSILON).ln())
+        .sum();
+
+    total / pred_proba.nrows().max(1) as f64
+}
+
+/// Computes the Shannon entropy (in nats) of the soft labels of each sample, `-sum_k p_ik ln(p_ik)`,
+/// taking `0 ln(0)` to be 0. Zero for a certain assignment, at most `ln(n_classes)`.
+///
+/// # Arguments:
+///
+/// * `proba`: The row-normalized class probabilities. (n_samples, n_classes)
+///
+/// # Returns:
+///
+/// The entropy of each sample. (n_samples)
+///
+/// # Example:
+/// ```
+/// use nalgebra::DMatrix;
+/// use statrs::assert_almost_eq;
+/// use mixturs::metrics::soft_label_entropy;
+///
+/// let proba = DMatrix::from_row_slice(2, 2, &[1.0, 0.0, 0.5, 0.5]);
+/// let entropy = soft_label_entropy(&proba);
+/// assert_almost_eq!(entropy[0], 0.0, 1e-12);
+/// assert_almost_eq!(entropy[1], 2.0f64.ln(), 1e-12);
+/// ```
+pub fn soft_label_entropy(proba: &DMatrix<f64>) -> DVector<f64> {
+    DVector::from_iterator(
+        proba.nrows(),
+        proba.row_iter().map(|row| -row.iter().filter(|&&p| p > 0.0).map(|p| p * p.ln()).sum::<f64>()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DMatrix;
+    use statrs::assert_almost_eq;
+    use super::soft_cross_entropy;
+
+    #[test]
+    fn test_soft_cross_entropy() {
+        let truth = DMatrix::from_row_slice(3, 3, &[
+            0.7, 0.2, 0.1,
+            0.1, 0.8, 0.1,
+            0.3, 0.3, 0.4,
+        ]);
+        let entropy = -truth.iter().map(|t| t * t.ln()).sum::<f64>() / 3.0;
+        assert_almost_eq!(soft_cross_entropy(&truth, &truth), entropy, 1e-12);
+
+        let mismatched = DMatrix::from_row_slice(3, 3, &[
+            0.1, 0.2, 0.7,
+            0.8, 0.1, 0.1,
+            0.4, 0.3, 0.3,
+        ]);
+        assert!(soft_cross_entropy(&mismatched, &truth) > entropy);
+
+        // Zero predicted probabilities are clamped
+        let hard = DMatrix::from_row_slice(3, 3, &[
+            0.0, 0.0, 1.0,
+            1.0, 0.0, 0.0,
+            0.0, 1.0, 0.0,
+        ]);
+        assert!(soft_cross_entropy(&hard, &truth).is_finite());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_soft_cross_entropy_not_normalized() {
+        let proba = DMatrix::from_row_slice(1, 2, &[0.5, 0.6]);
+        soft_cross_entropy(&proba, &proba);
+    }
+}
@@ -0,0 +1,140 @@
+use nalgebra::{DMatrix, DVector, RowDVector};
+use rand::Rng;
+
+/// Computes the inertia (within-cluster sum of squared distances to the cluster means) of a clustering.
+///
+/// # Arguments:
+///
+/// * `data`: The data points. (n_dim, n_points)
+/// * `labels`: The cluster label of each point. (n_points)
+///
+/// # Returns:
+///
+/// The within-cluster sum of squares.
+///
+/// # Example:
+/// ```
+/// use nalgebra::DMatrix;
+/// use statrs::assert_almost_eq;
+/// use mixturs::metrics::inertia;
+///
+/// let data = DMatrix::from_row_slice(1, 4, &[0.0, 2.0, 10.0, 12.0]);
+/// assert_almost_eq!(inertia(&data, &[0, 0, 1, 1]), 4.0, 1e-12);
+/// ```
+pub fn inertia(
+    data: &DMatrix<f64>,
+    labels: &[usize],
+) -> f64 {
+    assert_eq!(data.ncols(), labels.len());
+    let n_clusters = labels.iter().max().map_or(0, |&k| k + 1);
+
+    let mut sums = vec![DVector::zeros(data.nrows()); n_clusters];
+    let mut counts = vec![0usize; n_clusters];
+    for (point, &label) in data.column_iter().zip(labels) {
+        sums[label] += point;
+        counts[label] += 1;
+    }
+    for (sum, &count) in sums.iter_mut().zip(&counts) {
+        if count > 0 {
+            *sum /= count as f64;
+        }
+    }
+
+    data.column_iter().zip(labels)
+        .map(|(point, &label)| (point - &sums[label]).norm_squared())
+        .sum()
+}
+
+/// Computes the Gap statistic [1] to choose the number of clusters. The log inertia of the data clustered
+/// with `k` clusters is compared against its expectation under uniformly random reference datasets drawn
+/// from the bounding box of the data.
+///
+/// [1] R. Tibshirani, G. Walther, and T. Hastie, “Estimating the number of clusters in a data set via the gap statistic,” 2001.
+///
+/// # Arguments:
+///
+/// * `data`: The data points. (n_dim, n_points)
+/// * `max_clusters`: The largest number of clusters to evaluate (`k` ranges over `1..=max_clusters`).
+/// * `fit_fn`: Clusters the given data into `k` clusters and returns the label of each point.
+/// * `n_refs`: Number of reference datasets per cluster count.
+/// * `rng`: A random number generator.
+///
+/// # Returns:
+///
+/// Tuple containing:
+/// * The smallest `k` such that `gap(k) >= gap(k + 1) - s(k + 1)`
+/// * The gap statistic for each cluster count (index `k - 1`)
+pub fn gap_statistic(
+    data: &DMatrix<f64>,
+    max_clusters: usize,
+    fit_fn: impl Fn(&DMatrix<f64>, usize) -> RowDVector<usize>,
+    n_refs: usize,
+    rng: &mut impl Rng,
+) -> (usize, Vec<f64>) {
+    assert!(max_clusters > 0 && n_refs > 0);
+
+    let lo: Vec<f64> = data.row_iter().map(|row| row.min()).collect();
+    let hi: Vec<f64> = data.row_iter().map(|row| row.max()).collect();
+
+    let mut gaps = Vec::with_capacity(max_clusters);
+    let mut errors = Vec::with_capacity(max_clusters);
+    for k in 1..=max_clusters {
+        let log_w = inertia(data, fit_fn(data, k).as_slice()).ln();
+
+        let log_w_refs: Vec<f64> = (0..n_refs).map(|_| {
+            let reference = DMatrix::from_fn(data.nrows(), data.ncols(), |d, _| {
+                lo[d] + (hi[d] - lo[d]) * rng.gen::<f64>()
+            });
+            inertia(&reference, fit_fn(&reference, k).as_slice()).ln()
+        }).collect();
+
+        let mean = log_w_refs.iter().sum::<f64>() / n_refs as f64;
+        let std = (log_w_refs.iter().map(|w| (w - mean).powi(2)).sum::<f64>() / n_refs as f64).sqrt();
+
+        gaps.push(mean - log_w);
+        errors.push(std * (1.0 + 1.0 / n_refs as f64).sqrt());
+    }
+
+    let best = (0..max_clusters - 1)
+        .find(|&k| gaps[k] >= gaps[k + 1] - errors[k + 1])
+        .map_or(max_clusters, |k| k + 1);
+
+    (best, gaps)
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, RowDVector};
+    use rand::prelude::StdRng;
+    use rand::SeedableRng;
+    use crate::testing::make_blobs;
+    use crate::utils::{kmeans_plusplus, nearest_centers};
+
+    /// Labels the points by their nearest k-means++ seed, seeded per `k` so the fit is a plain function.
+    fn kmeans(data: &DMatrix<f64>, k: usize) -> RowDVector<usize> {
+        let centers = kmeans_plusplus(data, k, 5, &mut StdRng::seed_from_u64(k as u64));
+        RowDVector::from_vec(nearest_centers(data, &centers))
+    }
+
+    #[test]
+    fn test_inertia() {
+        let data = DMatrix::from_row_slice(2, 4, &[
+            0.0, 2.0, 10.0, 10.0,
+            0.0, 0.0, 1.0, 3.0,
+        ]);
+        assert_eq!(super::inertia(&data, &[0, 0, 1, 1]), 4.0);
+        assert_eq!(super::inertia(&data, &[0, 1, 2, 3]), 0.0);
+    }
+
+    #[test]
+    fn test_gap_statistic() {
+        let centers = DMatrix::from_column_slice(2, 3, &[0.0, 0.0, 10.0, 0.0, 0.0, 10.0]);
+        let (x, _) = make_blobs(50, &centers, 0.5, 42);
+
+        let mut rng = StdRng::seed_from_u64(42);
+        let (k, gaps) = super::gap_statistic(&x, 6, kmeans, 10, &mut rng);
+
+        assert_eq!(gaps.len(), 6);
+        assert_eq!(k, 3, "gaps: {:?}", gaps);
+    }
+}
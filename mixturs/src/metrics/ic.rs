@@ -1,8 +1,11 @@
 use std::collections::HashMap;
-use nalgebra::RowDVector;
+use nalgebra::{DMatrix, RowDVector};
 use statrs::statistics::Statistics;
+use crate::{Error, FitOptions, Model, ModelOptions, MonitoringCallback};
 use crate::metrics::{EvalData, Metric};
 use crate::params::thin::{hard_assignment, MixtureParams, SuperMixtureParams, ThinParams};
+use crate::state::GlobalState;
+use crate::stats::NormalConjugatePrior;
 
 /// `aic` computes the Akaike Information Criterion (AIC) for a model
 ///
@@ -61,6 +64,72 @@ pub fn bic(
     -2.0 * avg_log_likelihood * dim as f64 + n_params as f64 * (dim as f64).ln()
 }
 
+/// Information criterion used to compare models, see [`aic`] and [`bic`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InformationCriterion {
+    Aic,
+    Bic,
+}
+
+impl InformationCriterion {
+    /// Scores the model on the data points (columns). Lower is better.
+    pub fn score<P: ThinParams>(&self, data: &DMatrix<f64>, params: &P) -> f64 {
+        let log_likelihood = SuperMixtureParams(params).log_likelihood(data.clone_owned());
+        let avg_log_likelihood = log_likelihood.column_iter().map(|col| col.max()).mean();
+
+        match self {
+            InformationCriterion::Aic => aic(data.ncols(), params.n_params(), avg_log_likelihood),
+            InformationCriterion::Bic => bic(data.ncols(), params.n_params(), avg_log_likelihood),
+        }
+    }
+}
+
+/// Fits a model for each cluster count in `k_range` and selects the one with the lowest information
+/// criterion. Split/merge proposals are disabled so each model keeps its initial number of clusters
+/// (unless clusters become empty).
+///
+/// # Arguments:
+///
+/// * `data`: The data points. (n_dim, n_points)
+/// * `k_range`: The cluster counts to evaluate (used as [`FitOptions::init_clusters`]).
+/// * `criterion`: The information criterion to minimize.
+/// * `model_options`: Options of the fitted models.
+/// * `fit_options`: Options of each fit. `init_clusters` and `iter_split_stop` are overridden.
+///
+/// # Returns:
+///
+/// The cluster count of the best model (excluding the outlier cluster) and its global state.
+///
+/// # Errors
+///
+/// Returns an [`Error`] if any of the fits fails or `k_range` is empty.
+pub fn select_model_by_ic<P: NormalConjugatePrior>(
+    data: &DMatrix<f64>,
+    k_range: impl IntoIterator<Item=usize>,
+    criterion: InformationCriterion,
+    model_options: &ModelOptions<P>,
+    fit_options: &FitOptions,
+) -> Result<(usize, GlobalState<P>), Error> {
+    let mut best: Option<(f64, usize, GlobalState<P>)> = None;
+    for k in k_range {
+        let mut options = fit_options.clone();
+        options.init_clusters = k;
+        options.iter_split_stop = options.iters;
+
+        let mut model = Model::from_options(model_options.clone());
+        model.fit(data.clone_owned(), &options, None::<MonitoringCallback<GlobalState<P>>>)?;
+
+        let score = criterion.score(data, model.params());
+        if best.as_ref().map_or(true, |(best_score, _, _)| score < *best_score) {
+            let n_clusters = model.n_clusters() - model_options.cluster_offset();
+            best = Some((score, n_clusters, model.params().clone()));
+        }
+    }
+
+    best.map(|(_, k, global)| (k, global))
+        .ok_or_else(|| Error::InvalidData("k_range does not contain any cluster counts".to_string()))
+}
+
 /// Akaike Information Criterion measure
 pub struct AIC;
 
@@ -73,7 +142,7 @@ impl<P: ThinParams> Metric<P> for AIC {
         metrics: &mut HashMap<String, f64>
     ) {
         let log_likelihood = SuperMixtureParams(params).log_likelihood(data.points.clone_owned());
-        let mut labels = RowDVector::zeros(data.points.ncols());
+        let mut labels = RowDVector::<usize>::zeros(data.points.ncols());
         hard_assignment(&log_likelihood, labels.as_mut_slice());
 
         let avg_log_likelihood = log_likelihood.column_iter().map(|col| col.max()).mean();
@@ -95,7 +164,7 @@ impl<P: ThinParams> Metric<P> for BIC {
         metrics: &mut HashMap<String, f64>
     ) {
         let log_likelihood = SuperMixtureParams(params).log_likelihood(data.points.clone_owned());
-        let mut labels = RowDVector::zeros(data.points.ncols());
+        let mut labels = RowDVector::<usize>::zeros(data.points.ncols());
         hard_assignment(&log_likelihood, labels.as_mut_slice());
 
         let avg_log_likelihood = log_likelihood.column_iter().map(|col| col.max()).mean();
@@ -105,3 +174,30 @@ impl<P: ThinParams> Metric<P> for BIC {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use nalgebra::DMatrix;
+    use crate::{Error, FitOptions, ModelOptions, NIW};
+    use crate::metrics::{InformationCriterion, select_model_by_ic};
+    use crate::testing::make_blobs;
+
+    #[test]
+    fn test_select_model_by_ic() {
+        let centers = DMatrix::from_column_slice(2, 3, &[0.0, 0.0, 10.0, 0.0, 0.0, 10.0]);
+        let (x, _) = make_blobs(100, &centers, 1.0, 42);
+
+        let mut model_options = ModelOptions::<NIW>::default(2);
+        model_options.outlier = None;
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 30;
+
+        let (k, global) = select_model_by_ic(
+            &x, 1..=6, InformationCriterion::Bic, &model_options, &fit_options,
+        ).unwrap();
+        assert!((2..=4).contains(&k), "selected {} clusters", k);
+        assert_eq!(global.clusters.len(), k);
+
+        let empty = select_model_by_ic(&x, 3..3, InformationCriterion::Bic, &model_options, &fit_options);
+        assert!(matches!(empty, Err(Error::InvalidData(_))));
+    }
+}
@@ -0,0 +1,86 @@
+use nalgebra::DMatrix;
+use crate::params::thin::{MixtureParams, SuperMixtureParams, ThinParams};
+use crate::utils::log_sum_exp;
+
+/// Normalizing constant of Rissanen's universal code for integers.
+const UNIVERSAL_CODE_CONSTANT: f64 = 2.865064;
+
+/// Length (in nats) of Rissanen's universal code for a positive integer:
+/// `ln(c) + ln(k) + ln(ln(k)) + ...` summing the positive terms.
+fn universal_code_length(k: usize) -> f64 {
+    let mut length = UNIVERSAL_CODE_CONSTANT.ln();
+    let mut term = (k.max(1) as f64).ln();
+    while term > 0.0 {
+        length += term;
+        term = term.ln();
+    }
+    length
+}
+
+/// Computes the two-part minimum description length (MDL) of the data under the mixture model, in nats.
+/// Lower is better.
+///
+/// The total length is the cost of encoding the model plus the cost of encoding the data given the model:
+/// * The number of clusters is encoded with Rissanen's universal code for integers.
+/// * Each of the [`ThinParams::n_params`] real-valued parameters is encoded to precision `1 / sqrt(n)`,
+///   costing `0.5 * ln(n)` nats, where `n` is the number of data points.
+/// * The data is encoded with the mixture density, costing its negative log-likelihood.
+///
+/// # Arguments:
+///
+/// * `params`: The mixture model.
+/// * `data`: The data points. (n_dim, n_points)
+///
+/// # Returns:
+///
+/// The description length in nats.
+pub fn mdl<P: ThinParams>(
+    params: &P,
+    data: &DMatrix<f64>,
+) -> f64 {
+    let n_points = data.ncols() as f64;
+    let log_likelihood = SuperMixtureParams(params).log_likelihood(data.clone_owned());
+    let data_length: f64 = log_likelihood.column_iter().map(|col| -log_sum_exp(col.iter().cloned())).sum();
+
+    let model_length = universal_code_length(params.n_clusters())
+        + 0.5 * params.n_params() as f64 * n_points.ln();
+
+    model_length + data_length
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DMatrix;
+    use rand::prelude::StdRng;
+    use rand::SeedableRng;
+    use crate::{ModelOptions, NIW};
+    use crate::metrics::mdl;
+    use crate::state::GlobalState;
+    use crate::testing::make_blobs;
+
+    #[test]
+    fn test_mdl_prefers_simpler_model() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let centers = DMatrix::from_column_slice(2, 1, &[0.0, 0.0]);
+        let (x, _) = make_blobs(200, &centers, 1.0, 42);
+
+        let mut options = ModelOptions::<NIW>::default(2);
+        options.outlier = None;
+        let single = GlobalState::from_sklearn_params(
+            &DMatrix::zeros(1, 2), &[DMatrix::identity(2, 2)], &[1.0], &options, &mut rng,
+        ).unwrap();
+
+        // The extra cluster duplicates the first, so the data fits equally well
+        let double = GlobalState::from_sklearn_params(
+            &DMatrix::zeros(2, 2), &[DMatrix::identity(2, 2), DMatrix::identity(2, 2)], &[0.5, 0.5], &options, &mut rng,
+        ).unwrap();
+
+        assert!(mdl(&single, &x) < mdl(&double, &x));
+    }
+
+    #[test]
+    fn test_universal_code_length() {
+        assert!(super::universal_code_length(1) > 0.0);
+        assert!(super::universal_code_length(2) < super::universal_code_length(100));
+    }
+}
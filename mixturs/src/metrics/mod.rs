@@ -1,12 +1,22 @@
 use std::collections::HashMap;
 pub use nmi::*;
 pub use ic::*;
+pub use gap::*;
+pub use mdl::*;
+pub use cross_entropy::*;
+pub use v_measure::*;
+pub use ari::*;
 use crate::callback::EvalData;
 use crate::params::thin::ThinParams;
 
 
 mod nmi;
 mod ic;
+mod gap;
+mod mdl;
+mod cross_entropy;
+mod v_measure;
+mod ari;
 
 
 pub trait Metric<P: ThinParams>: Send + Sync {
@@ -0,0 +1,105 @@
+use std::hash::Hash;
+use crate::metrics::{contingency_matrix, entropy};
+
+/// Computes the conditional entropy `H(A|B)` of the contingency table, where `A` indexes the rows and `B` the columns.
+fn conditional_entropy(contingency: &[Vec<usize>]) -> f64 {
+    let n_cols = contingency.first().map_or(0, |row| row.len());
+    let col_sums: Vec<usize> = (0..n_cols)
+        .map(|c| contingency.iter().map(|row| row[c]).sum())
+        .collect();
+    let total = col_sums.iter().sum::<usize>() as f64;
+
+    let mut result = 0.0;
+    for row in contingency {
+        for (&n, &n_col) in row.iter().zip(&col_sums) {
+            if n > 0 {
+                result -= n as f64 / total * (n as f64 / n_col as f64).ln();
+            }
+        }
+    }
+
+    result
+}
+
+/// Computes the homogeneity, completeness and V-measure of a clustering given the true labels.
+/// A clustering is homogeneous if each cluster contains only members of a single class, and complete if
+/// all members of a class are assigned to the same cluster. The V-measure is their harmonic mean.
+///
+/// # Arguments:
+///
+/// * `labels_pred`: The predicted labels
+/// * `labels_true`: The true labels of the data.
+///
+/// # Returns:
+///
+/// A tuple of (homogeneity, completeness, v_measure), each in the range [0, 1].
+///
+/// # Example:
+/// ```
+/// use statrs::assert_almost_eq;
+/// use mixturs::metrics::v_measure;
+///
+/// let labels_true = vec![0, 0, 1, 1];
+/// let labels_pred = vec![0, 0, 1, 1];
+///
+/// let (h, c, v) = v_measure(&labels_pred, &labels_true);
+/// assert_almost_eq!(h, 1.0, 1e-12);
+/// assert_almost_eq!(c, 1.0, 1e-12);
+/// assert_almost_eq!(v, 1.0, 1e-12);
+/// ```
+pub fn v_measure<T: Copy + Hash + Eq + Ord>(
+    labels_pred: &[T],
+    labels_true: &[T],
+) -> (f64, f64, f64) {
+    assert_eq!(labels_pred.len(), labels_true.len(), "Label vectors must have the same length");
+    if labels_true.is_empty() {
+        return (1.0, 1.0, 1.0);
+    }
+
+    let contingency = contingency_matrix(labels_true, labels_pred);
+    let transposed: Vec<Vec<usize>> = (0..contingency[0].len())
+        .map(|c| contingency.iter().map(|row| row[c]).collect())
+        .collect();
+
+    let h_true = entropy(labels_true).unwrap();
+    let h_pred = entropy(labels_pred).unwrap();
+
+    let homogeneity = if h_true == 0.0 { 1.0 } else { 1.0 - conditional_entropy(&contingency) / h_true };
+    let completeness = if h_pred == 0.0 { 1.0 } else { 1.0 - conditional_entropy(&transposed) / h_pred };
+    let v_measure = if homogeneity + completeness == 0.0 {
+        0.0
+    } else {
+        2.0 * homogeneity * completeness / (homogeneity + completeness)
+    };
+
+    (homogeneity, completeness, v_measure)
+}
+
+#[cfg(test)]
+mod tests {
+    use statrs::assert_almost_eq;
+    use super::*;
+
+    #[test]
+    fn test_v_measure_homogeneous_incomplete() {
+        // Every cluster is pure, but each class is spread over two clusters
+        let labels_true = vec![0, 0, 1, 1];
+        let labels_pred = vec![0, 1, 2, 3];
+
+        let (h, c, v) = v_measure(&labels_pred, &labels_true);
+        assert_almost_eq!(h, 1.0, 1e-12);
+        assert!(c < 1.0);
+        assert_almost_eq!(c, 0.5, 1e-12);
+        assert_almost_eq!(v, 2.0 / 3.0, 1e-12);
+    }
+
+    #[test]
+    fn test_v_measure_complete_inhomogeneous() {
+        let labels_true = vec![0, 1, 2, 3];
+        let labels_pred = vec![0, 0, 1, 1];
+
+        let (h, c, _) = v_measure(&labels_pred, &labels_true);
+        assert_almost_eq!(h, 0.5, 1e-12);
+        assert_almost_eq!(c, 1.0, 1e-12);
+    }
+}
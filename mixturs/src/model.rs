@@ -1,11 +1,106 @@
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
 use std::thread::available_parallelism;
-use nalgebra::{DMatrix, RowDVector};
+use std::time::Instant;
+use nalgebra::{DMatrix, DMatrixSlice, DVector, RowDVector};
 use rand::prelude::*;
-use crate::callback::{Callback};
-use crate::params::options::{FitOptions, ModelOptions};
-use crate::params::thin::{MixtureParams, SuperMixtureParams};
-use crate::state::{GlobalState, GlobalWorker, LocalState, LocalWorker, ShardedState};
-use crate::stats::NormalConjugatePrior;
+use statrs::distribution::Normal;
+use statrs::function::gamma::ln_gamma;
+use crate::callback::{Callback, MonitoringCallback};
+use crate::error::Error;
+use crate::metrics::soft_label_entropy;
+use crate::params::options::{AuxInit, FitOptions, InitStrategy, ModelOptions, StopCriteria};
+use crate::params::thin::{MixtureParams, soft_assignment, SuperMixtureParams};
+use crate::state::{BatchedState, GlobalState, GlobalWorker, LocalState, LocalWorker, ShardedState, validate_frozen, validate_weights};
+use crate::stats::{AcceptanceStats, BORROWED_CHUNK_SIZE, Covariance, NormalConjugatePrior, PriorHyperParams, SplitMergeEvent};
+use crate::utils::{argmax, bimodality_coefficient, CsvChunks, DataScalar, f64_column_blocks, log_sum_exp, sample_normalized, unique_columns, validate_data};
+
+/// Reason the fit loop stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// All [`FitOptions::iters`] iterations have run.
+    MaxIters,
+    /// The [`StopCriteria::time_budget`] was exhausted.
+    TimeBudget,
+    /// Fewer labels than [`StopCriteria::label_change_tol`] changed.
+    LabelChange,
+    /// The log-likelihood improved by less than [`FitOptions::tol`] for [`FitOptions::patience`]
+    /// consecutive iterations.
+    LogLikelihoodPlateau,
+}
+
+impl Default for StopReason {
+    fn default() -> Self {
+        StopReason::MaxIters
+    }
+}
+
+/// Diagnostics collected while fitting the model.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FitResult {
+    /// Snapshots of the primary labels recorded after burn-in. (n_samples, n_points)
+    ///
+    /// Only recorded if [`FitOptions::record_label_trace`] is set.
+    pub label_trace: Option<DMatrix<usize>>,
+    /// Number of points held out for validation.
+    ///
+    /// Only set if [`FitOptions::validation_fraction`] is set.
+    pub validation_points: usize,
+    /// Mean predictive log-likelihood of the held-out points under the fitted mixture.
+    ///
+    /// Only set if [`FitOptions::validation_fraction`] is set.
+    pub validation_log_likelihood: Option<f64>,
+    /// Why the fit loop stopped.
+    pub stop_reason: StopReason,
+    /// Number of proposed and accepted split/merge moves during the fit.
+    pub acceptance: AcceptanceStats,
+    /// Total log-likelihood of the model after each iteration: the log-likelihood of the points under their
+    /// assigned clusters plus the log prior of the partition (see [`log_partition_prior`]).
+    ///
+    /// Only recorded if [`FitOptions::record_ll_trace`] is set.
+    pub log_likelihood_trace: Vec<f64>,
+    /// Number of clusters after each iteration.
+    pub n_clusters_trace: Vec<usize>,
+    /// Number of iterations that were run, less than [`FitOptions::iters`] if the fit stopped early.
+    pub n_iters: usize,
+    /// Responsibilities of the clusters for each point under the cluster distributions of the last sampling
+    /// sweep, normalized to sum to one. (n_points, n_clusters)
+    ///
+    /// Only returned if [`FitOptions::return_responsibilities`] is set.
+    pub responsibilities: Option<DMatrix<f64>>,
+    /// Split/merge proposals in the order they were made.
+    ///
+    /// Only recorded if [`FitOptions::record_split_merge_events`] is set.
+    pub split_merge_events: Vec<SplitMergeEvent>,
+}
+
+/// Moves the split/merge events recorded by the global state into `events`, tagged with the iteration.
+fn take_split_merge_events<P: NormalConjugatePrior>(global: &mut GlobalState<P>, iter: usize, events: &mut Vec<SplitMergeEvent>) {
+    if let Some(recorded) = &mut global.split_merge_events {
+        events.extend(recorded.drain(..).map(|event| SplitMergeEvent { iter, ..event }));
+    }
+}
+
+/// Log prior probability of a partition of the points into clusters of the given sizes under a
+/// Dirichlet process (Chinese restaurant process) with concentration `alpha`. Empty clusters are ignored.
+///
+/// # Example
+/// ```
+/// use mixturs::model::log_partition_prior;
+///
+/// // A single point always forms a single cluster
+/// assert_eq!(log_partition_prior(&[1], 2.0), 0.0);
+/// ```
+pub fn log_partition_prior(counts: &[usize], alpha: f64) -> f64 {
+    let n_points: usize = counts.iter().sum();
+    let (n_clusters, ln_sizes) = counts.iter()
+        .filter(|&&n| n > 0)
+        .fold((0.0, 0.0), |(k, acc), &n| (k + 1.0, acc + ln_gamma(n as f64)));
+
+    n_clusters * alpha.ln() + ln_sizes + ln_gamma(alpha) - ln_gamma(alpha + n_points as f64)
+}
 
 /// Dirichlet Process Mixture Model (DPMM) Sub-Clusters model introduced in
 /// [1] and [2].
@@ -33,21 +128,59 @@ use crate::stats::NormalConjugatePrior;
 ///     x.clone_owned(),
 ///     &fit_options,
 ///     Some(callback)
-/// );
+/// ).unwrap();
 /// ```
 pub struct Model<
     P: NormalConjugatePrior,
+    T: DataScalar = f64,
 > {
     global: Option<GlobalState<P>>,
     model_options: ModelOptions<P>,
+    local: Option<LocalState<P, usize, T>>,
+    fit_options: Option<FitOptions>,
 }
 
+/// Model storing the data it is fitted on as `f32`, halving the memory used by the data matrix.
+pub type ModelF32<P> = Model<P, f32>;
+
 impl<P: NormalConjugatePrior> Model<P> {
     /// Create a new model from a set of model options.
     pub fn from_options(model_options: ModelOptions<P>) -> Self {
+        Self::new(model_options)
+    }
+
+    /// Create a model from an already known global state, as if it was fitted.
+    #[cfg(test)]
+    pub(crate) fn from_global(model_options: ModelOptions<P>, global: GlobalState<P>) -> Self {
+        Self {
+            global: Some(global),
+            ..Self::new(model_options)
+        }
+    }
+}
+
+impl<P: NormalConjugatePrior, T: DataScalar> Model<P, T> {
+    /// Create a new model storing the data it is fitted on as `T` from a set of model options.
+    /// Prediction always takes `f64` data.
+    ///
+    /// # Example
+    /// ```
+    /// use nalgebra::DMatrix;
+    /// use mixturs::{FitOptions, ModelF32, ModelOptions, MonitoringCallback, NIW};
+    /// use mixturs::state::GlobalState;
+    ///
+    /// let x = DMatrix::<f64>::new_random(2, 100);
+    /// let mut model = ModelF32::new(ModelOptions::<NIW>::default(2));
+    /// model.fit(x.map(|v| v as f32), &FitOptions::default(), None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+    /// let (_, labels) = model.predict(x);
+    /// assert_eq!(labels.len(), 100);
+    /// ```
+    pub fn new(model_options: ModelOptions<P>) -> Self {
         Self {
             global: None,
             model_options,
+            local: None,
+            fit_options: None,
         }
     }
 
@@ -60,6 +193,11 @@ impl<P: NormalConjugatePrior> Model<P> {
         }
     }
 
+    /// Dimensionality of the data the model is fitted on.
+    pub fn dim(&self) -> usize {
+        self.model_options.dim
+    }
+
     /// Check whether the model is already fitted.
     pub fn is_fitted(&self) -> bool {
         self.global.is_some()
@@ -67,12 +205,25 @@ impl<P: NormalConjugatePrior> Model<P> {
 
     /// Fit the model to the data.
     ///
+    /// The data is taken by value and moved into the local state without copying, so fitting large
+    /// datasets does not require twice the memory. Pass `data.clone()` to keep using the data
+    /// afterwards, or set [`FitOptions::keep_local`] to recover it from the local state with [`Model::into_parts`].
+    /// Only single worker fits without validation hold-out keep the original buffer; sharding and
+    /// hold-out split the data into new matrices.
+    ///
     /// # Arguments
     ///
-    /// * `data`: The data to fit the model to.
+    /// * `data`: The data to fit the model to. (n_features, n_samples)
     /// * `fit_options`: Options for the fitting procedure.
     /// * `callback`: Callback function to monitor the fitting procedure.
     ///
+    /// # Errors
+    ///
+    /// Returns an [`Error`] if the number of data rows does not match `ModelOptions::dim`, the data is
+    /// empty or contains non-finite values, no initial clusters are requested, constraints are combined
+    /// with more than one worker, the model options are improper or the mixture weights degenerate while
+    /// sampling.
+    ///
     /// # Examples
     ///
     /// ```
@@ -95,30 +246,333 @@ impl<P: NormalConjugatePrior> Model<P> {
     ///    x.clone_owned(),
     ///   &fit_options,
     ///  Some(callback)
-    /// );
+    /// ).unwrap();
+    /// ```
+    ///
+    /// The data is moved into the model and can no longer be used by the caller:
+    ///
+    /// ```compile_fail
+    /// use nalgebra::DMatrix;
+    /// use mixturs::{FitOptions, Model, ModelOptions, MonitoringCallback, NIW};
+    /// use mixturs::state::GlobalState;
+    ///
+    /// let x = DMatrix::new_random(2, 100);
+    /// let mut model = Model::from_options(ModelOptions::<NIW>::default(2));
+    /// model.fit(x, &FitOptions::default(), None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+    /// println!("{}", x.ncols());
     /// ```
     pub fn fit(
         &mut self,
-        data: DMatrix<f64>,
+        data: DMatrix<T>,
         fit_options: &FitOptions,
         callback: Option<impl Callback<GlobalState<P>>>,
-    ) {
+    ) -> Result<FitResult, Error> {
+        validate_data(&data, Some(self.dim()))?;
+        if fit_options.init_clusters == 0 {
+            return Err(Error::EmptyCluster);
+        }
+        validate_frozen(fit_options.frozen.as_deref(), data.ncols())?;
+        validate_weights(fit_options.weights.as_deref(), data.ncols())?;
+        if fit_options.constraints.is_some() && !matches!(fit_options.workers, 0 | 1) {
+            return Err(Error::InvalidData("constraints require a single worker".to_string()));
+        }
+
         let mut rng = SmallRng::seed_from_u64(fit_options.seed);
-        match fit_options.workers {
+
+        // Hold out validation points
+        let (data, validation, frozen, weights) = match fit_options.validation_fraction {
+            Some(fraction) => {
+                if !(0.0..1.0).contains(&fraction) {
+                    return Err(Error::InvalidData(format!("validation fraction {} is not in [0, 1)", fraction)));
+                }
+                if fit_options.constraints.is_some() {
+                    return Err(Error::InvalidData("validation fraction cannot be combined with constraints".to_string()));
+                }
+
+                let n_points = data.ncols();
+                let n_validation = ((fraction * n_points as f64).round() as usize).min(n_points - 1);
+                let mut is_validation = vec![false; n_points];
+                for i in rand::seq::index::sample(&mut rng, n_points, n_validation) {
+                    is_validation[i] = true;
+                }
+
+                let (validation_idx, train_idx): (Vec<usize>, Vec<usize>) = (0..n_points)
+                    .partition(|&i| is_validation[i]);
+                let frozen = fit_options.frozen.as_ref().map(|frozen| train_idx.iter().map(|&i| frozen[i]).collect());
+                let weights = fit_options.weights.as_ref().map(|weights| train_idx.iter().map(|&i| weights[i]).collect());
+                (data.select_columns(&train_idx), Some(data.select_columns(&validation_idx)), frozen, weights)
+            }
+            None => (data, None, fit_options.frozen.clone(), fit_options.weights.clone()),
+        };
+
+        // Fit the unique points weighted by their multiplicity, their labels are expanded back afterwards
+        let (data, weights, duplicates) = if fit_options.dedup_init {
+            if fit_options.constraints.is_some() || fit_options.frozen.is_some() {
+                return Err(Error::InvalidData("dedup_init cannot be combined with constraints or frozen points".to_string()));
+            }
+
+            let (unique, index) = unique_columns(&data);
+            let mut counts = vec![0.0; unique.len()];
+            for (i, &u) in index.iter().enumerate() {
+                counts[u] += weights.as_ref().map_or(1.0, |weights| weights[i]);
+            }
+            (data.select_columns(&unique), Some(counts), Some((data, weights, index)))
+        } else {
+            (data, weights, None)
+        };
+
+        let (mut result, mut local) = match fit_options.workers {
             0 | 1 => {
                 let mut local = LocalState::from_data(data);
+                local.apply_fit_options(fit_options);
+                local.weights = weights.map(DVector::from_vec);
                 local.init(fit_options.init_clusters, &mut rng);
+                local.frozen = frozen;
 
-                self.fit_worker(&mut local, fit_options, callback);
+                (self.fit_worker(&mut local, fit_options, callback)?, local)
             },
             workers => {
-                let workers = if workers < 0 { available_parallelism().unwrap().get() as i32 } else { workers };
-                let mut local = ShardedState::from_data(data, workers as usize);
+                let workers = if workers < 0 { available_parallelism().map_or(1, |n| n.get()) as i32 } else { workers };
+                let mut local = ShardedState::<P, usize, T>::from_data(data, workers as usize);
+                for shard in &mut local.shards {
+                    shard.apply_fit_options(fit_options);
+                }
                 local.init(fit_options.init_clusters, &mut rng);
+                let mut offset = 0;
+                for shard in &mut local.shards {
+                    let range = offset..offset + shard.n_points();
+                    shard.frozen = frozen.as_ref().map(|frozen| frozen[range.clone()].to_vec());
+                    shard.weights = weights.as_ref().map(|weights| DVector::from_column_slice(&weights[range]));
+                    offset += shard.n_points();
+                }
 
-                self.fit_worker(&mut local, fit_options, callback);
+                (self.fit_worker(&mut local, fit_options, callback)?, local.into_local(fit_options))
             }
+        };
+
+        if let Some((data, weights, index)) = duplicates {
+            local.labels = RowDVector::from_iterator(index.len(), index.iter().map(|&u| local.labels[u]));
+            local.labels_aux = RowDVector::from_iterator(index.len(), index.iter().map(|&u| local.labels_aux[u]));
+            local.data = data;
+            local.weights = weights.map(DVector::from_vec);
+            result.label_trace = result.label_trace.map(|trace| trace.select_columns(&index));
+            result.responsibilities = result.responsibilities.map(|probs| probs.select_rows(&index));
+        }
+
+        if let Some(validation) = validation {
+            result.validation_points = validation.ncols();
+            result.validation_log_likelihood = Some(self.mean_log_likelihood(T::as_f64(&validation).into_owned()));
+        }
+
+        self.local = fit_options.keep_local.then_some(local);
+        self.fit_options = Some(fit_options.clone());
+        Ok(result)
+    }
+
+    /// Continue fitting from a previously fitted (or checkpointed) global state, e.g. one restored with
+    /// serde. The labels of the data are initialized by predicting them with `global`, after which the
+    /// sampler runs for another [`FitOptions::iters`] iterations as with [`FitOptions::reuse`].
+    ///
+    /// The state of the random number generator used by the original fit is not stored, so the
+    /// resumed chain is seeded from [`FitOptions::seed`] and the split/merge step counter of `global`
+    /// instead. Resuming is deterministic, but does not reproduce an uninterrupted fit exactly.
+    ///
+    /// # Arguments
+    ///
+    /// * `global`: The global state to resume from.
+    /// * `data`: The data to fit the model to. (n_features, n_samples)
+    /// * `fit_options`: Options for the remaining iterations.
+    /// * `callback`: Callback function to monitor the fitting procedure.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error`] if the data or the clusters of `global` do not match `ModelOptions::dim`,
+    /// the data is empty or contains non-finite values or the mixture weights degenerate while sampling.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use nalgebra::DMatrix;
+    /// use mixturs::{FitOptions, Model, ModelOptions, MonitoringCallback, NIW};
+    /// use mixturs::state::GlobalState;
+    ///
+    /// let x = DMatrix::new_random(2, 100);
+    /// let mut model = Model::from_options(ModelOptions::<NIW>::default(2));
+    /// let fit_options = FitOptions::default();
+    /// model.fit(x.clone_owned(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+    ///
+    /// let checkpoint = model.params().clone();
+    /// let mut resumed = Model::from_options(ModelOptions::<NIW>::default(2));
+    /// resumed.fit_resume(checkpoint, x, &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+    /// assert!(resumed.is_fitted());
+    /// ```
+    pub fn fit_resume(
+        &mut self,
+        global: GlobalState<P>,
+        data: DMatrix<T>,
+        fit_options: &FitOptions,
+        callback: Option<impl Callback<GlobalState<P>>>,
+    ) -> Result<FitResult, Error> {
+        validate_data(&data, Some(self.dim()))?;
+        if let Some(cluster) = global.clusters.iter().find(|c| c.prim.post.dim() != self.dim()) {
+            return Err(Error::DimensionMismatch { expected: self.dim(), got: cluster.prim.post.dim() });
+        }
+        validate_frozen(fit_options.frozen.as_deref(), data.ncols())?;
+        validate_weights(fit_options.weights.as_deref(), data.ncols())?;
+
+        let seed = fit_options.seed ^ global.split_rng.counter.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let mut labels = Vec::with_capacity(data.ncols());
+        for (_, block) in f64_column_blocks(&data) {
+            labels.extend(global.predict(&block, &self.model_options).iter().cloned());
+        }
+
+        let n_points = labels.len();
+        let mut local = LocalState::<P, usize, T>::from_data(data);
+        local.labels = RowDVector::from_vec(labels);
+        local.labels_aux = RowDVector::from_fn(n_points, |_, _| rng.gen_range(0..2));
+        local.apply_fit_options(fit_options);
+        local.frozen = fit_options.frozen.clone();
+        local.weights = fit_options.weights.clone().map(DVector::from_vec);
+        if local.aux_init == AuxInit::RandomHyperplane {
+            local.apply_hyperplane_aux_labels(&(0..global.clusters.len()).collect::<Vec<_>>(), &mut rng);
+        }
+
+        let mut options = fit_options.clone();
+        options.reuse = true;
+        options.seed = seed;
+        self.global = Some(global);
+        let result = self.fit_worker(&mut local, &options, callback)?;
+
+        self.local = fit_options.keep_local.then_some(local);
+        self.fit_options = Some(fit_options.clone());
+        Ok(result)
+    }
+
+    /// Fit the model to a CSV file with one data point per row, reading it in chunks of `batch_rows` rows
+    /// so the full data matrix is never materialized. See [`Model::fit_csv_reader`].
+    pub fn fit_csv(
+        &mut self,
+        path: impl AsRef<Path>,
+        batch_rows: usize,
+        fit_options: &FitOptions,
+    ) -> Result<FitResult, Error> {
+        let path = path.as_ref();
+        self.fit_csv_reader(|| File::open(path).map(BufReader::new), batch_rows, fit_options)
+    }
+
+    /// Fit the model to CSV data with one data point per row, read in chunks of `batch_rows` rows
+    /// (see [`CsvChunks`] for the accepted format). The chunks are the mini-batches of [`Model::fit_batches`],
+    /// so the fit covers all the data while only a single chunk is kept in memory. Every pass over the data
+    /// reads it from a fresh reader returned by `open`.
+    ///
+    /// # Arguments
+    ///
+    /// * `open`: Opens the CSV data from the start.
+    /// * `batch_rows`: Number of rows (points) per chunk.
+    /// * `fit_options`: Options for the fitting procedure.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error`] if the data cannot be read, the rows have inconsistent column counts,
+    /// the data is empty or the fit fails.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io::Cursor;
+    /// use mixturs::{FitOptions, Model, ModelOptions, NIW};
+    ///
+    /// let csv = "x,y\n0.0,0.1\n0.2,0.0\n5.0,5.1\n5.2,4.9\n";
+    /// let mut model = Model::from_options(ModelOptions::<NIW>::default(2));
+    /// model.fit_csv_reader(|| Ok(Cursor::new(csv)), 2, &FitOptions::default()).unwrap();
+    /// ```
+    pub fn fit_csv_reader<R: BufRead>(
+        &mut self,
+        mut open: impl FnMut() -> std::io::Result<R>,
+        batch_rows: usize,
+        fit_options: &FitOptions,
+    ) -> Result<FitResult, Error> {
+        // The first read error ends the pass and is returned once the fit stops
+        let error = RefCell::new(None);
+        let error_ref = &error;
+        let result = self.fit_batches(move || {
+            let chunks = match open() {
+                Ok(reader) => Some(CsvChunks::new(reader, batch_rows)),
+                Err(e) => {
+                    error_ref.borrow_mut().get_or_insert(Error::from(e));
+                    None
+                }
+            };
+            chunks.into_iter().flatten().map_while(move |chunk| match chunk {
+                Ok(chunk) => Some(chunk),
+                Err(e) => {
+                    error_ref.borrow_mut().get_or_insert(e);
+                    None
+                }
+            })
+        }, fit_options);
+
+        match error.into_inner() {
+            Some(e) => Err(e),
+            None => result,
+        }
+    }
+
+    /// Fit the model to data that does not fit in memory at once, streamed as mini-batches of points.
+    /// The batches are fitted as a [`BatchedState`] with the same sampler as [`Model::fit`], so the fit follows
+    /// it on the concatenated data. Only the labels of the points are kept in memory, the data is re-read
+    /// whenever the sampler needs the points: once per iteration for the sampling sweep and the cluster
+    /// statistics, and again after clusters are split or reset.
+    ///
+    /// The initial labels are always drawn at random (see [`InitStrategy::Random`]).
+    ///
+    /// # Arguments
+    ///
+    /// * `batches`: Called at the start of every pass over the data to iterate over the batches
+    /// (n_features, n_points), e.g. by re-reading them from disk. Every pass must yield the same points.
+    /// * `fit_options`: Options for the fitting procedure. Constraints, frozen points, weights, a
+    /// validation fraction and other initialization strategies are not supported.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error`] if a batch is invalid (see [`validate_data`]), there are no batches, a pass
+    /// yields different batches than the first, unsupported options are set or the fit fails.
+    ///
+    /// # Example
+    /// ```
+    /// use nalgebra::DMatrix;
+    /// use mixturs::{FitOptions, Model, ModelOptions, NIW};
+    ///
+    /// let data = DMatrix::new_random(2, 400);
+    /// let mut model = Model::from_options(ModelOptions::<NIW>::default(2));
+    /// model.fit_batches(|| (0..4).map(|i| data.columns(i * 100, 100).into_owned()), &FitOptions::default()).unwrap();
+    /// ```
+    pub fn fit_batches<I: IntoIterator<Item=DMatrix<f64>>>(
+        &mut self,
+        batches: impl FnMut() -> I,
+        fit_options: &FitOptions,
+    ) -> Result<FitResult, Error> {
+        if fit_options.constraints.is_some() || fit_options.frozen.is_some() || fit_options.weights.is_some()
+            || fit_options.validation_fraction.is_some() || fit_options.init_strategy != InitStrategy::Random {
+            return Err(Error::InvalidData(
+                "constraints, frozen points, weights, a validation fraction and k-means++ initialization are not supported when fitting batches".to_string()
+            ));
+        }
+        if fit_options.init_clusters == 0 {
+            return Err(Error::EmptyCluster);
+        }
+
+        let mut rng = SmallRng::seed_from_u64(fit_options.seed);
+        let mut local = BatchedState::from_batches(batches, self.dim())?;
+        for shard in &mut local.shards {
+            shard.apply_fit_options(fit_options);
         }
+        local.init(fit_options.init_clusters, &mut rng);
+        local.take_error()?;
+
+        let result = self.fit_worker(&mut local, fit_options, None::<MonitoringCallback<GlobalState<P>>>);
+        local.take_error()?;
+        result
     }
 
     /// Fit the model using the data workers.
@@ -147,43 +601,68 @@ impl<P: NormalConjugatePrior> Model<P> {
     /// let callback = MonitoringCallback::from_data(
     ///        EvalData::from_sample(&x, None, 1000)
     /// );
-    /// let mut local = ShardedState::from_data(x, 4);
+    /// let mut local = ShardedState::<NIW>::from_data(x, 4);
     ///
     /// model.fit_worker(
     ///     &mut local,
     ///     &fit_options,
     ///     Some(callback)
-    /// );
+    /// ).unwrap();
     /// ```
     pub fn fit_worker<L: LocalWorker<P>>(
         &mut self,
         local: &mut L,
         fit_options: &FitOptions,
         mut callback: Option<impl Callback<GlobalState<P>>>,
-    ) {
+    ) -> Result<FitResult, Error> {
+        self.model_options.validate()?;
+
         let mut rng = SmallRng::seed_from_u64(fit_options.seed);
+        let mut label_trace = Vec::new();
+        let start = Instant::now();
+        let mut stop_reason = StopReason::MaxIters;
+        let mut prev_labels = None;
+        let mut log_likelihood_trace = Vec::new();
+        let mut n_clusters_trace = Vec::new();
+        let mut n_iters = 0;
+        let mut stalled = 0;
+        let mut plateau_end: Option<usize> = None;
 
         // (Re)initialize global state
         if fit_options.reuse {
-            if self.global.is_none() {
-                panic!("Cannot reuse global state if it has not been initialized yet");
+            let global = self.global.as_ref().ok_or_else(|| {
+                Error::InvalidData("cannot reuse the global state before the model is fitted".to_string())
+            })?;
+            let expected = self.model_options.dim;
+            for cluster in &global.clusters {
+                if cluster.prim.prior.dim() != expected {
+                    return Err(Error::DimensionMismatch { expected, got: cluster.prim.prior.dim() });
+                }
             }
         } else {
             let data_stats = local.collect_data_stats();
             self.global = Some(
-                GlobalState::from_init(&data_stats, fit_options.init_clusters, &self.model_options, &mut rng)
+                GlobalState::try_from_init(&data_stats, fit_options.init_clusters, &self.model_options, &mut rng)?
             );
         }
         let global = self.global.as_mut().unwrap();
+        global.acceptance = AcceptanceStats::default();
+        global.split_merge_events = fit_options.record_split_merge_events.then(Vec::new);
+        let mut split_merge_events = Vec::new();
 
         // Initialize clusters from local states / data
         let stats = local.collect_cluster_stats(GlobalWorker::n_clusters(global));
         global.update_clusters_post(stats);
         global.update_sample_clusters(&self.model_options, &mut rng);
 
+        let mut options = self.model_options.clone();
+        let n_scheduled = fit_options.iters.saturating_sub(fit_options.iter_split_stop);
         for i in 0..fit_options.iters {
-            let is_cooldown = i >= fit_options.iters - fit_options.argmax_sample_stop;
-            let no_more_actions = i >= fit_options.iters - fit_options.iter_split_stop;
+            options.alpha = self.model_options.effective_alpha(i, n_scheduled);
+
+            // Once a plateau is detected only the final hard assignment iterations remain
+            let is_cooldown = i >= fit_options.iters - fit_options.argmax_sample_stop || plateau_end.is_some();
+            let no_more_actions = i >= fit_options.iters - fit_options.iter_split_stop || plateau_end.is_some();
             let no_more_splits = GlobalWorker::n_clusters(global) >= fit_options.max_clusters;
 
             // Before step callback
@@ -192,8 +671,12 @@ impl<P: NormalConjugatePrior> Model<P> {
             }
 
             // Expectation step
-            global.update_sample_clusters(&self.model_options, &mut rng);
-            local.apply_label_sampling(global, is_cooldown, &mut rng);
+            global.update_sample_clusters(&options, &mut rng);
+            if global.weights.iter().any(|w| !w.is_finite()) {
+                return Err(Error::DegenerateWeights);
+            }
+            let hard_assign = is_cooldown || self.model_options.hard_assignment;
+            local.apply_label_sampling(global, hard_assign, &mut rng)?;
 
             // Maximization step
             let stats = local.collect_cluster_stats(GlobalWorker::n_clusters(global));
@@ -203,17 +686,28 @@ impl<P: NormalConjugatePrior> Model<P> {
             let bad_clusters = global.collect_bad_clusters();
             local.apply_cluster_reset(&bad_clusters, &mut rng);
 
+            // Dissolve clusters that are too small into their nearest cluster
+            let small_clusters = global.collect_small_clusters(&options);
+            if !small_clusters.is_empty() {
+                local.apply_cluster_reassign(&small_clusters, &mut rng);
+                let stats = local.collect_cluster_stats(GlobalWorker::n_clusters(global));
+                global.update_clusters_post(stats);
+                let removed_idx = global.collect_remove_clusters(&options);
+                local.apply_cluster_remove(&removed_idx);
+            }
+
             // Compute metrics before any action is applied
             if let Some(callback) = &mut callback {
                 callback.during_step(i, global);
             }
 
             // Proposal step
+            let mut split_rng = global.split_rng.next_rng();
             if !no_more_actions {
                 // Propose split actions
                 if !no_more_splits {
-                    let split_idx = global.check_and_split(&self.model_options, &mut rng);
-                    local.apply_split(&split_idx, &mut rng);
+                    let split_idx = global.check_and_split(&options, &mut split_rng);
+                    local.apply_split(&split_idx, &mut split_rng);
 
                     if !split_idx.is_empty() {
                         let stats = local.collect_cluster_stats(GlobalWorker::n_clusters(global));
@@ -222,19 +716,96 @@ impl<P: NormalConjugatePrior> Model<P> {
                 }
 
                 // Propose merge actions
-                let merge_idx = global.check_and_merge(&self.model_options, &mut rng);
+                let merge_idx = global.check_and_merge(&options, &mut split_rng);
                 local.apply_merge(&merge_idx);
             }
+            take_split_merge_events(global, i, &mut split_merge_events);
 
             // Remove empty clusters
-            let removed_idx = global.collect_remove_clusters(&self.model_options);
+            let removed_idx = global.collect_remove_clusters(&options);
             local.apply_cluster_remove(&removed_idx);
 
+            // Record label trace
+            if fit_options.record_label_trace
+                && i >= fit_options.trace_burn_in
+                && (i - fit_options.trace_burn_in) % fit_options.trace_thinning.max(1) == 0 {
+                label_trace.push(local.collect_labels());
+            }
+
+            // Record convergence diagnostics
+            n_iters = i + 1;
+            n_clusters_trace.push(GlobalWorker::n_clusters(global));
+            let StopCriteria { time_budget, label_change_tol } = &fit_options.stop_criteria;
+            let detect_plateau = fit_options.patience != usize::MAX;
+            if fit_options.record_ll_trace || detect_plateau {
+                let offset = self.model_options.cluster_offset();
+                let counts: Vec<_> = global.clusters[offset..].iter().map(|c| c.n_points()).collect();
+                let ll = local.log_likelihood(global) + log_partition_prior(&counts, options.alpha);
+
+                // Count consecutive iterations without progress
+                if detect_plateau {
+                    if matches!(log_likelihood_trace.last(), Some(prev) if ll - prev < fit_options.tol) {
+                        stalled += 1;
+                    } else {
+                        stalled = 0;
+                    }
+                    if stalled >= fit_options.patience.max(1) && !is_cooldown {
+                        plateau_end = Some(i + 1 + fit_options.argmax_sample_stop);
+                    }
+                }
+                log_likelihood_trace.push(ll);
+            }
+
             // After step callback
             if let Some(callback) = &mut callback {
                 callback.after_step(i);
             }
+
+            // Check the stopping conditions
+            if matches!(plateau_end, Some(end) if i + 1 >= end) {
+                stop_reason = StopReason::LogLikelihoodPlateau;
+                break;
+            }
+            if matches!(time_budget, Some(budget) if start.elapsed() >= *budget) {
+                stop_reason = StopReason::TimeBudget;
+                break;
+            }
+            if let Some(tol) = label_change_tol {
+                let labels = local.collect_labels();
+                if let Some(prev) = &prev_labels {
+                    let changed = labels.iter().zip(prev.iter()).filter(|(a, b)| a != b).count();
+                    if (changed as f64) < tol * labels.len() as f64 {
+                        stop_reason = StopReason::LabelChange;
+                        break;
+                    }
+                }
+                prev_labels = Some(labels);
+            }
         }
+
+        let label_trace = if !fit_options.record_label_trace {
+            None
+        } else if label_trace.is_empty() {
+            Some(DMatrix::zeros(0, local.n_points()))
+        } else {
+            Some(DMatrix::from_rows(&label_trace))
+        };
+
+        let responsibilities = fit_options.return_responsibilities
+            .then(|| local.collect_responsibilities(global));
+        global.split_merge_events = None;
+
+        Ok(FitResult {
+            label_trace,
+            responsibilities,
+            stop_reason,
+            acceptance: global.acceptance,
+            log_likelihood_trace: if fit_options.record_ll_trace { log_likelihood_trace } else { Vec::new() },
+            n_clusters_trace,
+            split_merge_events,
+            n_iters,
+            ..FitResult::default()
+        })
     }
 
     /// Predict the cluster labels for the data and their confidence.
@@ -262,7 +833,7 @@ impl<P: NormalConjugatePrior> Model<P> {
     /// let mut model = Model::from_options(model_options);
     ///
     /// let fit_options = FitOptions::default();
-    /// model.fit(x.clone_owned(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>);
+    /// model.fit(x.clone_owned(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
     ///
     /// let (confidence, labels) = model.predict(x);
     /// ```
@@ -274,13 +845,1489 @@ impl<P: NormalConjugatePrior> Model<P> {
             panic!("Cannot predict if model has not been fitted yet");
         }
 
-        let global = self.global.as_ref().unwrap();
-        SuperMixtureParams(global).predict(data)
+        self.params().predict_with_confidence(data, &self.model_options)
     }
 
-    pub fn params(&self) -> &GlobalState<P> {
-        self.global.as_ref().expect("Cannot get params if model has not been fitted yet")
+    /// Predicts the labels of the data points using the given class log-priors instead of the fitted
+    /// mixture weights. Useful when the class balance at deployment differs from the training data.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The data to predict the labels for. (n_features, n_samples)
+    /// * `class_log_priors`: Log prior probability of each cluster. (n_clusters)
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of priors does not match the number of clusters.
+    pub fn predict_with_prior(
+        &self,
+        data: DMatrix<f64>,
+        class_log_priors: &[f64],
+    ) -> DVector<usize> {
+        let params = SuperMixtureParams(self.params());
+        assert_eq!(
+            class_log_priors.len(), params.n_clusters(),
+            "Number of class priors must match the number of clusters"
+        );
+
+        let mut log_likelihood = DMatrix::zeros(params.n_clusters(), data.ncols());
+        for (k, &log_prior) in class_log_priors.iter().enumerate() {
+            let ll = params.ln_pdf(k, data.clone_owned()).add_scalar(log_prior);
+            log_likelihood.row_mut(k).copy_from_slice(ll.as_slice());
+        }
+
+        let low_density = self.params().low_density_points(&data, &self.model_options);
+        let outlier = self.model_options.outlier_index();
+        DVector::from_iterator(
+            log_likelihood.ncols(),
+            log_likelihood.column_iter().zip(low_density).map(|(col, low_density)| match outlier {
+                Some(outlier) if low_density => outlier,
+                _ => argmax(col.iter().cloned()).unwrap_or(0),
+            }),
+        )
     }
-}
 
+    /// Predicts the labels of points stored in a flat row-major buffer of shape (rows, cols), with one
+    /// point per row, e.g. the contiguous buffer of a tensor.
+    /// A row-major (n_samples, n_features) buffer has the same memory layout as the column-major
+    /// (n_features, n_samples) matrices used by the model, so the buffer is read in place without reordering.
+    /// Only one chunk of points is copied at a time, so the buffer is never duplicated as a whole.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The points. (rows * cols)
+    /// * `rows`: Number of points
+    /// * `cols`: Number of features
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer length does not equal `rows * cols`.
+    pub fn predict_slice(
+        &self,
+        data: &[f64],
+        rows: usize,
+        cols: usize,
+    ) -> DVector<usize> {
+        assert_eq!(data.len(), rows * cols, "Buffer length must equal rows * cols");
+
+        let points = DMatrixSlice::from_slice(data, cols, rows);
+        let mut labels = DVector::zeros(rows);
+        for start in (0..rows).step_by(BORROWED_CHUNK_SIZE) {
+            let n = BORROWED_CHUNK_SIZE.min(rows - start);
+            let (_, chunk) = self.params().predict_with_confidence(points.columns(start, n).into_owned(), &self.model_options);
+            labels.rows_mut(start, n).copy_from(&chunk.transpose());
+        }
+        labels
+    }
+
+    /// Lazily iterates over the hard assignments of the data points, yielding `(point_index, label, responsibility)`
+    /// tuples where the responsibility is the probability of the assigned cluster. The responsibilities are
+    /// computed in small chunks of points, so results can be streamed (e.g. to disk) without materializing
+    /// them for all points.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The data points (n_dim, n_points)
+    pub fn assignments<'a>(&'a self, data: &'a DMatrix<f64>) -> Assignments<'a, P> {
+        Assignments {
+            params: self.params(),
+            options: &self.model_options,
+            data,
+            next: 0,
+            chunk_start: 0,
+            probs: DMatrix::zeros(0, 0),
+            labels: RowDVector::zeros(0),
+        }
+    }
+
+    /// Samples a cluster label for a single point from its responsibilities.
+    ///
+    /// Unlike [`Model::predict`], which picks the most likely cluster, the label is drawn at random so
+    /// repeated calls reflect the assignment uncertainty of the point.
+    ///
+    /// # Arguments
+    ///
+    /// * `point`: The point to assign. (n_dim)
+    /// * `rng`: A random number generator
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error`] if the point has zero likelihood under every cluster.
+    pub fn sample_label(
+        &self,
+        point: &DVector<f64>,
+        rng: &mut impl Rng,
+    ) -> Result<usize, Error> {
+        let data = DMatrix::from_column_slice(point.len(), 1, point.as_slice());
+        let log_likelihood = SuperMixtureParams(self.params()).log_likelihood(data);
+
+        let mut label = [0];
+        soft_assignment(log_likelihood, &mut label, rng)?;
+        Ok(label[0])
+    }
+
+    /// Explains the assignment of a point by the per-feature contributions to the log density of its
+    /// most likely cluster, i.e. the squared standardized deviations `(x_i - mu_i)^2 / sigma_ii`.
+    ///
+    /// # Arguments
+    ///
+    /// * `point`: The point to explain. (n_dim)
+    /// * `k`: Maximum number of features to return
+    ///
+    /// # Returns
+    ///
+    /// Up to `k` pairs of feature index and contribution, sorted by decreasing contribution.
+    pub fn explain_assignment(
+        &self,
+        point: &DVector<f64>,
+        k: usize,
+    ) -> Vec<(usize, f64)> {
+        let data = DMatrix::from_column_slice(point.len(), 1, point.as_slice());
+        let (_, labels) = self.params().predict_with_confidence(data, &self.model_options);
+        let dist = &self.params().clusters[labels[0]].prim.dist;
+
+        let mut contributions: Vec<(usize, f64)> = point.iter().enumerate()
+            .map(|(i, x)| (i, (x - dist.mu()[i]).powi(2) / dist.cov()[(i, i)]))
+            .collect();
+        contributions.sort_by(|a, b| b.1.total_cmp(&a.1));
+        contributions.truncate(k);
+        contributions
+    }
+
+    /// Mean log-likelihood of the data points (columns) under the fitted mixture.
+    pub fn mean_log_likelihood(
+        &self,
+        data: DMatrix<f64>,
+    ) -> f64 {
+        let n_points = data.ncols();
+        let log_likelihood = SuperMixtureParams(self.params()).log_likelihood(data);
+        let total: f64 = log_likelihood.column_iter().map(|col| log_sum_exp(col.iter().cloned())).sum();
+
+        total / n_points as f64
+    }
+
+    /// Draws synthetic points from the posterior predictive distribution of the fitted mixture, e.g. to
+    /// overlay on the real data to assess the fit. Each point picks a cluster by its mixture weight, then
+    /// draws normal parameters from the cluster posterior and finally the point from that normal.
+    ///
+    /// # Arguments
+    ///
+    /// * `n`: Number of points to draw
+    /// * `rng`: A random number generator
+    ///
+    /// # Returns
+    ///
+    /// The synthetic data points. (n_dim, n)
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DegenerateWeights`] if no mixture weight is positive.
+    pub fn posterior_predictive_sample(
+        &self,
+        n: usize,
+        rng: &mut impl Rng,
+    ) -> Result<DMatrix<f64>, Error> {
+        let global = self.params();
+        let mut samples = DMatrix::zeros(self.dim(), n);
+        for mut col in samples.column_iter_mut() {
+            let k = sample_normalized(rng, global.weights.iter().cloned())
+                .ok_or(Error::DegenerateWeights)?;
+            let dist = P::sample(&global.clusters[k].prim.post, rng);
+            col.copy_from(&dist.sample(rng));
+        }
+        Ok(samples)
+    }
+
+    /// Shannon entropy (in nats) of the responsibilities of each point, see [`GlobalState::predict_proba`].
+    /// Points near multiple clusters have a high entropy (at most `ln(n_clusters)`), points well inside
+    /// a cluster an entropy near zero. [`GlobalState::mean_assignment_entropy`] is the mean over the points.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The data points (n_dim, n_points)
+    ///
+    /// # Returns
+    ///
+    /// The assignment entropy of each point. (n_points)
+    pub fn assignment_entropy(
+        &self,
+        data: DMatrix<f64>,
+    ) -> DVector<f64> {
+        soft_label_entropy(&self.params().predict_proba(&data, &self.model_options))
+    }
+
+    /// Counts, for each pair of clusters, the points on the boundary between them: the points whose two
+    /// highest responsibilities belong to the pair and differ by less than `margin_threshold`.
+    /// Nonzero counts indicate clusters that border each other.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The data points (n_dim, n_points)
+    /// * `margin_threshold`: Maximum difference between the two highest responsibilities of a boundary point (in [0, 1])
+    ///
+    /// # Returns
+    ///
+    /// A symmetric matrix of boundary point counts with a zero diagonal. (n_clusters, n_clusters)
+    pub fn cluster_adjacency(
+        &self,
+        data: &DMatrix<f64>,
+        margin_threshold: f64,
+    ) -> DMatrix<usize> {
+        let (probs, _) = self.params().predict_with_confidence(data.clone_owned(), &self.model_options);
+        let mut adjacency = DMatrix::zeros(probs.nrows(), probs.nrows());
+        if probs.nrows() < 2 {
+            return adjacency;
+        }
+
+        for col in probs.column_iter() {
+            let (mut first, mut second) = if col[0] >= col[1] { (0, 1) } else { (1, 0) };
+            for k in 2..col.nrows() {
+                if col[k] > col[first] {
+                    second = first;
+                    first = k;
+                } else if col[k] > col[second] {
+                    second = k;
+                }
+            }
+
+            if col[first] - col[second] < margin_threshold {
+                adjacency[(first, second)] += 1;
+                adjacency[(second, first)] += 1;
+            }
+        }
+
+        adjacency
+    }
 
+    /// Anomaly score of each query point, defined as the negative log density of its most likely
+    /// (weighted) cluster. Points in low-density regions score higher.
+    ///
+    /// # Arguments
+    ///
+    /// * `queries`: The query points (n_dim, n_queries)
+    ///
+    /// # Returns
+    ///
+    /// The anomaly score of each query. (n_queries)
+    pub fn predictive_uncertainty(
+        &self,
+        queries: DMatrix<f64>,
+    ) -> DVector<f64> {
+        let log_likelihood = SuperMixtureParams(self.params()).log_likelihood(queries);
+        DVector::from_iterator(
+            log_likelihood.ncols(),
+            log_likelihood.column_iter().map(|col| -col.max()),
+        )
+    }
+
+    /// Measures how stable the cluster assignments are under Gaussian perturbations of the data.
+    /// Each trial adds independent noise to every feature and re-predicts the labels with the fitted
+    /// model (no refitting).
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The data points (n_dim, n_points)
+    /// * `noise_std`: Standard deviation of the added noise
+    /// * `n_trials`: Number of perturbed copies to predict
+    /// * `rng`: A random number generator
+    ///
+    /// # Returns
+    ///
+    /// The mean fraction of points keeping their label across the trials (in [0, 1]).
+    pub fn noise_stability(
+        &self,
+        data: &DMatrix<f64>,
+        noise_std: f64,
+        n_trials: usize,
+        rng: &mut impl Rng,
+    ) -> f64 {
+        let global = self.params();
+        let (_, labels) = global.predict_with_confidence(data.clone_owned(), &self.model_options);
+        if n_trials == 0 || data.ncols() == 0 {
+            return 1.0;
+        }
+
+        let normal = Normal::new(0.0, 1.0).unwrap();
+        let kept: usize = (0..n_trials).map(|_| {
+            let noisy = data.map(|x| x + noise_std * normal.sample(rng));
+            let (_, noisy_labels) = global.predict_with_confidence(noisy, &self.model_options);
+            labels.iter().zip(noisy_labels.iter()).filter(|(a, b)| a == b).count()
+        }).sum();
+
+        kept as f64 / (n_trials * data.ncols()) as f64
+    }
+
+    /// Flags clusters that are likely bimodal and should be split, e.g. because splits stopped early.
+    /// The points assigned to each cluster are projected onto their principal axis and a cluster is
+    /// flagged if the [`bimodality_coefficient`] of the projection exceeds that of a uniform distribution (`5/9`).
+    /// The outlier cluster and clusters with fewer than 8 assigned points are never flagged.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The data points (n_dim, n_points)
+    ///
+    /// # Returns
+    ///
+    /// The indices of the flagged clusters.
+    pub fn detect_multimodal_clusters(
+        &self,
+        data: &DMatrix<f64>,
+    ) -> Vec<usize> {
+        let (_, labels) = self.params().predict_with_confidence(data.clone_owned(), &self.model_options);
+        let first = self.model_options.cluster_offset();
+
+        (first..self.n_clusters()).filter(|&k| {
+            let indices: Vec<usize> = (0..data.ncols()).filter(|&i| labels[i] == k).collect();
+            if indices.len() < 8 {
+                return false;
+            }
+
+            let points = data.select_columns(&indices);
+            let eigen = points.column_cov().symmetric_eigen();
+            let axis = eigen.eigenvectors.column(eigen.eigenvalues.imax());
+            let projection: Vec<f64> = points.column_iter().map(|x| x.dot(&axis)).collect();
+
+            bimodality_coefficient(&projection) > 5.0 / 9.0
+        }).collect()
+    }
+
+    /// Log-determinant of each cluster covariance. See [`GlobalState::cluster_log_dets`].
+    pub fn cluster_log_dets(&self) -> Vec<f64> {
+        self.params().cluster_log_dets()
+    }
+
+    /// Density mode of each cluster. See [`GlobalState::cluster_modes`].
+    pub fn cluster_modes(&self) -> Vec<DVector<f64>> {
+        self.params().cluster_modes()
+    }
+
+    pub fn params(&self) -> &GlobalState<P> {
+        self.global.as_ref().expect("Cannot get params if model has not been fitted yet")
+    }
+
+    /// Decomposes a model fitted with [`Model::fit`] into its global state, the final local state
+    /// (labels and data of the last fit, only if [`FitOptions::keep_local`] was set), the model options and
+    /// the options of the last fit.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the model has not been fitted with [`Model::fit`] yet.
+    pub fn into_parts(self) -> (GlobalState<P>, Option<LocalState<P, usize, T>>, ModelOptions<P>, FitOptions) {
+        match (self.global, self.fit_options) {
+            (Some(global), Some(fit_options)) => (global, self.local, self.model_options, fit_options),
+            _ => panic!("Cannot decompose model if it has not been fitted yet"),
+        }
+    }
+}
+
+/// Number of points whose responsibilities are computed at once by [`Assignments`].
+const ASSIGNMENT_CHUNK_SIZE: usize = 1024;
+
+/// Lazy iterator over the hard assignments of data points. See [`Model::assignments`].
+pub struct Assignments<'a, P: NormalConjugatePrior> {
+    params: &'a GlobalState<P>,
+    options: &'a ModelOptions<P>,
+    data: &'a DMatrix<f64>,
+    next: usize,
+    chunk_start: usize,
+    probs: DMatrix<f64>,
+    labels: RowDVector<usize>,
+}
+
+impl<'a, P: NormalConjugatePrior> Iterator for Assignments<'a, P> {
+    type Item = (usize, usize, f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.data.ncols() {
+            return None;
+        }
+
+        // Compute the next chunk of responsibilities
+        if self.next >= self.chunk_start + self.labels.len() {
+            let n = ASSIGNMENT_CHUNK_SIZE.min(self.data.ncols() - self.next);
+            let chunk = self.data.columns(self.next, n).clone_owned();
+            let (probs, labels) = self.params.predict_with_confidence(chunk, self.options);
+            self.chunk_start = self.next;
+            self.probs = probs;
+            self.labels = labels;
+        }
+
+        let i = self.next - self.chunk_start;
+        let label = self.labels[i];
+        self.next += 1;
+        Some((self.next - 1, label, self.probs[(label, i)]))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.data.ncols() - self.next;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a, P: NormalConjugatePrior> ExactSizeIterator for Assignments<'a, P> {}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::io::Cursor;
+    use std::time::Duration;
+    use nalgebra::{DMatrix, DVector, RowDVector};
+    use rand::prelude::StdRng;
+    use rand::SeedableRng;
+    use statrs::distribution::MultivariateNormal;
+    use crate::{FitOptions, Model, ModelOptions, MonitoringCallback, NIW, SplitMergeKind};
+    use crate::callback::CheckpointCallback;
+    use crate::Error;
+    use crate::model::{log_partition_prior, StopReason};
+    use crate::params::{AlphaSchedule, InitStrategy, PairwiseConstraints, StopCriteria};
+    use crate::state::{GlobalState, LocalState, LocalWorker};
+    use crate::stats::{BORROWED_CHUNK_SIZE, FromData, NIWParams, NIWStats, PriorHyperParams};
+    use crate::stats::tests::test_almost_mat;
+    use crate::metrics::normalized_mutual_info_score;
+    use crate::testing::{make_blobs, sklearn_model};
+    use crate::utils::argmax;
+
+    #[test]
+    fn test_label_trace() {
+        let x = DMatrix::new_random(2, 100);
+
+        let mut model_options = ModelOptions::<NIW>::default(2);
+        model_options.outlier = None;
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 10;
+        fit_options.record_label_trace = true;
+        fit_options.trace_burn_in = 4;
+        fit_options.trace_thinning = 2;
+
+        let mut model = Model::from_options(model_options);
+        let result = model.fit(x, &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+
+        // Iterations 4, 6 and 8 are recorded
+        let trace = result.label_trace.unwrap();
+        assert_eq!(trace.nrows(), 3);
+        assert_eq!(trace.ncols(), 100);
+    }
+
+    #[test]
+    fn test_return_responsibilities() {
+        let centers = DMatrix::from_column_slice(2, 3, &[0.0, 0.0, 10.0, 0.0, 0.0, 10.0]);
+        let (x, _) = make_blobs(100, &centers, 1.0, 42);
+
+        for workers in [1, 2] {
+            let mut model_options = ModelOptions::<NIW>::default(2);
+            model_options.hard_assignment = true;
+            let mut fit_options = FitOptions::default();
+            fit_options.iters = 30;
+            fit_options.workers = workers;
+            fit_options.return_responsibilities = true;
+            fit_options.record_label_trace = true;
+            fit_options.trace_burn_in = fit_options.iters - 1;
+
+            let mut model = Model::from_options(model_options);
+            let result = model.fit(x.clone(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+
+            let responsibilities = result.responsibilities.unwrap();
+            assert_eq!(responsibilities.shape(), (x.ncols(), model.params().clusters.len()));
+
+            // The final labels are the most responsible clusters
+            let labels = result.label_trace.unwrap();
+            for (i, row) in responsibilities.row_iter().enumerate() {
+                statrs::assert_almost_eq!(row.sum(), 1.0, 1e-10);
+                assert_eq!(argmax(row.iter().cloned()), Some(labels[(0, i)]));
+            }
+        }
+
+        let mut model = Model::from_options(ModelOptions::<NIW>::default(2));
+        let result = model.fit(x, &FitOptions::default(), None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+        assert!(result.responsibilities.is_none());
+    }
+
+    #[test]
+    fn test_split_merge_events() {
+        let centers = DMatrix::from_column_slice(2, 2, &[0.0, 0.0, 10.0, 10.0]);
+        let (x, _) = make_blobs(100, &centers, 1.0, 42);
+
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 30;
+        fit_options.record_split_merge_events = true;
+        let mut model = Model::from_options(ModelOptions::<NIW>::default(2));
+        let result = model.fit(x.clone(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+
+        // The bimodal initial cluster is split
+        let events = &result.split_merge_events;
+        assert!(events.iter().any(|e| e.kind == SplitMergeKind::Split && e.accepted && e.log_ratio > 0.0));
+        assert!(events.windows(2).all(|w| w[0].iter <= w[1].iter && w[1].iter < result.n_iters));
+
+        let count = |kind: SplitMergeKind, accepted: bool| {
+            events.iter().filter(|e| e.kind == kind && (e.accepted || !accepted)).count()
+        };
+        let acceptance = result.acceptance;
+        assert_eq!(count(SplitMergeKind::Split, true), acceptance.splits_accepted);
+        assert_eq!(count(SplitMergeKind::Merge, false), acceptance.merges_proposed);
+        assert_eq!(count(SplitMergeKind::Merge, true), acceptance.merges_accepted);
+        assert!(model.params().split_merge_events.is_none());
+
+        let result = model.fit(x, &FitOptions::default(), None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+        assert!(result.split_merge_events.is_empty());
+    }
+
+    #[test]
+    fn test_stop_criteria() {
+        let x = DMatrix::new_random(2, 100);
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 1000;
+        fit_options.stop_criteria = StopCriteria {
+            time_budget: Some(Duration::ZERO),
+            label_change_tol: Some(0.0),
+        };
+        fit_options.tol = 0.0;
+        fit_options.patience = 1;
+
+        let mut model = Model::from_options(ModelOptions::<NIW>::default(2));
+        let result = model.fit(x.clone(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+        assert_eq!(result.stop_reason, StopReason::TimeBudget);
+
+        fit_options.iters = 10;
+        fit_options.stop_criteria = StopCriteria::default();
+        let result = model.fit(x, &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+        assert_eq!(result.stop_reason, StopReason::MaxIters);
+    }
+
+    #[test]
+    fn test_acceptance_stats() {
+        let centers = DMatrix::from_column_slice(2, 3, &[0.0, 0.0, 10.0, 0.0, 0.0, 10.0]);
+        let (x, _) = make_blobs(100, &centers, 1.0, 42);
+
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 50;
+        let mut model = Model::from_options(ModelOptions::<NIW>::default(2));
+        let result = model.fit(x, &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+
+        let acceptance = result.acceptance;
+        assert!(acceptance.splits_proposed > 0);
+        assert!(acceptance.splits_accepted > 0);
+        assert!(acceptance.splits_accepted <= acceptance.splits_proposed);
+        assert!(acceptance.merges_accepted <= acceptance.merges_proposed);
+        assert_eq!(acceptance, model.params().acceptance);
+    }
+
+    #[test]
+    fn test_convergence_traces() {
+        let centers = DMatrix::from_column_slice(2, 3, &[0.0, 0.0, 10.0, 0.0, 0.0, 10.0]);
+        let (x, _) = make_blobs(100, &centers, 1.0, 42);
+
+        let mut model_options = ModelOptions::<NIW>::default(2);
+        model_options.outlier = None;
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 50;
+        fit_options.record_ll_trace = true;
+
+        let mut model = Model::from_options(model_options);
+        let result = model.fit(x, &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+
+        assert_eq!(result.log_likelihood_trace.len(), 50);
+        assert_eq!(result.n_clusters_trace.len(), 50);
+        assert!(result.log_likelihood_trace.iter().all(|ll| ll.is_finite()));
+        assert!(result.log_likelihood_trace[49] > result.log_likelihood_trace[0]);
+        assert_eq!(result.n_clusters_trace[49], model.n_clusters());
+
+        fit_options.record_ll_trace = false;
+        let result = model.fit(DMatrix::new_random(2, 10), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+        assert!(result.log_likelihood_trace.is_empty());
+    }
+
+    #[test]
+    fn test_early_stopping() {
+        let x = DMatrix::new_random(2, 100);
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 100;
+        fit_options.argmax_sample_stop = 5;
+
+        let mut model = Model::from_options(ModelOptions::<NIW>::default(2));
+        let result = model.fit(x.clone(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+        assert_eq!(result.n_iters, 100);
+        assert_eq!(result.stop_reason, StopReason::MaxIters);
+
+        // Every iteration stalls: the plateau is detected after iteration 3 (the first has nothing to compare
+        // against) and is followed by the 5 hard assignment iterations
+        fit_options.tol = f64::INFINITY;
+        fit_options.patience = 3;
+        let result = model.fit(x, &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+        assert_eq!(result.n_iters, 9);
+        assert_eq!(result.stop_reason, StopReason::LogLikelihoodPlateau);
+        assert_eq!(result.n_clusters_trace.len(), 9);
+        assert!(result.log_likelihood_trace.is_empty());
+    }
+
+    #[test]
+    fn test_kmeans_plusplus_init() {
+        let centers = DMatrix::from_column_slice(2, 4, &[0.0, 0.0, 15.0, 0.0, 0.0, 15.0, 15.0, 15.0]);
+        let (x, y) = make_blobs(100, &centers, 1.0, 42);
+        let mut model_options = ModelOptions::<NIW>::default(2);
+        model_options.outlier = None;
+
+        let mut fit_options = FitOptions::default();
+        fit_options.init_clusters = 4;
+        fit_options.iters = 60;
+        fit_options.record_ll_trace = true;
+        let fit = |init_strategy: InitStrategy| {
+            let mut fit_options = fit_options.clone();
+            fit_options.init_strategy = init_strategy;
+            let mut model = Model::from_options(model_options.clone());
+            model.fit(x.clone(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap()
+                .log_likelihood_trace
+        };
+        let random = fit(InitStrategy::Random);
+        let kmeans = fit(InitStrategy::KMeansPlusPlus { n: 3 });
+
+        // Iterations until the log-likelihood is within 1% of the best one reached
+        let best = random.iter().chain(&kmeans).cloned().fold(f64::NEG_INFINITY, f64::max);
+        let target = best - 0.01 * best.abs();
+        let reached = |trace: &[f64]| trace.iter().position(|&ll| ll >= target).unwrap_or(trace.len());
+        assert!(reached(&kmeans) < reached(&random), "k-means++ {} >= random {}", reached(&kmeans), reached(&random));
+
+        // Sharded fits seed the same centers in every shard
+        fit_options.workers = 2;
+        fit_options.iters = 5;
+        fit_options.argmax_sample_stop = 1;
+        fit_options.init_strategy = InitStrategy::KMeansPlusPlus { n: 3 };
+        let mut model = Model::from_options(model_options);
+        model.fit(x.clone(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+        let (_, labels) = model.predict(x);
+        assert!(normalized_mutual_info_score(y.as_slice(), labels.as_slice()) > 0.95);
+    }
+
+    #[test]
+    fn test_min_cluster_size() {
+        let centers = DMatrix::from_column_slice(2, 2, &[0.0, 0.0, 10.0, 0.0]);
+        let (x, _) = make_blobs(100, &centers, 1.0, 42);
+        // A tiny isolated group of points
+        let x = DMatrix::from_fn(2, 203, |r, c| if c < 200 { x[(r, c)] } else { 60.0 + c as f64 * 0.01 });
+
+        let mut model_options = ModelOptions::<NIW>::default(2);
+        model_options.outlier = None;
+        let mut fit_options = FitOptions::default();
+        fit_options.init_clusters = 3;
+
+        let mut model = Model::from_options(model_options.clone());
+        let mut local = LocalState::<NIW>::from_data(x.clone());
+        local.labels = RowDVector::from_fn(203, |_, i| if i < 200 { i / 100 } else { 2 });
+        model_options.min_cluster_size = 10;
+        let mut model_min = Model::from_options(model_options);
+        let mut local_min = local.clone();
+
+        model.fit_worker(&mut local, &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+        assert!(model.params().cluster_sizes().contains(&3), "sizes {:?}", model.params().cluster_sizes());
+
+        // The tiny cluster is absorbed by the nearest blob
+        model_min.fit_worker(&mut local_min, &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+        let sizes = model_min.params().cluster_sizes();
+        assert!(sizes.iter().all(|&n| n >= 10), "sizes {:?}", sizes);
+        assert_eq!(local_min.labels[202], local_min.labels[150]);
+    }
+
+    #[test]
+    fn test_fit_batches() {
+        let centers = DMatrix::from_column_slice(2, 3, &[0.0, 0.0, 10.0, 0.0, 0.0, 10.0]);
+        let (x, y) = make_blobs(200, &centers, 1.0, 42);
+        let mut model_options = ModelOptions::<NIW>::default(2);
+        model_options.outlier = None;
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 60;
+
+        let mut full = Model::from_options(model_options.clone());
+        full.fit(x.clone(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+
+        // Every chunk holds points of every blob
+        let (data, passes) = (&x, Cell::new(0));
+        let mut streamed = Model::from_options(model_options);
+        let result = streamed.fit_batches(|| {
+            passes.set(passes.get() + 1);
+            (0..4).map(move |c| data.select_columns((c..data.ncols()).step_by(4).collect::<Vec<_>>().iter()))
+        }, &fit_options).unwrap();
+        assert!(passes.get() > fit_options.iters, "passes {}", passes.get());
+        assert_eq!(result.n_iters, fit_options.iters);
+
+        assert_eq!(streamed.n_clusters(), full.n_clusters());
+        let (_, labels_full) = full.predict(x.clone());
+        let (_, labels_streamed) = streamed.predict(x.clone());
+        assert!(normalized_mutual_info_score(labels_full.as_slice(), labels_streamed.as_slice()) > 0.95);
+        assert!(normalized_mutual_info_score(y.as_slice(), labels_streamed.as_slice()) > 0.95);
+
+        let mut model = Model::from_options(ModelOptions::<NIW>::default(2));
+        assert_eq!(model.fit_batches(Vec::new, &fit_options).unwrap_err(), Error::EmptyData);
+        assert_eq!(
+            model.fit_batches(|| vec![DMatrix::zeros(3, 5)], &fit_options).unwrap_err(),
+            Error::DimensionMismatch { expected: 2, got: 3 }
+        );
+
+        // Later passes must yield the batches of the first
+        let sizes = RefCell::new(vec![100, 50]);
+        let err = model.fit_batches(|| {
+            let batch = x.columns(0, sizes.borrow_mut().pop().unwrap_or(100)).into_owned();
+            vec![batch]
+        }, &fit_options).unwrap_err();
+        assert!(matches!(err, Error::InvalidData(_)), "{:?}", err);
+
+        let mut constrained = fit_options.clone();
+        constrained.frozen = Some(vec![false; x.ncols()]);
+        assert!(matches!(model.fit_batches(|| vec![x.clone()], &constrained), Err(Error::InvalidData(_))));
+
+        let mut seeded = fit_options.clone();
+        seeded.init_strategy = InitStrategy::KMeansPlusPlus { n: 3 };
+        assert!(matches!(model.fit_batches(|| vec![x.clone()], &seeded), Err(Error::InvalidData(_))));
+    }
+
+    #[test]
+    fn test_fit_batches_shares_fit_loop() {
+        let centers = DMatrix::from_column_slice(2, 2, &[0.0, 0.0, 10.0, 0.0]);
+        let (x, _) = make_blobs(100, &centers, 1.0, 42);
+        // A tiny isolated group of points
+        let x = DMatrix::from_fn(2, 203, |r, c| if c < 200 { x[(r, c)] } else { 60.0 + c as f64 * 0.01 });
+        let data = &x;
+        let batches = || (0..3).map(move |c| data.select_columns((c..data.ncols()).step_by(3).collect::<Vec<_>>().iter()));
+
+        let mut model_options = ModelOptions::<NIW>::default(2);
+        model_options.outlier = None;
+        model_options.min_cluster_size = 10;
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 50;
+        fit_options.init_clusters = 3;
+
+        let mut model = Model::from_options(model_options.clone());
+        model.fit_batches(batches, &fit_options).unwrap();
+        let sizes = model.params().cluster_sizes();
+        assert!(sizes.iter().all(|&n| n >= 10), "sizes {:?}", sizes);
+
+        fit_options.stop_criteria.time_budget = Some(Duration::ZERO);
+        fit_options.record_label_trace = true;
+        let result = model.fit_batches(batches, &fit_options).unwrap();
+        assert_eq!(result.stop_reason, StopReason::TimeBudget);
+        assert_eq!(result.n_iters, 1);
+        assert_eq!(result.label_trace.unwrap().ncols(), x.ncols());
+    }
+
+    #[test]
+    fn test_checkpoint_callback() {
+        let x = DMatrix::new_random(2, 100);
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 23;
+
+        let mut checkpoints = Vec::new();
+        let mut model = Model::from_options(ModelOptions::<NIW>::default(2));
+        let callback = CheckpointCallback::new(5, |i, state: &GlobalState<NIW>| {
+            checkpoints.push((i, state.clusters.len()));
+        });
+        model.fit(x, &fit_options, Some(callback)).unwrap();
+
+        let iters: Vec<usize> = checkpoints.iter().map(|&(i, _)| i).collect();
+        assert_eq!(iters, vec![4, 9, 14, 19]);
+        assert!(checkpoints.iter().all(|&(_, n_clusters)| n_clusters > 0));
+    }
+
+    #[test]
+    fn test_log_partition_prior() {
+        // CRP probability of the partition {{1, 2}, {3}} with alpha = 1: 1 * 1/2 * 1/3
+        statrs::assert_almost_eq!(log_partition_prior(&[2, 1], 1.0), (1.0f64 / 6.0).ln(), 1e-12);
+        assert_eq!(log_partition_prior(&[2, 0, 1], 1.0), log_partition_prior(&[2, 1], 1.0));
+    }
+
+    #[test]
+    fn test_deterministic_parallel_fit() {
+        let centers = DMatrix::from_column_slice(2, 3, &[0.0, 0.0, 10.0, 0.0, 0.0, 10.0]);
+        let (x, _) = make_blobs(100, &centers, 1.0, 42);
+
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 30;
+        fit_options.workers = 4;
+        fit_options.keep_local = true;
+
+        // Equivalent to running with different RAYON_NUM_THREADS
+        let fit_with_threads = |n_threads: usize| {
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(n_threads).build().unwrap();
+            pool.install(|| {
+                let mut model = Model::from_options(ModelOptions::<NIW>::default(2));
+                model.fit(x.clone(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+                model.into_parts().1.unwrap().labels
+            })
+        };
+
+        assert_eq!(fit_with_threads(1), fit_with_threads(4));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_split_rng_checkpoint() {
+        let centers = DMatrix::from_column_slice(2, 3, &[0.0, 0.0, 6.0, 0.0, 0.0, 6.0]);
+        let (x, _) = make_blobs(100, &centers, 1.0, 42);
+        let model_options = ModelOptions::<NIW>::default(2);
+
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 10;
+        let mut model = Model::from_options(model_options.clone());
+        model.fit(x.clone_owned(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+
+        // Continue one chain from the in-memory state and one from a serialized checkpoint of it
+        let bytes = bincode::serialize(model.params()).unwrap();
+        let mut restored = Model::from_options(model_options);
+        restored.global = Some(bincode::deserialize(&bytes).unwrap());
+
+        fit_options.reuse = true;
+        fit_options.keep_local = true;
+        model.fit(x.clone_owned(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+        restored.fit(x, &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+
+        assert_eq!(restored.params().split_rng, model.params().split_rng);
+        assert_eq!(restored.params().split_rng.counter, 20);
+        assert_eq!(restored.params().weights, model.params().weights);
+        let model = model.into_parts().1.unwrap();
+        let restored = restored.into_parts().1.unwrap();
+        assert_eq!(restored.labels, model.labels);
+    }
+
+    #[test]
+    fn test_fit_resume() {
+        let centers = DMatrix::from_column_slice(2, 3, &[0.0, 0.0, 10.0, 0.0, 0.0, 10.0]);
+        let (x, y) = make_blobs(100, &centers, 1.0, 42);
+        let model_options = ModelOptions::<NIW>::default(2);
+
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 100;
+        let mut full = Model::from_options(model_options.clone());
+        full.fit(x.clone_owned(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+
+        fit_options.iters = 50;
+        let mut first = Model::from_options(model_options.clone());
+        first.fit(x.clone_owned(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+        let checkpoint = first.params().clone();
+
+        let mut resumed = Model::from_options(model_options);
+        resumed.fit_resume(checkpoint, x.clone_owned(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+        assert_eq!(resumed.params().split_rng.counter, 100);
+
+        let ll_full = full.mean_log_likelihood(x.clone_owned());
+        let ll_resumed = resumed.mean_log_likelihood(x.clone_owned());
+        assert!((ll_full - ll_resumed).abs() < 0.1, "full {} resumed {}", ll_full, ll_resumed);
+
+        let (_, labels) = resumed.predict(x);
+        assert!(normalized_mutual_info_score(y.as_slice(), labels.as_slice()) > 0.9);
+
+        // The clusters of the checkpoint must match the dimensionality of the model
+        let mut wrong_dim = Model::from_options(ModelOptions::<NIW>::default(3));
+        let result = wrong_dim.fit_resume(
+            first.params().clone(), DMatrix::new_random(3, 100), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>,
+        );
+        assert_eq!(result.unwrap_err(), Error::DimensionMismatch { expected: 3, got: 2 });
+        assert!(!wrong_dim.is_fitted());
+    }
+
+    #[test]
+    fn test_fit_frozen() {
+        let centers = DMatrix::from_column_slice(2, 3, &[0.0, 0.0, 10.0, 0.0, 0.0, 10.0]);
+        let (x, _) = make_blobs(100, &centers, 1.0, 42);
+        let frozen: Vec<bool> = (0..x.ncols()).map(|i| i % 10 == 0).collect();
+
+        // The mask reaches the local state of single and sharded fits
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 20;
+        fit_options.frozen = Some(frozen.clone());
+        fit_options.keep_local = true;
+        for workers in [1, 2] {
+            fit_options.workers = workers;
+            let mut model = Model::from_options(ModelOptions::<NIW>::default(2));
+            model.fit(x.clone(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+            let local = model.into_parts().1.unwrap();
+            assert_eq!(local.frozen.as_ref(), Some(&frozen));
+        }
+
+        // Resumed fits keep the predicted labels of the frozen points (up to removed empty clusters)
+        fit_options.workers = 1;
+        let mut model = Model::from_options(ModelOptions::<NIW>::default(2));
+        model.fit(x.clone(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+        let checkpoint = model.params().clone();
+        let predicted = checkpoint.predict(&x, &model.model_options);
+        fit_options.iter_split_stop = fit_options.iters;
+        let mut resumed = Model::from_options(ModelOptions::<NIW>::default(2));
+        resumed.fit_resume(checkpoint, x.clone(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+        let local = resumed.into_parts().1.unwrap();
+        let frozen_idx: Vec<_> = (0..x.ncols()).filter(|&i| frozen[i]).collect();
+        for &i in &frozen_idx {
+            for &j in &frozen_idx {
+                assert_eq!(local.labels[i] == local.labels[j], predicted[i] == predicted[j]);
+            }
+        }
+
+        // The mask must cover every point
+        fit_options.frozen = Some(vec![true; 10]);
+        let mut model = Model::from_options(ModelOptions::<NIW>::default(2));
+        let result = model.fit(x, &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>);
+        assert!(matches!(result, Err(Error::InvalidData(_))));
+    }
+
+    #[test]
+    fn test_fit_weights() {
+        let centers = DMatrix::from_column_slice(2, 3, &[0.0, 0.0, 10.0, 0.0, 0.0, 10.0]);
+        let (x, _) = make_blobs(100, &centers, 1.0, 42);
+        let weights: Vec<f64> = (0..x.ncols()).map(|i| (i % 3 + 1) as f64).collect();
+        let total = weights.iter().sum::<f64>() as usize;
+
+        // The weights reach the local state of single and sharded fits, also with subsampled and
+        // parallel statistics
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 20;
+        fit_options.weights = Some(weights.clone());
+        fit_options.keep_local = true;
+        fit_options.max_stats_points = Some(50);
+        for (workers, par_stats_chunk) in [(1, None), (2, None), (1, Some(20))] {
+            fit_options.workers = workers;
+            fit_options.par_stats_chunk = par_stats_chunk;
+            let mut model = Model::from_options(ModelOptions::<NIW>::default(2));
+            model.fit(x.clone(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+            let n_clusters = model.n_clusters();
+            let local = model.into_parts().1.unwrap();
+            assert_eq!(local.weights, Some(DVector::from_vec(weights.clone())));
+            assert_eq!(local.collect_data_stats().n_points, total);
+            let stats = local.collect_cluster_stats(n_clusters);
+            assert_eq!(stats.iter().map(|stats| stats.prim.n_points).sum::<usize>(), total);
+        }
+
+        // The weights must cover every point and be non-negative
+        for weights in [vec![1.0; 10], vec![-1.0; x.ncols()]] {
+            fit_options.weights = Some(weights);
+            let mut model = Model::from_options(ModelOptions::<NIW>::default(2));
+            let result = model.fit(x.clone(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>);
+            assert!(matches!(result, Err(Error::InvalidData(_))));
+        }
+    }
+
+    #[test]
+    fn test_dim_mismatch() {
+        let x = DMatrix::new_random(3, 100);
+
+        let mut model = Model::from_options(ModelOptions::<NIW>::default(2));
+        let result = model.fit(x, &FitOptions::default(), None::<MonitoringCallback<GlobalState<NIW>>>);
+
+        assert_eq!(result.unwrap_err(), Error::DimensionMismatch { expected: 2, got: 3 });
+        assert_eq!(model.dim(), 2);
+        assert!(!model.is_fitted());
+    }
+
+    #[test]
+    fn test_sample_label() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let x = DMatrix::new_random(2, 100);
+
+        let mut model_options = ModelOptions::<NIW>::default(2);
+        model_options.outlier = None;
+        let mut global = GlobalState::from_init(&NIWStats::from_data(&x), 2, &model_options, &mut rng);
+        for (cluster, mu) in global.clusters.iter_mut().zip([-1.0, 1.0]) {
+            cluster.prim.dist = MultivariateNormal::new(vec![mu, 0.0], vec![1.0, 0.0, 0.0, 1.0]).unwrap();
+        }
+        global.weights = vec![0.5, 0.5];
+
+        let mut model = Model::from_global(model_options, global);
+
+        // A point near the decision boundary
+        let point = DVector::from_vec(vec![0.3, 0.0]);
+        let (probs, _) = model.predict(DMatrix::from_column_slice(2, 1, point.as_slice()));
+
+        let n_samples = 5000;
+        let mut counts = [0usize; 2];
+        for _ in 0..n_samples {
+            counts[model.sample_label(&point, &mut rng).unwrap()] += 1;
+        }
+
+        assert!(counts[0] > 0 && counts[1] > 0);
+        for k in 0..2 {
+            let freq = counts[k] as f64 / n_samples as f64;
+            assert!((freq - probs[(k, 0)]).abs() < 0.03, "cluster {}: {} vs {}", k, freq, probs[(k, 0)]);
+        }
+    }
+
+    #[test]
+    fn test_into_parts() {
+        let x = DMatrix::new_random(2, 100);
+
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 10;
+        fit_options.workers = 2;
+
+        // The local state is only kept on request
+        let mut model = Model::from_options(ModelOptions::<NIW>::default(2));
+        model.fit(x.clone_owned(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+        assert!(model.into_parts().1.is_none());
+
+        fit_options.keep_local = true;
+        let mut model = Model::from_options(ModelOptions::<NIW>::default(2));
+        model.fit(x.clone_owned(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+        let n_clusters = model.n_clusters();
+
+        // The model is moved into its parts
+        let (global, local, model_options, fit_options) = model.into_parts();
+        let local = local.unwrap();
+        assert_eq!(global.clusters.len(), n_clusters);
+        assert_eq!(local.data, x);
+        assert!(local.labels.iter().all(|&l| l < n_clusters));
+        assert_eq!(model_options.dim, 2);
+        assert_eq!(fit_options.iters, 10);
+        assert!(fit_options.keep_local);
+    }
+
+    #[test]
+    fn test_noise_stability() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let centers = DMatrix::from_column_slice(2, 2, &[0.0, 0.0, 10.0, 10.0]);
+        let (x, _) = make_blobs(100, &centers, 1.0, 42);
+
+        let mut model_options = ModelOptions::<NIW>::default(2);
+        model_options.outlier = None;
+        let model = sklearn_model(
+            &centers.transpose(),
+            &[DMatrix::identity(2, 2), DMatrix::identity(2, 2)],
+            &[0.5, 0.5],
+            model_options,
+        );
+
+        let small = model.noise_stability(&x, 0.01, 5, &mut rng);
+        let large = model.noise_stability(&x, 10.0, 5, &mut rng);
+        assert!(small > 0.99, "small noise stability {}", small);
+        assert!(large < small && large < 0.9, "large noise stability {}", large);
+    }
+
+    #[test]
+    fn test_fit_errors() {
+        let x = DMatrix::new_random(2, 100);
+        let fit = |model_options: ModelOptions<NIW>, x: DMatrix<f64>, fit_options: &FitOptions| {
+            Model::from_options(model_options)
+                .fit(x, fit_options, None::<MonitoringCallback<GlobalState<NIW>>>)
+                .unwrap_err()
+        };
+
+        let mut model_options = ModelOptions::<NIW>::default(2);
+        model_options.alpha = 0.0;
+        assert!(matches!(fit(model_options, x.clone_owned(), &FitOptions::default()), Error::InvalidData(_)));
+
+        let mut model_options = ModelOptions::<NIW>::default(2);
+        model_options.alpha_schedule = AlphaSchedule::Linear { start: -1.0, end: 1.0 };
+        assert!(matches!(fit(model_options, x.clone_owned(), &FitOptions::default()), Error::InvalidData(_)));
+
+        let mut model_options = ModelOptions::<NIW>::default(2);
+        model_options.data_dist.psi = DMatrix::zeros(2, 2);
+        assert_eq!(fit(model_options, x.clone_owned(), &FitOptions::default()), Error::SingularCovariance);
+
+        let mut fit_options = FitOptions::default();
+        fit_options.init_clusters = 0;
+        assert_eq!(fit(ModelOptions::default(2), x.clone_owned(), &fit_options), Error::EmptyCluster);
+
+        assert_eq!(
+            fit(ModelOptions::default(3), x.clone_owned(), &FitOptions::default()),
+            Error::DimensionMismatch { expected: 3, got: 2 }
+        );
+
+        let mut model_options = ModelOptions::<NIW>::default(2);
+        model_options.outlier.as_mut().unwrap().dist = NIWParams::default(3);
+        assert_eq!(
+            fit(model_options, x.clone_owned(), &FitOptions::default()),
+            Error::DimensionMismatch { expected: 2, got: 3 }
+        );
+
+        let mut nan = x.clone_owned();
+        nan[(1, 5)] = f64::NAN;
+        assert_eq!(fit(ModelOptions::default(2), nan, &FitOptions::default()), Error::NonFiniteData { feature: 1, point: 5 });
+        assert_eq!(fit(ModelOptions::default(2), DMatrix::zeros(2, 0), &FitOptions::default()), Error::EmptyData);
+
+        let mut inf = x.clone_owned();
+        inf[(0, 3)] = f64::NEG_INFINITY;
+        assert_eq!(fit(ModelOptions::default(2), inf, &FitOptions::default()), Error::NonFiniteData { feature: 0, point: 3 });
+
+        let mut fit_options = FitOptions::default();
+        fit_options.reuse = true;
+        assert!(matches!(fit(ModelOptions::default(2), x.clone_owned(), &fit_options), Error::InvalidData(_)));
+
+        let mut fit_options = FitOptions::default();
+        fit_options.constraints = Some(PairwiseConstraints::new(vec![(0, 1)], vec![]));
+        fit_options.workers = 2;
+        assert!(matches!(fit(ModelOptions::default(2), x.clone_owned(), &fit_options), Error::InvalidData(_)));
+    }
+
+    #[test]
+    fn test_predictive_uncertainty() {
+        let mut model_options = ModelOptions::<NIW>::default(2);
+        model_options.outlier = None;
+        let model = sklearn_model(
+            &DMatrix::from_row_slice(2, 2, &[0.0, 0.0, 10.0, 10.0]),
+            &[DMatrix::identity(2, 2), DMatrix::identity(2, 2)],
+            &[0.5, 0.5],
+            model_options,
+        );
+
+        let queries = DMatrix::from_column_slice(2, 3, &[0.1, -0.1, 10.0, 9.5, 50.0, -50.0]);
+        let scores = model.predictive_uncertainty(queries);
+        assert!(scores[2] > scores[0]);
+        assert!(scores[2] > scores[1]);
+    }
+
+    #[test]
+    fn test_validation_fraction() {
+        let centers = DMatrix::from_column_slice(2, 2, &[0.0, 0.0, 10.0, 10.0]);
+        let (x, _) = make_blobs(50, &centers, 1.0, 42);
+
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 20;
+        fit_options.validation_fraction = Some(0.2);
+        fit_options.keep_local = true;
+
+        let mut model = Model::from_options(ModelOptions::<NIW>::default(2));
+        let result = model.fit(x, &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+
+        assert_eq!(result.validation_points, 20);
+        assert!(result.validation_log_likelihood.unwrap().is_finite());
+        assert_eq!(model.local.as_ref().unwrap().n_points(), 80);
+    }
+
+    #[test]
+    fn test_explain_assignment() {
+        let mut model_options = ModelOptions::<NIW>::default(3);
+        model_options.outlier = None;
+        let model = sklearn_model(
+            &DMatrix::from_row_slice(2, 3, &[0.0, 0.0, 0.0, 20.0, 20.0, 20.0]),
+            &[DMatrix::from_diagonal_element(3, 3, 1.0), DMatrix::from_diagonal_element(3, 3, 1.0)],
+            &[0.5, 0.5],
+            model_options,
+        );
+
+        let explanation = model.explain_assignment(&DVector::from_vec(vec![0.5, 3.0, -1.0]), 2);
+        assert_eq!(explanation.len(), 2);
+        assert_eq!(explanation[0].0, 1);
+        assert_eq!(explanation[1].0, 2);
+        statrs::assert_almost_eq!(explanation[0].1, 9.0, 1e-10);
+    }
+
+    #[test]
+    fn test_fit_u32_labels() {
+        use rand::rngs::SmallRng;
+
+        let centers = DMatrix::from_column_slice(2, 3, &[0.0, 0.0, 6.0, 6.0, -6.0, 6.0]);
+        let (x, _) = make_blobs(50, &centers, 1.0, 42);
+
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 20;
+
+        let mut local_usize = LocalState::<NIW>::from_data(x.clone());
+        local_usize.init(fit_options.init_clusters, &mut SmallRng::seed_from_u64(1));
+        let mut model = Model::from_options(ModelOptions::<NIW>::default(2));
+        model.fit_worker(&mut local_usize, &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+
+        let mut local_u32 = LocalState::<NIW, u32>::from_data(x);
+        local_u32.init(fit_options.init_clusters, &mut SmallRng::seed_from_u64(1));
+        let mut model = Model::from_options(ModelOptions::<NIW>::default(2));
+        model.fit_worker(&mut local_u32, &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+
+        assert_eq!(local_u32.collect_labels(), local_usize.collect_labels());
+        assert_eq!(local_u32.labels.map(|l| l as usize), local_usize.labels);
+    }
+
+    #[test]
+    fn test_fit_f32() {
+        use crate::model::ModelF32;
+
+        let centers = DMatrix::from_column_slice(2, 3, &[0.0, 0.0, 10.0, 0.0, 0.0, 10.0]);
+        let (x, y) = make_blobs(100, &centers, 1.0, 42);
+
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 50;
+        fit_options.keep_local = true;
+        for workers in [1, 2] {
+            fit_options.workers = workers;
+            let mut model = ModelF32::new(ModelOptions::<NIW>::default(2));
+            model.fit(x.map(|v| v as f32), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+
+            let (_, labels) = model.predict(x.clone());
+            let nmi = normalized_mutual_info_score(y.as_slice(), labels.as_slice());
+            assert!(nmi > 0.9, "workers {}: nmi {}", workers, nmi);
+
+            let local = model.into_parts().1.unwrap();
+            assert_eq!(local.data, x.map(|v| v as f32));
+        }
+    }
+
+    #[test]
+    fn test_predict_with_prior() {
+        let means = DMatrix::from_row_slice(2, 1, &[-1.0, 1.0]);
+        let covariances = vec![DMatrix::identity(1, 1); 2];
+        let mut model_options = ModelOptions::<NIW>::default(1);
+        model_options.outlier = None;
+        // Without an outlier cluster, low density points are not reassigned to cluster 0
+        model_options.outlier_min_density = Some(0.5);
+        let model = sklearn_model(&means, &covariances, &[0.5, 0.5], model_options);
+
+        // Boundary points slightly on the side of the first cluster
+        let data = DMatrix::from_row_slice(1, 3, &[-0.2, -0.1, 3.0]);
+        let labels = model.predict_with_prior(data.clone(), &[0.5f64.ln(), 0.5f64.ln()]);
+        assert_eq!(labels.as_slice(), &[0, 0, 1]);
+
+        // A strong prior pulls the boundary points to the favored cluster
+        let labels = model.predict_with_prior(data, &[0.01f64.ln(), 0.99f64.ln()]);
+        assert_eq!(labels.as_slice(), &[1, 1, 1]);
+    }
+
+    #[test]
+    fn test_outlier_min_density_prediction_paths() {
+        let mut model_options = ModelOptions::<NIW>::default(1);
+        model_options.outlier_min_density = Some(0.01);
+        let means = DMatrix::from_row_slice(1, 1, &[0.0]);
+        let mut global = GlobalState::from_sklearn_params(
+            &means, &[DMatrix::identity(1, 1)], &[1.0], &model_options, &mut StdRng::seed_from_u64(42),
+        ).unwrap();
+        // A broad outlier cluster that never wins on its own density near the other cluster
+        global.clusters[0].prim.dist = MultivariateNormal::new(vec![0.0], vec![1e6]).unwrap();
+        let mut model = Model::from_global(model_options, global);
+
+        // Only the last point falls below the minimum density of N(0, 1)
+        let data = DMatrix::from_row_slice(1, 3, &[0.0, 2.0, 3.0]);
+        let expected = [1, 1, 0];
+        let (probs, labels) = model.predict(data.clone());
+        assert_eq!(labels.as_slice(), &expected);
+        assert_eq!(probs[(0, 2)], 1.0);
+        assert_eq!(model.predict_slice(data.as_slice(), 3, 1).as_slice(), &expected);
+        assert_eq!(model.predict_with_prior(data.clone(), &[0.5f64.ln(), 0.5f64.ln()]).as_slice(), &expected);
+        let assigned: Vec<usize> = model.assignments(&data).map(|(_, label, _)| label).collect();
+        assert_eq!(assigned, expected);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_predict_with_prior_length() {
+        let means = DMatrix::from_row_slice(2, 1, &[-1.0, 1.0]);
+        let covariances = vec![DMatrix::identity(1, 1); 2];
+        let mut model_options = ModelOptions::<NIW>::default(1);
+        model_options.outlier = None;
+        let model = sklearn_model(&means, &covariances, &[0.5, 0.5], model_options);
+
+        model.predict_with_prior(DMatrix::zeros(1, 3), &[0.0]);
+    }
+
+    #[test]
+    fn test_detect_multimodal_clusters() {
+        let centers = DMatrix::from_column_slice(2, 3, &[-4.0, 0.0, 4.0, 0.0, 0.0, 20.0]);
+        let (x, _) = make_blobs(100, &centers, 1.0, 42);
+
+        // The first two blobs are merged into a single wide cluster
+        let mut model_options = ModelOptions::<NIW>::default(2);
+        model_options.outlier = None;
+        let model = sklearn_model(
+            &DMatrix::from_row_slice(2, 2, &[0.0, 0.0, 0.0, 20.0]),
+            &[DMatrix::from_diagonal(&DVector::from_column_slice(&[17.0, 1.0])), DMatrix::identity(2, 2)],
+            &[2.0 / 3.0, 1.0 / 3.0],
+            model_options,
+        );
+
+        assert_eq!(model.detect_multimodal_clusters(&x), vec![0]);
+    }
+
+    #[test]
+    fn test_fit_moves_data() {
+        let x = DMatrix::new_random(2, 100);
+        let ptr = x.as_ptr();
+
+        let mut fit_options = FitOptions::default();
+        fit_options.workers = 1;
+        fit_options.iters = 5;
+        fit_options.keep_local = true;
+
+        let mut model = Model::from_options(ModelOptions::<NIW>::default(2));
+        model.fit(x, &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+
+        // The local state owns the original buffer
+        let local = model.into_parts().1.unwrap();
+        assert_eq!(local.data.as_ptr(), ptr);
+    }
+
+    #[test]
+    fn test_fit_csv() {
+        let centers = DMatrix::from_column_slice(2, 3, &[0.0, 0.0, 10.0, 0.0, 0.0, 10.0]);
+        let (x, y) = make_blobs(100, &centers, 1.0, 42);
+        let mut csv = "a,b\n".to_string();
+        for point in x.column_iter() {
+            csv.push_str(&format!("{},{}\n", point[0], point[1]));
+        }
+
+        let mut model_options = ModelOptions::<NIW>::default(2);
+        model_options.outlier = None;
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 30;
+
+        // The chunks are the mini-batches of an in-memory streamed fit over all the data
+        let mut streamed = Model::from_options(model_options.clone());
+        streamed.fit_batches(|| (0..3).map(|c| x.columns(c * 100, 100).into_owned()), &fit_options).unwrap();
+        let mut chunked = Model::from_options(model_options.clone());
+        chunked.fit_csv_reader(|| Ok(Cursor::new(csv.as_bytes())), 100, &fit_options).unwrap();
+        assert_eq!(chunked.n_clusters(), streamed.n_clusters());
+        assert_eq!(chunked.params().weights, streamed.params().weights);
+
+        // and recover the blobs like the in-memory fit
+        let mut full = Model::from_options(model_options);
+        full.fit(x.clone(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+        let (_, labels_full) = full.predict(x.clone());
+        let (_, labels) = chunked.predict(x);
+        assert!(normalized_mutual_info_score(labels_full.as_slice(), labels.as_slice()) > 0.95);
+        assert!(normalized_mutual_info_score(y.as_slice(), labels.as_slice()) > 0.9);
+
+        let mut model = Model::from_options(ModelOptions::<NIW>::default(2));
+        assert!(matches!(
+            model.fit_csv_reader(|| Ok(Cursor::new("1,2\n3,4,5\n")), 1, &fit_options),
+            Err(Error::InvalidData(_))
+        ));
+    }
+
+    #[test]
+    fn test_fit_dedup_init() {
+        let points = DMatrix::from_column_slice(2, 6, &[0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 10.0, 10.0, 11.0, 10.0, 10.0, 11.0]);
+        let x = DMatrix::from_fn(2, 600, |i, j| points[(i, j % 6)]);
+
+        let mut model_options = ModelOptions::<NIW>::default(2);
+        model_options.outlier = None;
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 30;
+        fit_options.record_label_trace = true;
+        fit_options.return_responsibilities = true;
+        fit_options.keep_local = true;
+
+        let mut naive = Model::from_options(model_options.clone());
+        naive.fit(x.clone(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+
+        fit_options.dedup_init = true;
+        let mut dedup = Model::from_options(model_options);
+        let result = dedup.fit(x.clone(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+
+        // The per-point results are expanded back to every point
+        assert_eq!(result.label_trace.unwrap().ncols(), 600);
+        assert_eq!(result.responsibilities.unwrap().nrows(), 600);
+
+        // Same partition up to the cluster ids, with the same cluster statistics as the fit on all points
+        let (naive_global, naive_local, _, _) = naive.into_parts();
+        let (dedup_global, dedup_local, _, _) = dedup.into_parts();
+        let (naive_local, dedup_local) = (naive_local.unwrap(), dedup_local.unwrap());
+        assert_eq!(dedup_local.data, x);
+        assert_eq!(dedup_local.weights, None);
+        statrs::assert_almost_eq!(
+            normalized_mutual_info_score(naive_local.labels.as_slice(), dedup_local.labels.as_slice()), 1.0, 1e-9
+        );
+        assert_eq!(dedup_global.clusters.len(), naive_global.clusters.len());
+        for (&k_naive, &k_dedup) in naive_local.labels.iter().zip(dedup_local.labels.iter()) {
+            let (naive_prim, dedup_prim) = (&naive_global.clusters[k_naive].prim, &dedup_global.clusters[k_dedup].prim);
+            assert_eq!(dedup_prim.stats.n_points, naive_prim.stats.n_points);
+            test_almost_mat(&dedup_prim.post.mu, &naive_prim.post.mu, 1e-9);
+            test_almost_mat(&dedup_prim.post.psi, &naive_prim.post.psi, 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_predict_slice() {
+        let centers = DMatrix::from_column_slice(2, 3, &[0.0, 0.0, 10.0, 0.0, 0.0, 10.0]);
+        // More points than fit in a single chunk
+        let (x, _) = make_blobs(400, &centers, 1.0, 42);
+
+        let mut model_options = ModelOptions::<NIW>::default(2);
+        model_options.outlier = None;
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 20;
+        let mut model = Model::from_options(model_options);
+        model.fit(x.clone(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+        assert!(x.ncols() > BORROWED_CHUNK_SIZE);
+
+        // Row-major (n_points, n_features) buffer
+        let buffer: Vec<f64> = x.transpose().row_iter().flat_map(|row| row.iter().cloned().collect::<Vec<_>>()).collect();
+        let labels = model.predict_slice(&buffer, x.ncols(), x.nrows());
+
+        let (_, expected) = model.predict(x);
+        assert_eq!(labels, expected.transpose());
+    }
+
+    #[test]
+    fn test_assignment_entropy() {
+        let means = DMatrix::from_row_slice(2, 1, &[-2.0, 2.0]);
+        let covariances = vec![DMatrix::identity(1, 1); 2];
+        let mut model_options = ModelOptions::<NIW>::default(1);
+        model_options.outlier = None;
+        let model = sklearn_model(&means, &covariances, &[0.5, 0.5], model_options);
+
+        let x = DMatrix::from_row_slice(1, 2, &[0.0, -5.0]);
+        let entropy = model.assignment_entropy(x.clone());
+        statrs::assert_almost_eq!(entropy[0], 2.0f64.ln(), 1e-9);
+        assert!(entropy[1] < 1e-3);
+
+        // The mean entropy of the global state agrees with the per-point entropies
+        let mean = model.params().mean_assignment_entropy(&x, &model.model_options);
+        statrs::assert_almost_eq!(mean, entropy.mean(), 1e-12);
+    }
+
+    #[test]
+    fn test_posterior_predictive_sample() {
+        let centers = DMatrix::from_column_slice(2, 3, &[0.0, 0.0, 6.0, 0.0, 3.0, 6.0]);
+        let (x, _) = make_blobs(300, &centers, 1.0, 42);
+
+        let mut model_options = ModelOptions::<NIW>::default(2);
+        model_options.outlier = None;
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 30;
+        let mut model = Model::from_options(model_options);
+        model.fit(x.clone(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+
+        let samples = model.posterior_predictive_sample(5000, &mut StdRng::seed_from_u64(42)).unwrap();
+        assert_eq!(samples.shape(), (2, 5000));
+
+        let (mean, sample_mean) = (x.column_mean(), samples.column_mean());
+        for d in 0..2 {
+            assert!((mean[d] - sample_mean[d]).abs() < 0.3, "feature {}: {} vs {}", d, mean[d], sample_mean[d]);
+        }
+
+        model.global.as_mut().unwrap().weights.iter_mut().for_each(|w| *w = 0.0);
+        assert_eq!(
+            model.posterior_predictive_sample(10, &mut StdRng::seed_from_u64(42)),
+            Err(Error::DegenerateWeights)
+        );
+    }
+
+    #[test]
+    fn test_cluster_adjacency() {
+        let means = DMatrix::from_row_slice(3, 1, &[-1.0, 1.0, 50.0]);
+        let covariances = vec![DMatrix::identity(1, 1); 3];
+        let mut model_options = ModelOptions::<NIW>::default(1);
+        model_options.outlier = None;
+        let model = sklearn_model(&means, &covariances, &[1.0 / 3.0; 3], model_options);
+
+        let centers = DMatrix::from_row_slice(1, 3, &[-1.0, 1.0, 50.0]);
+        let (x, _) = make_blobs(200, &centers, 1.0, 42);
+        let adjacency = model.cluster_adjacency(&x, 0.5);
+
+        assert_eq!(adjacency, adjacency.transpose());
+        assert!(adjacency[(0, 1)] > 0);
+        assert_eq!(adjacency[(0, 2)], 0);
+        assert_eq!(adjacency[(1, 2)], 0);
+        assert!((0..3).all(|k| adjacency[(k, k)] == 0));
+    }
+
+    #[test]
+    fn test_assignments() {
+        let centers = DMatrix::from_column_slice(2, 3, &[0.0, 0.0, 4.0, 0.0, 0.0, 4.0]);
+        let (x, _) = make_blobs(1000, &centers, 1.0, 42);
+
+        let mut model_options = ModelOptions::<NIW>::default(2);
+        model_options.outlier = None;
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 20;
+        let mut model = Model::from_options(model_options);
+        model.fit(x.clone(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+
+        let assignments: Vec<_> = model.assignments(&x).collect();
+        let (probs, labels) = model.predict(x.clone());
+
+        assert_eq!(assignments.len(), x.ncols());
+        for (j, &(i, label, responsibility)) in assignments.iter().enumerate() {
+            assert_eq!(i, j);
+            assert_eq!(label, labels[i]);
+            statrs::assert_almost_eq!(responsibility, probs[(label, i)], 1e-12);
+        }
+    }
+}
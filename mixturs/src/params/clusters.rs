@@ -4,9 +4,16 @@ use std::iter::Sum;
 use std::ops::{Add, AddAssign};
 use rand::{Rng, distributions::Distribution};
 use statrs::distribution::{Dirichlet, MultivariateNormal};
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
 use crate::stats::{NormalConjugatePrior, SufficientStats};
 
 /// Parameters for a supercluster.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(
+    serialize = "P::HyperParams: Serialize, P::SuffStats: Serialize",
+    deserialize = "P::HyperParams: Deserialize<'de>, P::SuffStats: Deserialize<'de>",
+)))]
 #[derive(Debug, Clone, PartialEq)]
 pub struct SuperClusterParams<P: NormalConjugatePrior> {
     /// Parameters for the primary cluster.
@@ -17,6 +24,9 @@ pub struct SuperClusterParams<P: NormalConjugatePrior> {
     pub weights: [f64; 2],
     /// Whether the supercluster is splittable.
     pub splittable: bool,
+    /// Number of consecutive iterations the supercluster has been splittable, i.e. for how long its
+    /// split proposal (auxiliary clusters) has been refined since convergence.
+    pub split_warmup: usize,
     /// History of the log likelihood of the supercluster to detect convergence.
     pub ll_history: LLHistory,
 }
@@ -41,6 +51,7 @@ impl<P: NormalConjugatePrior> SuperClusterParams<P> {
             aux,
             weights,
             splittable: false,
+            split_warmup: 0,
             ll_history: LLHistory::new(burnout_period),
         }
     }
@@ -66,6 +77,7 @@ impl<P: NormalConjugatePrior> SuperClusterParams<P> {
             aux: [prim_l, prim_r],
             weights,
             splittable: false,
+            split_warmup: 0,
             ll_history: LLHistory::new(burnout_period),
         }
     }
@@ -144,9 +156,31 @@ impl<P: NormalConjugatePrior> SufficientStats for SuperClusterStats<P> {
     fn n_points(&self) -> usize {
         self.prim.n_points()
     }
+
+    fn dim(&self) -> usize {
+        self.prim.dim()
+    }
+
+    fn rescaled(&self, n_points: usize) -> Self {
+        let factor = n_points as f64 / self.prim.n_points().max(1) as f64;
+        let aux_points = |aux: &P::SuffStats| (aux.n_points() as f64 * factor).round() as usize;
+
+        Self::new(
+            self.prim.rescaled(n_points),
+            [
+                self.aux[0].rescaled(aux_points(&self.aux[0])),
+                self.aux[1].rescaled(aux_points(&self.aux[1])),
+            ],
+        )
+    }
 }
 
 /// Parameters for a cluster.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(
+    serialize = "P::HyperParams: Serialize, P::SuffStats: Serialize",
+    deserialize = "P::HyperParams: Deserialize<'de>, P::SuffStats: Deserialize<'de>",
+)))]
 #[derive(Debug, Clone, PartialEq)]
 pub struct ClusterParams<P: NormalConjugatePrior> {
     /// Prior distribution params for the cluster.
@@ -156,9 +190,29 @@ pub struct ClusterParams<P: NormalConjugatePrior> {
     /// Sufficient statistics for the cluster.
     pub stats: P::SuffStats,
     /// Normal Distribution for the cluster.
+    #[cfg_attr(feature = "serde", serde(with = "mvn_serde"))]
     pub dist: MultivariateNormal,
 }
 
+/// (De)serializes a [`MultivariateNormal`] through its mean and covariance.
+#[cfg(feature = "serde")]
+mod mvn_serde {
+    use nalgebra::{DMatrix, DVector};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use serde::de::Error;
+    use statrs::distribution::MultivariateNormal;
+
+    pub fn serialize<S: Serializer>(dist: &MultivariateNormal, serializer: S) -> Result<S::Ok, S::Error> {
+        (dist.mu(), dist.cov()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<MultivariateNormal, D::Error> {
+        let (mu, cov) = <(DVector<f64>, DMatrix<f64>)>::deserialize(deserializer)?;
+        MultivariateNormal::new(mu.as_slice().to_vec(), cov.as_slice().to_vec())
+            .map_err(|_| D::Error::custom("covariance is not positive definite"))
+    }
+}
+
 impl<P: NormalConjugatePrior> ClusterParams<P> {
     pub fn new(prior: P::HyperParams, post: P::HyperParams, stats: P::SuffStats, dist: MultivariateNormal) -> Self {
         Self { prior, post, stats, dist }
@@ -184,6 +238,7 @@ impl<P: NormalConjugatePrior> ClusterParams<P> {
 
 
 /// Log likelihood history to track cluster convergence.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
 pub struct LLHistory {
     pub ll_history: VecDeque<f64>,
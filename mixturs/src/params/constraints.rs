@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use nalgebra::{DMatrix, DVector};
+use rand::Rng;
+use crate::error::Error;
+use crate::utils::{argmax_weighted_ties, Label, sample_weighted};
+
+/// Pairwise must-link and cannot-link constraints between data points (column indices) for
+/// semi-supervised clustering.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PairwiseConstraints {
+    /// Pairs of points that must share a label
+    pub must_link: Vec<(usize, usize)>,
+    /// Pairs of points that must not share a label
+    pub cannot_link: Vec<(usize, usize)>,
+}
+
+impl PairwiseConstraints {
+    pub fn new(must_link: Vec<(usize, usize)>, cannot_link: Vec<(usize, usize)>) -> Self {
+        Self { must_link, cannot_link }
+    }
+
+    /// Groups the constrained points into must-linked components.
+    ///
+    /// # Returns
+    ///
+    /// Tuple containing:
+    /// * Groups of points that must share a label
+    /// * Pairs of group indices that must not share a label
+    pub fn groups(&self) -> (Vec<Vec<usize>>, Vec<(usize, usize)>) {
+        let mut parent = HashMap::new();
+        for &(a, b) in self.must_link.iter().chain(&self.cannot_link) {
+            find_root(&mut parent, a);
+            find_root(&mut parent, b);
+        }
+        for &(a, b) in &self.must_link {
+            let (root_a, root_b) = (find_root(&mut parent, a), find_root(&mut parent, b));
+            if root_a != root_b {
+                parent.insert(root_a, root_b);
+            }
+        }
+
+        let mut points: Vec<usize> = parent.keys().cloned().collect();
+        points.sort_unstable();
+
+        let mut group_idx = HashMap::new();
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        for i in points {
+            let root = find_root(&mut parent, i);
+            let g = *group_idx.entry(root).or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+            groups[g].push(i);
+        }
+
+        let cannot_link = self.cannot_link.iter()
+            .map(|&(a, b)| (group_idx[&find_root(&mut parent, a)], group_idx[&find_root(&mut parent, b)]))
+            .collect();
+
+        (groups, cannot_link)
+    }
+
+    /// Reassigns the constrained points such that must-linked points share a label (sampled jointly from
+    /// their summed log likelihoods) and cannot-linked points are not assigned the same label.
+    /// If every cluster is excluded by cannot-link constraints, the constraints are ignored for that group.
+    ///
+    /// Frozen points keep their label and a group containing one follows it instead of being sampled, so
+    /// the other groups are kept apart from it. Constraints contradicting the frozen labels themselves
+    /// (e.g. cannot-linked points frozen to the same cluster) cannot be satisfied and are left violated.
+    ///
+    /// # Arguments
+    ///
+    /// * `log_likelihood`: A matrix of log probabilities of shape (n_clusters, n_samples)
+    /// * `weights`: The weight of each cluster, breaking exact ties of hard assignments as in
+    /// [`hard_assignment_weighted_ties`](crate::params::thin::hard_assignment_weighted_ties) (n_clusters)
+    /// * `labels`: The current cluster assignments which are updated in place
+    /// * `frozen`: Optional mask of points (n_samples) whose label is fixed
+    /// * `hard_assign`: Whether to pick the most likely cluster instead of sampling
+    /// * `rng`: A random number generator
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error`] naming the first point of a group whose joint log probabilities contain a NaN
+    /// or positive infinity, or are all negative infinity (zero weights).
+    pub fn apply<L: Label>(
+        &self,
+        log_likelihood: &DMatrix<f64>,
+        weights: &[f64],
+        labels: &mut [L],
+        frozen: Option<&[bool]>,
+        hard_assign: bool,
+        rng: &mut impl Rng,
+    ) -> Result<(), Error> {
+        let (groups, cannot_link) = self.groups();
+        let is_frozen = |i: usize| matches!(frozen, Some(frozen) if frozen[i]);
+
+        // Groups with a frozen point follow it, before the other groups are kept apart from them
+        let mut is_fixed = vec![false; groups.len()];
+        for (g, group) in groups.iter().enumerate() {
+            if let Some(&fixed) = group.iter().find(|&&i| is_frozen(i)) {
+                let label = labels[fixed];
+                for &i in group.iter().filter(|&&i| !is_frozen(i)) {
+                    labels[i] = label;
+                }
+                is_fixed[g] = true;
+            }
+        }
+
+        for (g, group) in groups.iter().enumerate().filter(|(g, _)| !is_fixed[*g]) {
+            let mut joint = DVector::zeros(log_likelihood.nrows());
+            for &i in group {
+                joint += log_likelihood.column(i);
+            }
+
+            // Exclude the clusters of cannot-linked groups
+            let mut masked = joint.clone();
+            for &(a, b) in &cannot_link {
+                let other = if a == g { b } else if b == g { a } else { continue };
+                masked[labels[groups[other][0]].as_usize()] = f64::NEG_INFINITY;
+            }
+            let joint = if masked.iter().any(|x| x.is_finite()) { masked } else { joint };
+
+            let point = group[0];
+            if let Some(&value) = joint.iter().find(|x| x.is_nan() || **x == f64::INFINITY) {
+                return Err(Error::InvalidWeight { point, value });
+            }
+
+            let label = if hard_assign {
+                argmax_weighted_ties(joint.iter().cloned(), weights).unwrap_or(0)
+            } else {
+                let max = joint.max();
+                sample_weighted(rng, joint.iter().map(|x| (x - max).exp()))
+                    .ok_or(Error::ZeroWeights { point })?
+            };
+
+            for &i in group {
+                labels[i] = L::from_usize(label);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn find_root(parent: &mut HashMap<usize, usize>, i: usize) -> usize {
+    let p = *parent.entry(i).or_insert(i);
+    if p == i {
+        i
+    } else {
+        let root = find_root(parent, p);
+        parent.insert(i, root);
+        root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DMatrix;
+    use rand::prelude::StdRng;
+    use rand::SeedableRng;
+    use crate::error::Error;
+    use crate::params::PairwiseConstraints;
+
+    #[test]
+    fn test_apply_errors() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let constraints = PairwiseConstraints::new(vec![(1, 2)], vec![]);
+        let weights = [0.5, 0.5];
+        let mut labels = [0usize; 3];
+
+        let zero = DMatrix::from_column_slice(2, 3, &[0.0, -1.0, f64::NEG_INFINITY, 0.0, -1.0, f64::NEG_INFINITY]);
+        assert_eq!(
+            constraints.apply(&zero, &weights, &mut labels, None, false, &mut rng),
+            Err(Error::ZeroWeights { point: 1 })
+        );
+
+        let nan = DMatrix::from_column_slice(2, 3, &[0.0, -1.0, -3.0, -2.0, f64::NAN, 0.0]);
+        for hard_assign in [true, false] {
+            assert!(matches!(
+                constraints.apply(&nan, &weights, &mut labels, None, hard_assign, &mut rng),
+                Err(Error::InvalidWeight { point: 1, value }) if value.is_nan()
+            ));
+        }
+
+        let ok = DMatrix::from_column_slice(2, 3, &[0.0, -1.0, f64::NEG_INFINITY, 0.0, f64::NEG_INFINITY, -1.0]);
+        assert_eq!(constraints.apply(&ok, &weights, &mut labels, None, false, &mut rng), Ok(()));
+        assert_eq!(labels[1..], [1, 1]);
+
+        // Exact ties of the joint log likelihood go to the heavier cluster
+        let tied = DMatrix::from_column_slice(2, 3, &[0.0, 0.0, -1.0, -2.0, -2.0, -1.0]);
+        for weights in [[0.4, 0.6], [0.6, 0.4]] {
+            constraints.apply(&tied, &weights, &mut labels, None, true, &mut rng).unwrap();
+            let heavier = if weights[1] > weights[0] { 1 } else { 0 };
+            assert_eq!(labels[1..], [heavier, heavier]);
+        }
+    }
+}
@@ -1,8 +1,10 @@
 pub mod clusters;
+pub mod constraints;
 pub mod options;
 pub mod thin;
 
 
 pub use clusters::*;
+pub use constraints::*;
 pub use options::*;
 pub use thin::*;
\ No newline at end of file
@@ -1,6 +1,17 @@
+use std::time::Duration;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+use crate::error::Error;
+use crate::params::constraints::PairwiseConstraints;
+use crate::state::validate_weights;
 use crate::stats::{NormalConjugatePrior, PriorHyperParams};
 
 /// Outlier removal options
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(
+    serialize = "P::HyperParams: Serialize",
+    deserialize = "P::HyperParams: Deserialize<'de>",
+)))]
 #[derive(Debug, Clone, PartialEq)]
 pub struct OutlierRemoval<P: NormalConjugatePrior> {
     /// Weight of the outlier prior
@@ -10,6 +21,11 @@ pub struct OutlierRemoval<P: NormalConjugatePrior> {
 }
 
 /// Options for the DPMMSC model
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(
+    serialize = "P::HyperParams: Serialize",
+    deserialize = "P::HyperParams: Deserialize<'de>",
+)))]
 #[derive(Debug, Clone, PartialEq)]
 pub struct ModelOptions<P: NormalConjugatePrior> {
     /// Prior for the complete data distribution
@@ -22,23 +38,335 @@ pub struct ModelOptions<P: NormalConjugatePrior> {
     pub burnout_period: usize,
     /// Outlier removal options
     pub outlier: Option<OutlierRemoval<P>>,
-    /// Whether to use hard assignment during expectation phase
+    /// Whether to use hard assignment during expectation phase. When set, every sweep assigns each point to
+    /// its most likely cluster instead of sampling its label, not only the final
+    /// [`argmax_sample_stop`](FitOptions::argmax_sample_stop) iterations.
     pub hard_assignment: bool,
+    /// Temperature dividing the log acceptance ratio of split/merge proposals.
+    /// Higher values accept more moves (more exploration), lower values are greedier.
+    pub split_merge_temperature: f64,
+    /// Dirichlet pseudo-counts added to the point counts of the (non-outlier) clusters by index when
+    /// sampling the mixture weights, softly favoring clusters with larger pseudo-counts.
+    /// Clusters without an entry get no pseudo-count.
+    pub weight_pseudo_counts: Option<Vec<f64>>,
+    /// Number of additional iterations a converged cluster keeps refining its auxiliary subclusters
+    /// before its split proposal is accepted or rejected. Helps splitting subtly multimodal clusters
+    /// whose subclusters need more time to separate.
+    pub split_warmup_iters: usize,
+    /// Minimum density a point must have under its most likely (non-outlier) cluster. Points below
+    /// it are assigned to the outlier cluster when predicting, regardless of the nearest cluster.
+    /// Only used when outlier removal is enabled.
+    pub outlier_min_density: Option<f64>,
+    /// Only propose merging clusters whose symmetric KL divergence (see
+    /// [`cluster_pairwise_divergence`](crate::state::cluster_pairwise_divergence)) is below this threshold.
+    /// Skips the acceptance ratio of clearly distinct pairs, which saves time when there are many clusters.
+    /// All pairs are proposed when not set.
+    pub merge_threshold: Option<f64>,
+    /// Clusters with fewer points are dissolved during the fit and their points moved to the nearest
+    /// (by mean) remaining cluster. The outlier cluster is never dissolved.
+    pub min_cluster_size: usize,
+    /// Schedule annealing the concentration parameter over the iterations where splits and merges are
+    /// proposed, see [`ModelOptions::effective_alpha`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub alpha_schedule: AlphaSchedule,
 }
 
 impl<P: NormalConjugatePrior> ModelOptions<P> {
     #[cfg(not(tarpaulin_include))]
     pub fn default(dim: usize) -> Self {
+        Self::builder(dim).options
+    }
+
+    /// Returns a builder starting from the [default](ModelOptions::default) options.
+    ///
+    /// # Example
+    /// ```
+    /// use mixturs::{ModelOptions, NIW};
+    ///
+    /// let options = ModelOptions::<NIW>::builder(2)
+    ///     .alpha(5.0)
+    ///     .without_outliers()
+    ///     .hard_assignment(true)
+    ///     .build()
+    ///     .unwrap();
+    /// assert!(options.outlier.is_none());
+    /// ```
+    pub fn builder(dim: usize) -> ModelOptionsBuilder<P> {
+        ModelOptionsBuilder::new(dim)
+    }
+
+    /// Index of the outlier cluster, `None` if outlier removal is disabled. The outlier cluster always
+    /// precedes the regular clusters.
+    pub fn outlier_index(&self) -> Option<usize> {
+        self.outlier.as_ref().map(|_| 0)
+    }
+
+    /// Index of the first regular (non-outlier) cluster, i.e. the number of clusters preceding them.
+    pub fn cluster_offset(&self) -> usize {
+        self.outlier_index().map_or(0, |k| k + 1)
+    }
+
+    /// Concentration parameter used at iteration `iter` of a fit according to the [`AlphaSchedule`]. The
+    /// schedule spans the first `n_scheduled` iterations, afterwards its final value is kept: `end` for a
+    /// linear schedule and `alpha` otherwise.
+    ///
+    /// # Example
+    /// ```
+    /// use mixturs::{ModelOptions, NIW};
+    /// use mixturs::params::AlphaSchedule;
+    ///
+    /// let options = ModelOptions::<NIW>::builder(2)
+    ///     .alpha(1.0)
+    ///     .alpha_schedule(AlphaSchedule::Linear { start: 11.0, end: 2.0 })
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(options.effective_alpha(0, 10), 11.0);
+    /// assert_eq!(options.effective_alpha(5, 10), 6.5);
+    /// assert_eq!(options.effective_alpha(10, 10), 2.0);
+    /// assert_eq!(options.effective_alpha(20, 10), 2.0);
+    /// ```
+    pub fn effective_alpha(&self, iter: usize, n_scheduled: usize) -> f64 {
+        match self.alpha_schedule {
+            AlphaSchedule::Constant => self.alpha,
+            AlphaSchedule::Linear { end, .. } if iter >= n_scheduled => end,
+            AlphaSchedule::Linear { start, end } => start + (end - start) * iter as f64 / n_scheduled as f64,
+            _ if iter >= n_scheduled => self.alpha,
+            AlphaSchedule::Exponential { start, rate } => self.alpha + (start - self.alpha) * (-rate * iter as f64).exp(),
+        }
+    }
+
+    /// Checks whether the options describe a proper model. Done by [`ModelOptionsBuilder::build`] and
+    /// before every fit, so options modified in place are checked as well.
+    ///
+    /// # Errors
+    /// * [`Error::InvalidData`] if the dimension is zero, `alpha`, the alpha schedule or the split/merge
+    /// temperature is not positive and finite, the outlier weight is not in `[0, 1)` or the merge threshold is negative.
+    /// * [`Error::DimensionMismatch`] if a prior does not match the dimension.
+    /// * [`Error::SingularCovariance`] if a prior is improper.
+    /// * [`Error::DegenerateWeights`] if a weight pseudo-count is negative or non-finite.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.dim == 0 {
+            return Err(Error::InvalidData("dimension must be positive".to_string()));
+        }
+        if !(self.alpha.is_finite() && self.alpha > 0.0) {
+            return Err(Error::InvalidData(format!("alpha {} must be positive and finite", self.alpha)));
+        }
+        let schedule_valid = match self.alpha_schedule {
+            AlphaSchedule::Constant => true,
+            AlphaSchedule::Linear { start, end } => start.is_finite() && start > 0.0 && end.is_finite() && end > 0.0,
+            AlphaSchedule::Exponential { start, rate } => start.is_finite() && start > 0.0 && rate.is_finite() && rate >= 0.0,
+        };
+        if !schedule_valid {
+            return Err(Error::InvalidData(format!("alpha schedule {:?} must be positive and finite", self.alpha_schedule)));
+        }
+        if !(self.split_merge_temperature.is_finite() && self.split_merge_temperature > 0.0) {
+            return Err(Error::InvalidData(format!(
+                "split/merge temperature {} must be positive and finite", self.split_merge_temperature
+            )));
+        }
+        if self.data_dist.dim() != self.dim {
+            return Err(Error::DimensionMismatch { expected: self.dim, got: self.data_dist.dim() });
+        }
+        if !self.data_dist.is_proper() {
+            return Err(Error::SingularCovariance);
+        }
+        if let Some(outlier) = &self.outlier {
+            if !(0.0..1.0).contains(&outlier.weight) {
+                return Err(Error::InvalidData(format!("outlier weight {} is not in [0, 1)", outlier.weight)));
+            }
+            if outlier.dist.dim() != self.dim {
+                return Err(Error::DimensionMismatch { expected: self.dim, got: outlier.dist.dim() });
+            }
+            if !outlier.dist.is_proper() {
+                return Err(Error::SingularCovariance);
+            }
+        }
+        if let Some(threshold) = self.merge_threshold {
+            if threshold.is_nan() || threshold < 0.0 {
+                return Err(Error::InvalidData(format!("merge threshold {} must be non-negative", threshold)));
+            }
+        }
+        if let Some(pseudo_counts) = &self.weight_pseudo_counts {
+            if pseudo_counts.iter().any(|c| !(c.is_finite() && *c >= 0.0)) {
+                return Err(Error::DegenerateWeights);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Builder for [`ModelOptions`] validating the options on [`build`](ModelOptionsBuilder::build).
+#[derive(Debug, Clone)]
+pub struct ModelOptionsBuilder<P: NormalConjugatePrior> {
+    options: ModelOptions<P>,
+}
+
+impl<P: NormalConjugatePrior> ModelOptionsBuilder<P> {
+    pub fn new(dim: usize) -> Self {
         Self {
-            data_dist: P::HyperParams::default(dim),
-            alpha: 10.0,
-            dim,
-            burnout_period: 20,
-            outlier: Some(OutlierRemoval {
-                weight: 0.05,
-                dist: P::HyperParams::default(dim),
-            }),
-            hard_assignment: false,
+            options: ModelOptions {
+                data_dist: P::HyperParams::default(dim),
+                alpha: 10.0,
+                dim,
+                burnout_period: 20,
+                outlier: Some(OutlierRemoval {
+                    weight: 0.05,
+                    dist: P::HyperParams::default(dim),
+                }),
+                hard_assignment: false,
+                split_merge_temperature: 1.0,
+                weight_pseudo_counts: None,
+                split_warmup_iters: 0,
+                outlier_min_density: None,
+                merge_threshold: None,
+                min_cluster_size: 1,
+                alpha_schedule: AlphaSchedule::Constant,
+            }
+        }
+    }
+
+    pub fn data_dist(mut self, data_dist: P::HyperParams) -> Self {
+        self.options.data_dist = data_dist;
+        self
+    }
+
+    pub fn alpha(mut self, alpha: f64) -> Self {
+        self.options.alpha = alpha;
+        self
+    }
+
+    pub fn burnout_period(mut self, burnout_period: usize) -> Self {
+        self.options.burnout_period = burnout_period;
+        self
+    }
+
+    /// Enables outlier removal with an outlier cluster of the given weight and prior.
+    pub fn outlier(mut self, weight: f64, dist: P::HyperParams) -> Self {
+        self.options.outlier = Some(OutlierRemoval { weight, dist });
+        self
+    }
+
+    pub fn without_outliers(mut self) -> Self {
+        self.options.outlier = None;
+        self
+    }
+
+    pub fn hard_assignment(mut self, hard_assignment: bool) -> Self {
+        self.options.hard_assignment = hard_assignment;
+        self
+    }
+
+    pub fn split_merge_temperature(mut self, temperature: f64) -> Self {
+        self.options.split_merge_temperature = temperature;
+        self
+    }
+
+    pub fn weight_pseudo_counts(mut self, pseudo_counts: Vec<f64>) -> Self {
+        self.options.weight_pseudo_counts = Some(pseudo_counts);
+        self
+    }
+
+    pub fn split_warmup_iters(mut self, iters: usize) -> Self {
+        self.options.split_warmup_iters = iters;
+        self
+    }
+
+    pub fn outlier_min_density(mut self, min_density: f64) -> Self {
+        self.options.outlier_min_density = Some(min_density);
+        self
+    }
+
+    pub fn merge_threshold(mut self, threshold: f64) -> Self {
+        self.options.merge_threshold = Some(threshold);
+        self
+    }
+
+    pub fn min_cluster_size(mut self, min_cluster_size: usize) -> Self {
+        self.options.min_cluster_size = min_cluster_size;
+        self
+    }
+
+    pub fn alpha_schedule(mut self, schedule: AlphaSchedule) -> Self {
+        self.options.alpha_schedule = schedule;
+        self
+    }
+
+    /// Validates and returns the options, see [`ModelOptions::validate`].
+    pub fn build(self) -> Result<ModelOptions<P>, Error> {
+        self.options.validate()?;
+        Ok(self.options)
+    }
+}
+
+/// Schedule of the Dirichlet process concentration parameter over the iterations of a fit. Starting with a
+/// high concentration encourages splitting (exploration) early on, before settling on [`ModelOptions::alpha`].
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlphaSchedule {
+    /// Always use [`ModelOptions::alpha`].
+    Constant,
+    /// Linearly interpolate from `start` to `end` over the scheduled iterations. `end` is the final value and
+    /// is kept after the schedule instead of [`ModelOptions::alpha`].
+    Linear { start: f64, end: f64 },
+    /// Decay exponentially from `start` towards [`ModelOptions::alpha`]: `alpha + (start - alpha) * exp(-rate * t)`.
+    Exponential { start: f64, rate: f64 },
+}
+
+impl Default for AlphaSchedule {
+    fn default() -> Self {
+        AlphaSchedule::Constant
+    }
+}
+
+/// Strategy initializing the auxiliary labels of a (new or reset) cluster, i.e. its split proposal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuxInit {
+    /// Each point picks an auxiliary cluster uniformly at random.
+    Uniform,
+    /// Points are split by a random hyperplane: they are projected onto a random gaussian direction and
+    /// thresholded at the median, giving a random but balanced split.
+    RandomHyperplane,
+}
+
+impl Default for AuxInit {
+    fn default() -> Self {
+        AuxInit::Uniform
+    }
+}
+
+/// Strategy initializing the primary labels of the points at the start of a fit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitStrategy {
+    /// Each point picks one of the initial clusters uniformly at random.
+    Random,
+    /// The initial clusters are seeded with [k-means++](crate::utils::kmeans_plusplus) and each point is
+    /// assigned to its nearest seed. `n` candidate seeds are sampled per cluster, keeping the best one.
+    KMeansPlusPlus { n: usize },
+}
+
+impl Default for InitStrategy {
+    fn default() -> Self {
+        InitStrategy::Random
+    }
+}
+
+/// Conditions stopping a fit before [`FitOptions::iters`] iterations have run. They are evaluated after each
+/// iteration and the fit stops as soon as any of the set conditions is met. Stopping early skips the
+/// remaining argmax (cooldown) iterations, unlike a log-likelihood plateau (see [`FitOptions::patience`]).
+#[derive(Debug, Clone, PartialEq)]
+pub struct StopCriteria {
+    /// Maximum wall-clock time spent fitting.
+    pub time_budget: Option<Duration>,
+    /// Stop once the fraction of points changing their primary label in an iteration drops below this.
+    pub label_change_tol: Option<f64>,
+}
+
+impl Default for StopCriteria {
+    fn default() -> Self {
+        Self {
+            time_budget: None,
+            label_change_tol: None,
         }
     }
 }
@@ -61,21 +389,424 @@ pub struct FitOptions {
     /// Number of iterations before max iteration to stop split/merge proposals
     pub iter_split_stop: usize,
     /// Number of workers (threads) for parallelization (-1 = number of CPUs)
+    ///
+    /// The data is split into one shard per worker, each sampling from its own random stream derived from
+    /// `seed`. A fit is therefore reproducible for a given seed and number of workers, regardless of the size
+    /// of the rayon thread pool (e.g. `RAYON_NUM_THREADS`).
     pub workers: i32,
+    /// Whether to record a snapshot of the primary labels after each (thinned) iteration.
+    /// The trace takes `n_samples * n_points * size_of::<usize>()` bytes, so keep it off for large datasets.
+    pub record_label_trace: bool,
+    /// Number of iterations to skip before recording the label trace
+    pub trace_burn_in: usize,
+    /// Record the label trace every `trace_thinning` iterations
+    pub trace_thinning: usize,
+    /// Whether to record the total log-likelihood of the model after each iteration.
+    /// Requires an extra pass over the data per iteration.
+    pub record_ll_trace: bool,
+    /// Whether to return the responsibilities of the clusters for each point in the fit result (see
+    /// [`crate::FitResult::responsibilities`]). Takes `n_points * n_clusters * size_of::<f64>()` bytes.
+    pub return_responsibilities: bool,
+    /// Whether to record every split/merge proposal with its log Hastings ratio (see
+    /// [`crate::FitResult::split_merge_events`]). Useful to diagnose clusters that do not split or merge.
+    pub record_split_merge_events: bool,
+    /// Whether the model keeps the final local state (the training data with its labels) after fitting, to be
+    /// returned by [`crate::Model::into_parts`]. Holds on to the full training data until the model is dropped.
+    pub keep_local: bool,
+    /// Must-link/cannot-link constraints between points respected during label sampling.
+    /// Constraints span the whole dataset, so they require a single worker (see [`FitOptions::workers`]).
+    pub constraints: Option<PairwiseConstraints>,
+    /// Optional mask of points (n_samples) whose initial label is kept fixed, see [`crate::state::LocalState::frozen`].
+    /// Only useful with labels that are meaningful from the start, e.g. when reusing a previous fit.
+    pub frozen: Option<Vec<bool>>,
+    /// Optional multiplicity of each point (n_samples), see [`crate::state::LocalState::weights`].
+    /// Lets aggregated rows stand for many identical observations without repeating them.
+    pub weights: Option<Vec<f64>>,
+    /// Whether argmax label sampling breaks exact ties randomly instead of picking the cluster with the largest
+    /// weight (then the lowest cluster index).
+    /// Avoids biasing perfectly symmetric data towards the first cluster.
+    pub random_tie_break: bool,
+    /// Estimate the sufficient statistics of clusters with more points than this from a random
+    /// subsample of this size (scaled up), trading a little accuracy for speed on huge clusters.
+    pub max_stats_points: Option<usize>,
+    /// Collect the sufficient statistics of each cluster in parallel chunks of this many points.
+    /// Useful when a single huge cluster dominates the data.
+    pub par_stats_chunk: Option<usize>,
+    /// Compute the label log-likelihoods in parallel chunks of this many points.
+    /// Speeds up label sampling on large datasets fit with a single worker, with identical results.
+    pub par_ll_chunk: Option<usize>,
+    /// Collect the sufficient statistics of the clusters in parallel, one task per cluster.
+    /// Speeds up fits with many clusters and a single worker, with identical results.
+    pub par_cluster_stats: bool,
+    /// Fraction of the points (chosen using `seed`) held out from fitting to report the held-out
+    /// predictive log-likelihood in the fit result.
+    pub validation_fraction: Option<f64>,
+    /// Whether exact-duplicate points are collapsed into a single point weighted by their multiplicity (see
+    /// [`FitOptions::weights`]) for the fit, and their labels expanded back afterwards. Speeds up fits on heavily
+    /// duplicated (e.g. one-hot or quantized) data. Cannot be combined with constraints or frozen points.
+    pub dedup_init: bool,
+    /// How the auxiliary labels (split proposals) of new and reset clusters are initialized.
+    pub aux_init: AuxInit,
+    /// How the primary labels are initialized. Ignored when reusing a previous fit.
+    pub init_strategy: InitStrategy,
+    /// Conditions stopping the fit early.
+    pub stop_criteria: StopCriteria,
+    /// Minimum improvement of the model log-likelihood (see [`crate::FitResult::log_likelihood_trace`]) for an
+    /// iteration to count as progress.
+    pub tol: f64,
+    /// Number of consecutive iterations without progress after which the final `argmax_sample_stop` hard
+    /// assignment iterations are run and the fit stops. `usize::MAX` always runs all `iters` iterations.
+    pub patience: usize,
 }
 
 impl Default for FitOptions {
     #[cfg(not(tarpaulin_include))]
     fn default() -> Self {
+        FitOptionsBuilder::new().options
+    }
+}
+
+impl FitOptions {
+    /// Returns a builder starting from the [default](FitOptions::default) options.
+    ///
+    /// # Example
+    /// ```
+    /// use mixturs::FitOptions;
+    ///
+    /// let options = FitOptions::builder()
+    ///     .init_clusters(4)
+    ///     .max_clusters(10)
+    ///     .iters(50)
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(options.init_clusters, 4);
+    /// ```
+    pub fn builder() -> FitOptionsBuilder {
+        FitOptionsBuilder::new()
+    }
+}
+
+/// Builder for [`FitOptions`] validating the options on [`build`](FitOptionsBuilder::build).
+#[derive(Debug, Clone)]
+pub struct FitOptionsBuilder {
+    options: FitOptions,
+}
+
+impl FitOptionsBuilder {
+    pub fn new() -> Self {
         Self {
-            seed: 42,
-            reuse: false,
-            init_clusters: 1,
-            max_clusters: usize::MAX,
-            iters: 100,
-            argmax_sample_stop: 5,
-            iter_split_stop: 5,
-            workers: 1,
+            options: FitOptions {
+                seed: 42,
+                reuse: false,
+                init_clusters: 1,
+                max_clusters: usize::MAX,
+                iters: 100,
+                argmax_sample_stop: 5,
+                iter_split_stop: 5,
+                workers: 1,
+                record_label_trace: false,
+                trace_burn_in: 0,
+                trace_thinning: 1,
+                record_ll_trace: false,
+                return_responsibilities: false,
+                record_split_merge_events: false,
+                keep_local: false,
+                constraints: None,
+                frozen: None,
+                weights: None,
+                random_tie_break: false,
+                max_stats_points: None,
+                par_stats_chunk: None,
+                par_ll_chunk: None,
+                par_cluster_stats: false,
+                validation_fraction: None,
+                dedup_init: false,
+                aux_init: AuxInit::Uniform,
+                init_strategy: InitStrategy::Random,
+                stop_criteria: StopCriteria::default(),
+                tol: 0.0,
+                patience: usize::MAX,
+            }
+        }
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.options.seed = seed;
+        self
+    }
+
+    pub fn reuse(mut self, reuse: bool) -> Self {
+        self.options.reuse = reuse;
+        self
+    }
+
+    pub fn init_clusters(mut self, init_clusters: usize) -> Self {
+        self.options.init_clusters = init_clusters;
+        self
+    }
+
+    pub fn max_clusters(mut self, max_clusters: usize) -> Self {
+        self.options.max_clusters = max_clusters;
+        self
+    }
+
+    pub fn iters(mut self, iters: usize) -> Self {
+        self.options.iters = iters;
+        self
+    }
+
+    pub fn argmax_sample_stop(mut self, iters: usize) -> Self {
+        self.options.argmax_sample_stop = iters;
+        self
+    }
+
+    pub fn iter_split_stop(mut self, iters: usize) -> Self {
+        self.options.iter_split_stop = iters;
+        self
+    }
+
+    pub fn workers(mut self, workers: i32) -> Self {
+        self.options.workers = workers;
+        self
+    }
+
+    /// Records the label trace after `burn_in` iterations, every `thinning` iterations.
+    pub fn label_trace(mut self, burn_in: usize, thinning: usize) -> Self {
+        self.options.record_label_trace = true;
+        self.options.trace_burn_in = burn_in;
+        self.options.trace_thinning = thinning;
+        self
+    }
+
+    pub fn record_ll_trace(mut self, record: bool) -> Self {
+        self.options.record_ll_trace = record;
+        self
+    }
+
+    pub fn return_responsibilities(mut self, return_responsibilities: bool) -> Self {
+        self.options.return_responsibilities = return_responsibilities;
+        self
+    }
+
+    pub fn record_split_merge_events(mut self, record: bool) -> Self {
+        self.options.record_split_merge_events = record;
+        self
+    }
+
+    pub fn keep_local(mut self, keep_local: bool) -> Self {
+        self.options.keep_local = keep_local;
+        self
+    }
+
+    pub fn constraints(mut self, constraints: PairwiseConstraints) -> Self {
+        self.options.constraints = Some(constraints);
+        self
+    }
+
+    pub fn frozen(mut self, frozen: Vec<bool>) -> Self {
+        self.options.frozen = Some(frozen);
+        self
+    }
+
+    pub fn weights(mut self, weights: Vec<f64>) -> Self {
+        self.options.weights = Some(weights);
+        self
+    }
+
+    pub fn random_tie_break(mut self, random_tie_break: bool) -> Self {
+        self.options.random_tie_break = random_tie_break;
+        self
+    }
+
+    pub fn validation_fraction(mut self, fraction: f64) -> Self {
+        self.options.validation_fraction = Some(fraction);
+        self
+    }
+
+    pub fn dedup_init(mut self, dedup_init: bool) -> Self {
+        self.options.dedup_init = dedup_init;
+        self
+    }
+
+    pub fn aux_init(mut self, aux_init: AuxInit) -> Self {
+        self.options.aux_init = aux_init;
+        self
+    }
+
+    pub fn init_strategy(mut self, init_strategy: InitStrategy) -> Self {
+        self.options.init_strategy = init_strategy;
+        self
+    }
+
+    pub fn stop_criteria(mut self, stop_criteria: StopCriteria) -> Self {
+        self.options.stop_criteria = stop_criteria;
+        self
+    }
+
+    /// Stops early after `patience` iterations improving the log-likelihood by less than `tol`,
+    /// see [`FitOptions::patience`].
+    pub fn early_stopping(mut self, tol: f64, patience: usize) -> Self {
+        self.options.tol = tol;
+        self.options.patience = patience;
+        self
+    }
+
+    /// Validates and returns the options.
+    ///
+    /// # Errors
+    /// [`Error::InvalidData`] if `init_clusters` is zero or exceeds `max_clusters`, `argmax_sample_stop` exceeds
+    /// `iters`, the trace thinning is zero, the validation fraction is not in `[0, 1)` or the log-likelihood plateau tol is negative.
+    pub fn build(self) -> Result<FitOptions, Error> {
+        let options = self.options;
+        if options.init_clusters == 0 {
+            return Err(Error::InvalidData("at least one initial cluster is required".to_string()));
+        }
+        if options.init_clusters > options.max_clusters {
+            return Err(Error::InvalidData(format!(
+                "init_clusters {} exceeds max_clusters {}", options.init_clusters, options.max_clusters
+            )));
         }
+        if options.argmax_sample_stop > options.iters {
+            return Err(Error::InvalidData(format!(
+                "argmax_sample_stop {} exceeds iters {}", options.argmax_sample_stop, options.iters
+            )));
+        }
+        if options.trace_thinning == 0 {
+            return Err(Error::InvalidData("trace thinning must be positive".to_string()));
+        }
+        if let Some(fraction) = options.validation_fraction {
+            if !(0.0..1.0).contains(&fraction) {
+                return Err(Error::InvalidData(format!("validation fraction {} is not in [0, 1)", fraction)));
+            }
+        }
+        if let Some(weights) = &options.weights {
+            validate_weights(Some(weights), weights.len())?;
+        }
+        if options.tol.is_nan() || options.tol < 0.0 {
+            return Err(Error::InvalidData(format!("log-likelihood plateau tol {} must be non-negative", options.tol)));
+        }
+
+        Ok(options)
+    }
+}
+
+impl Default for FitOptionsBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DMatrix;
+    use crate::error::Error;
+    use crate::params::{AlphaSchedule, FitOptions, ModelOptions};
+    use crate::stats::{NIW, NIWParams, PriorHyperParams};
+
+    #[test]
+    fn test_model_options_builder() {
+        let options = ModelOptions::<NIW>::builder(3)
+            .alpha(2.0)
+            .burnout_period(5)
+            .without_outliers()
+            .hard_assignment(true)
+            .build()
+            .unwrap();
+        assert_eq!(options.alpha, 2.0);
+        assert_eq!(options.burnout_period, 5);
+        assert!(options.outlier.is_none());
+        assert!(options.hard_assignment);
+
+        let (built, default) = (ModelOptions::<NIW>::builder(3).build().unwrap(), ModelOptions::<NIW>::default(3));
+        assert_eq!(built.data_dist, default.data_dist);
+        assert_eq!((built.alpha, built.burnout_period), (default.alpha, default.burnout_period));
+        assert_eq!(built.outlier.map(|o| o.weight), default.outlier.map(|o| o.weight));
+    }
+
+    #[test]
+    fn test_model_options_validation() {
+        let build = |builder: crate::params::ModelOptionsBuilder<NIW>| builder.build().unwrap_err();
+
+        assert!(matches!(build(ModelOptions::builder(0)), Error::InvalidData(_)));
+        assert!(matches!(build(ModelOptions::builder(2).alpha(0.0)), Error::InvalidData(_)));
+        assert!(matches!(build(ModelOptions::builder(2).alpha(f64::NAN)), Error::InvalidData(_)));
+        assert!(matches!(build(ModelOptions::builder(2).split_merge_temperature(-1.0)), Error::InvalidData(_)));
+        assert!(matches!(
+            build(ModelOptions::builder(2).outlier(1.0, NIWParams::default(2))),
+            Error::InvalidData(_)
+        ));
+        assert_eq!(
+            build(ModelOptions::builder(2).data_dist(NIWParams::default(3))),
+            Error::DimensionMismatch { expected: 2, got: 3 }
+        );
+        assert!(matches!(build(ModelOptions::builder(2).merge_threshold(-1.0)), Error::InvalidData(_)));
+        assert_eq!(build(ModelOptions::builder(2).weight_pseudo_counts(vec![1.0, -1.0])), Error::DegenerateWeights);
+
+        let mut improper = NIWParams::default(2);
+        improper.psi = DMatrix::zeros(2, 2);
+        assert_eq!(build(ModelOptions::builder(2).data_dist(improper)), Error::SingularCovariance);
+
+        // Options modified in place are checked by the same validator
+        let mut options = ModelOptions::<NIW>::default(2);
+        options.alpha = 0.0;
+        assert!(matches!(options.validate(), Err(Error::InvalidData(_))));
+    }
+
+    #[test]
+    fn test_fit_options_builder() {
+        let options = FitOptions::builder()
+            .seed(7)
+            .init_clusters(3)
+            .max_clusters(5)
+            .iters(20)
+            .early_stopping(1e-3, 4)
+            .build()
+            .unwrap();
+        assert_eq!(options.seed, 7);
+        assert_eq!((options.init_clusters, options.max_clusters, options.iters), (3, 5, 20));
+        assert_eq!((options.tol, options.patience), (1e-3, 4));
+
+        let default = FitOptions::default();
+        assert_eq!((default.tol, default.patience), (0.0, usize::MAX));
+    }
+
+    #[test]
+    fn test_fit_options_validation() {
+        let build = |builder: crate::params::FitOptionsBuilder| builder.build().unwrap_err();
+
+        assert!(matches!(build(FitOptions::builder().init_clusters(0)), Error::InvalidData(_)));
+        assert!(matches!(build(FitOptions::builder().init_clusters(4).max_clusters(3)), Error::InvalidData(_)));
+        assert!(matches!(build(FitOptions::builder().iters(3).argmax_sample_stop(5)), Error::InvalidData(_)));
+        assert!(matches!(build(FitOptions::builder().label_trace(0, 0)), Error::InvalidData(_)));
+        assert!(matches!(build(FitOptions::builder().validation_fraction(1.0)), Error::InvalidData(_)));
+        assert!(matches!(build(FitOptions::builder().early_stopping(-1.0, 3)), Error::InvalidData(_)));
+    }
+
+    #[test]
+    fn test_alpha_schedule() {
+        let fit_options = FitOptions::default();
+        let n = fit_options.iters - fit_options.iter_split_stop;
+        let options = |schedule| ModelOptions::<NIW>::builder(2).alpha(2.0).alpha_schedule(schedule).build().unwrap();
+
+        let constant = options(AlphaSchedule::Constant);
+        assert_eq!((constant.effective_alpha(0, n), constant.effective_alpha(n - 1, n)), (2.0, 2.0));
+
+        let linear = options(AlphaSchedule::Linear { start: 50.0, end: 10.0 });
+        statrs::assert_almost_eq!(linear.effective_alpha(0, n), 50.0, 1e-12);
+        statrs::assert_almost_eq!(linear.effective_alpha(n - 1, n), 50.0 - 40.0 * (n - 1) as f64 / n as f64, 1e-12);
+        // The ramp ends at `end` and keeps it, without jumping to `alpha`
+        assert_eq!(linear.effective_alpha(n, n), 10.0);
+        assert_eq!(linear.effective_alpha(n + 5, n), 10.0);
+
+        let exponential = options(AlphaSchedule::Exponential { start: 50.0, rate: 0.1 });
+        statrs::assert_almost_eq!(exponential.effective_alpha(0, n), 50.0, 1e-12);
+        statrs::assert_almost_eq!(
+            exponential.effective_alpha(n - 1, n), 2.0 + 48.0 * (-0.1 * (n - 1) as f64).exp(), 1e-12
+        );
+        assert_eq!(exponential.effective_alpha(n, n), 2.0);
+
+        let build = |schedule| ModelOptions::<NIW>::builder(2).alpha_schedule(schedule).build().unwrap_err();
+        assert!(matches!(build(AlphaSchedule::Linear { start: 0.0, end: 1.0 }), Error::InvalidData(_)));
+        assert!(matches!(build(AlphaSchedule::Exponential { start: 10.0, rate: -1.0 }), Error::InvalidData(_)));
     }
 }
@@ -1,9 +1,11 @@
 use itertools::repeat_n;
-use nalgebra::{DMatrix, RowDVector};
+use nalgebra::{DMatrix, DVector, RowDVector};
 use rand::Rng;
+use rayon::prelude::*;
 use statrs::distribution::MultivariateNormal;
+use crate::error::Error;
 use crate::stats::ContinuousBatchwise;
-use crate::utils::{col_normalize_log_weights, replacement_sampling_weighted};
+use crate::utils::{argmax, argmax_weighted_ties, col_normalize_log_weights, DataScalar, Label, sample_weighted};
 
 
 pub trait ThinParams: Clone + Send + Sync {
@@ -22,6 +24,16 @@ pub trait ThinParams: Clone + Send + Sync {
     /// Weights of the auxiliary clusters given the primary cluster.
     fn cluster_aux_weights(&self, cluster_id: usize) -> &[f64; 2];
 
+    /// Log density of the data points (columns) under the primary cluster.
+    fn cluster_ln_pdf(&self, cluster_id: usize, data: DMatrix<f64>) -> DVector<f64> {
+        self.cluster_dist(cluster_id).batchwise_ln_pdf(data)
+    }
+
+    /// Log density of the data points (columns) under the auxiliary cluster given the primary cluster.
+    fn cluster_aux_ln_pdf(&self, cluster_id: usize, aux_id: usize, data: DMatrix<f64>) -> DVector<f64> {
+        self.cluster_aux_dist(cluster_id, aux_id).batchwise_ln_pdf(data)
+    }
+
     /// Number of parameters in the model.
     fn n_params(&self) -> usize {
         let dim = self.cluster_dist(0).mu().len();
@@ -74,6 +86,10 @@ impl<'a, D: ThinParams> MixtureParams for SuperMixtureParams<'a, D> {
     fn weights(&self) -> &[f64] {
         self.0.cluster_weights()
     }
+
+    fn ln_pdf(&self, cluster_id: usize, data: DMatrix<f64>) -> DVector<f64> {
+        self.0.cluster_ln_pdf(cluster_id, data)
+    }
 }
 
 /// Selects auxiliary cluster params from thin params for a given super cluster
@@ -91,6 +107,10 @@ impl<'a, D: ThinParams> MixtureParams for AuxMixtureParams<'a, D> {
     fn weights(&self) -> &[f64] {
         self.0.cluster_aux_weights(self.1)
     }
+
+    fn ln_pdf(&self, cluster_id: usize, data: DMatrix<f64>) -> DVector<f64> {
+        self.0.cluster_aux_ln_pdf(self.1, cluster_id, data)
+    }
 }
 
 pub trait MixtureParams {
@@ -103,16 +123,17 @@ pub trait MixtureParams {
     /// Weights of the primary clusters.
     fn weights(&self) -> &[f64];
 
+    /// Log density of the data points (columns) under the cluster.
+    fn ln_pdf(&self, cluster_id: usize, data: DMatrix<f64>) -> DVector<f64> {
+        self.dist(cluster_id).batchwise_ln_pdf(data)
+    }
+
     /// Log-likelihood of the data points (columns) given the model.
     fn log_likelihood(&self, data: DMatrix<f64>) -> DMatrix<f64> {
         let mut ll = DMatrix::zeros(self.n_clusters(), data.ncols());
         // Add cluster log probabilities
         for (cluster_id, data) in repeat_n(data, self.n_clusters()).enumerate() {
-            ll.row_mut(cluster_id).copy_from_slice(
-                self.dist(cluster_id)
-                    .batchwise_ln_pdf(data)
-                    .as_slice()
-            );
+            ll.row_mut(cluster_id).copy_from_slice(self.ln_pdf(cluster_id, data).as_slice());
         }
 
         // Add mixture weights
@@ -125,12 +146,63 @@ pub trait MixtureParams {
         ll
     }
 
+    /// Computes the same log-likelihood as [`MixtureParams::log_likelihood`] in parallel, splitting the data
+    /// points into chunks of `chunk_size` columns that are each filled on their own thread.
+    /// Every entry is computed independently, so the result is identical to the sequential version
+    /// regardless of the number of threads.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The data points (columns), converted to `f64` one chunk at a time.
+    /// * `chunk_size`: Number of points per chunk.
+    fn par_log_likelihood<T: DataScalar>(&self, data: &DMatrix<T>, chunk_size: usize) -> DMatrix<f64>
+        where Self: Sync
+    {
+        let chunk_size = chunk_size.max(1);
+        let chunks: Vec<_> = (0..data.ncols()).step_by(chunk_size).collect::<Vec<_>>()
+            .into_par_iter()
+            .map(|start| {
+                let end = (start + chunk_size).min(data.ncols());
+                (start, self.log_likelihood(T::columns_f64(data, start..end).into_owned()))
+            })
+            .collect();
+
+        let mut ll = DMatrix::zeros(self.n_clusters(), data.ncols());
+        for (start, chunk) in chunks {
+            ll.columns_mut(start, chunk.ncols()).copy_from(&chunk);
+        }
+        ll
+    }
+
+    /// Recomputes the row of a single cluster in a log-likelihood matrix computed by
+    /// [`MixtureParams::log_likelihood`], e.g. after only that cluster's distribution was resampled.
+    /// The rows of the other clusters are left untouched, so they must still be valid (including
+    /// their weights).
+    ///
+    /// # Arguments
+    ///
+    /// * `ll`: The log-likelihood matrix to update. (n_clusters, n_points)
+    /// * `data`: The data points (columns) the matrix was computed for.
+    /// * `cluster_id`: The cluster whose row is recomputed.
+    fn update_log_likelihood(&self, ll: &mut DMatrix<f64>, data: &DMatrix<f64>, cluster_id: usize) {
+        let ln_weight = self.weights()[cluster_id].ln();
+        let cluster_ll = self.ln_pdf(cluster_id, data.clone_owned());
+        for (x, l) in ll.row_mut(cluster_id).iter_mut().zip(cluster_ll.iter()) {
+            *x = l + ln_weight;
+        }
+    }
+
     /// Predict the cluster labels for the data points (columns).
+    /// Also returns the responsibilities of the clusters for each point, normalized to sum to one. (n_clusters, n_points)
     fn predict(&self, data: DMatrix<f64>) -> (DMatrix<f64>, RowDVector<usize>) {
         let mut labels = RowDVector::zeros(data.ncols());
         let log_likelihood = self.log_likelihood(data);
         hard_assignment(&log_likelihood, labels.as_mut_slice());
-        let probs = col_normalize_log_weights(log_likelihood);
+        let mut probs = col_normalize_log_weights(log_likelihood);
+        for mut col in probs.column_iter_mut() {
+            let sum = col.sum();
+            col /= sum;
+        }
 
         (probs, labels)
     }
@@ -138,6 +210,8 @@ pub trait MixtureParams {
 
 
 /// Assigns each column of `log_likelihood` to the cluster with the highest log probability.
+/// Ties (including columns that are entirely `-inf`) go to the lowest cluster index and NaN entries are
+/// ignored, see [`argmax`].
 ///
 /// # Arguments
 ///
@@ -151,16 +225,92 @@ pub trait MixtureParams {
 /// use nalgebra::{DMatrix, RowDVector};
 ///
 /// let log_likelihood = DMatrix::from_row_slice(2, 3, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
-/// let mut labels = RowDVector::zeros(3);
+/// let mut labels = RowDVector::<usize>::zeros(3);
 /// hard_assignment(&log_likelihood, labels.as_mut_slice());
 /// assert_eq!(labels, RowDVector::from_row_slice(&[1, 1, 1]));
 /// ```
-pub fn hard_assignment(
+pub fn hard_assignment<L: Label>(
     log_likelihood: &DMatrix<f64>,
-    labels: &mut [usize],
+    labels: &mut [L],
 ) {
     for (i, row) in log_likelihood.column_iter().enumerate() {
-        labels[i] = row.argmax().0;
+        labels[i] = L::from_usize(argmax(row.iter().cloned()).unwrap_or(0));
+    }
+}
+
+
+/// Assigns each column of `log_likelihood` to the cluster with the highest log probability. Exact ties
+/// (including columns that are entirely `-inf`) go to the tied cluster with the largest weight and then to the
+/// lowest cluster index, so the assignment does not depend on the order of the clusters unless their weights
+/// are equal as well. NaN entries are ignored.
+///
+/// # Arguments
+///
+/// * `log_likelihood`: A matrix of log probabilities of shape (n_clusters, n_samples)
+/// * `weights`: The weight of each cluster (n_clusters)
+/// * `labels`: A mutable vector of length `n_samples` the cluster assignments will be written to.
+///
+/// # Examples
+///
+/// ```
+/// use mixturs::params::thin::hard_assignment_weighted_ties;
+/// use nalgebra::{DMatrix, RowDVector};
+///
+/// let log_likelihood = DMatrix::from_row_slice(3, 2, &[1.0, 0.0, 3.0, 0.0, 3.0, 2.0]);
+/// let mut labels = RowDVector::<usize>::zeros(2);
+/// hard_assignment_weighted_ties(&log_likelihood, &[0.2, 0.3, 0.5], labels.as_mut_slice());
+/// assert_eq!(labels, RowDVector::from_row_slice(&[2, 2]));
+/// ```
+pub fn hard_assignment_weighted_ties<L: Label>(
+    log_likelihood: &DMatrix<f64>,
+    weights: &[f64],
+    labels: &mut [L],
+) {
+    for (i, col) in log_likelihood.column_iter().enumerate() {
+        labels[i] = L::from_usize(argmax_weighted_ties(col.iter().cloned(), weights).unwrap_or(0));
+    }
+}
+
+
+/// Assigns each column of `log_likelihood` to the cluster with the highest log probability, breaking
+/// exact ties by a uniform random choice among the tied clusters instead of the lowest index.
+///
+/// # Arguments
+///
+/// * `log_likelihood`: A matrix of log probabilities of shape (n_clusters, n_samples)
+/// * `labels`: A mutable vector of length `n_samples` the cluster assignments will be written to.
+/// * `rng`: A random number generator.
+///
+/// # Examples
+///
+/// ```
+/// use mixturs::params::thin::hard_assignment_random_ties;
+/// use nalgebra::{DMatrix, RowDVector};
+/// use rand::prelude::StdRng;
+/// use rand::SeedableRng;
+///
+/// let mut rng = StdRng::seed_from_u64(42);
+/// let log_likelihood = DMatrix::from_row_slice(3, 2, &[1.0, 0.0, 3.0, 0.0, 3.0, 2.0]);
+/// let mut labels = RowDVector::<usize>::zeros(2);
+/// hard_assignment_random_ties(&log_likelihood, labels.as_mut_slice(), &mut rng);
+/// assert!(labels[0] == 1 || labels[0] == 2);
+/// assert_eq!(labels[1], 2);
+/// ```
+pub fn hard_assignment_random_ties<L: Label>(
+    log_likelihood: &DMatrix<f64>,
+    labels: &mut [L],
+    rng: &mut impl Rng,
+) {
+    for (i, col) in log_likelihood.column_iter().enumerate() {
+        let max = col.max();
+        let n_ties = col.iter().filter(|&&x| x == max).count();
+        let pick = if n_ties > 1 { rng.gen_range(0..n_ties) } else { 0 };
+
+        labels[i] = L::from_usize(col.iter()
+            .enumerate()
+            .filter(|(_, &x)| x == max)
+            .nth(pick)
+            .map_or(0, |(k, _)| k));
     }
 }
 
@@ -174,6 +324,11 @@ pub fn hard_assignment(
 /// * `labels`: A mutable vector of length `n_samples` the cluster assignments will be written to.
 /// * `rng`: A random number generator.
 ///
+/// # Errors
+///
+/// Returns an [`Error`] naming the first point (column) whose log probabilities contain a NaN
+/// or positive infinity, or are all negative infinity (zero weights).
+///
 /// # Examples
 ///
 /// ```
@@ -181,17 +336,133 @@ pub fn hard_assignment(
 /// use nalgebra::{DMatrix, RowDVector};
 ///
 /// let log_likelihood = DMatrix::from_row_slice(2, 3, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
-/// let mut labels = RowDVector::zeros(3);
+/// let mut labels = RowDVector::<usize>::zeros(3);
 /// let mut rng = rand::thread_rng();
-/// soft_assignment(log_likelihood, labels.as_mut_slice(), &mut rng);
+/// soft_assignment(log_likelihood, labels.as_mut_slice(), &mut rng).unwrap();
 /// ```
-pub fn soft_assignment(
+pub fn soft_assignment<L: Label>(
     log_likelihood: DMatrix<f64>,
-    labels: &mut [usize],
+    labels: &mut [L],
     rng: &mut impl Rng,
-) {
+) -> Result<(), Error> {
+    for (point, col) in log_likelihood.column_iter().enumerate() {
+        if let Some(&value) = col.iter().find(|x| x.is_nan() || **x == f64::INFINITY) {
+            return Err(Error::InvalidWeight { point, value });
+        }
+    }
+
     let probs = col_normalize_log_weights(log_likelihood);
-    for (i, col) in probs.column_iter().enumerate() {
-        replacement_sampling_weighted(rng, col.into_iter().cloned(), &mut labels[i..=i]);
+    for (point, col) in probs.column_iter().enumerate() {
+        let label = sample_weighted(rng, col.iter().cloned())
+            .ok_or(Error::ZeroWeights { point })?;
+        labels[point] = L::from_usize(label);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DMatrix;
+    use rand::prelude::StdRng;
+    use rand::{Rng, SeedableRng};
+    use statrs::distribution::MultivariateNormal;
+    use crate::error::Error;
+    use crate::params::thin::{hard_assignment_weighted_ties, MixtureParams, OwnedThinParams, soft_assignment, SuperMixtureParams};
+    use crate::stats::tests::test_almost_mat;
+
+    #[test]
+    fn test_soft_assignment_errors() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut labels = [0usize; 3];
+
+        let zero = DMatrix::from_column_slice(2, 3, &[0.0, -1.0, f64::NEG_INFINITY, f64::NEG_INFINITY, -2.0, 0.0]);
+        assert_eq!(soft_assignment(zero, &mut labels, &mut rng), Err(Error::ZeroWeights { point: 1 }));
+
+        let nan = DMatrix::from_column_slice(2, 3, &[0.0, -1.0, -3.0, -2.0, f64::NAN, 0.0]);
+        assert!(matches!(
+            soft_assignment(nan, &mut labels, &mut rng),
+            Err(Error::InvalidWeight { point: 2, value }) if value.is_nan()
+        ));
+
+        let ok = DMatrix::from_column_slice(2, 3, &[0.0, f64::NEG_INFINITY, -3.0, -2.0, f64::NEG_INFINITY, 0.0]);
+        assert_eq!(soft_assignment(ok, &mut labels, &mut rng), Ok(()));
+        assert_eq!(labels, [0, 1, 1]);
+    }
+
+    #[test]
+    fn test_hard_assignment_weighted_ties() {
+        let ninf = f64::NEG_INFINITY;
+        let log_likelihood = DMatrix::from_column_slice(3, 5, &[
+            -1.0, -2.0, -1.0,   // tie between 0 and 2, 2 is heavier
+            -1.0, -1.0, -3.0,   // tie between 0 and 1 with equal weights, lowest index wins
+            -4.0, -3.0, -3.5,   // no tie
+            ninf, ninf, ninf,   // all clusters tie
+            f64::NAN, -2.0, -2.0, // NaN is ignored
+        ]);
+        let mut labels = [0usize; 5];
+        hard_assignment_weighted_ties(&log_likelihood, &[0.3, 0.3, 0.4], &mut labels);
+        assert_eq!(labels, [2, 0, 1, 2, 2]);
+
+        // Reversing the cluster order picks the same clusters, except for ties between equal weights
+        let reversed = DMatrix::from_fn(3, 5, |k, i| log_likelihood[(2 - k, i)]);
+        hard_assignment_weighted_ties(&reversed, &[0.4, 0.3, 0.3], &mut labels);
+        assert_eq!(labels, [0, 1, 1, 0, 0]);
+    }
+
+    #[test]
+    fn test_predict_normalized() {
+        let data = DMatrix::new_random(2, 50);
+        let dist = |mu: f64| MultivariateNormal::new(vec![mu, mu], vec![1.0, 0.0, 0.0, 1.0]).unwrap();
+        let params = OwnedThinParams {
+            clusters: vec![dist(0.0), dist(1.0), dist(2.0)],
+            cluster_weights: vec![0.2, 0.3, 0.5],
+            clusters_aux: vec![],
+            cluster_weights_aux: vec![],
+        };
+
+        let (probs, _) = SuperMixtureParams(&params).predict(data);
+        for col in probs.column_iter() {
+            statrs::assert_almost_eq!(col.sum(), 1.0, 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_par_log_likelihood() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let data = DMatrix::from_fn(2, 1003, |_, _| rng.gen_range(-5.0..5.0));
+        let params = OwnedThinParams {
+            clusters: vec![
+                MultivariateNormal::new(vec![-1.0, 0.0], vec![1.0, 0.2, 0.2, 1.0]).unwrap(),
+                MultivariateNormal::new(vec![2.0, 1.0], vec![0.5, 0.0, 0.0, 2.0]).unwrap(),
+            ],
+            cluster_weights: vec![0.3, 0.7],
+            clusters_aux: vec![],
+            cluster_weights_aux: vec![],
+        };
+
+        let ll = SuperMixtureParams(&params).log_likelihood(data.clone());
+        for chunk_size in [1, 100, 1003, 5000] {
+            assert_eq!(SuperMixtureParams(&params).par_log_likelihood(&data, chunk_size), ll);
+        }
+    }
+
+    #[test]
+    fn test_update_log_likelihood() {
+        let data = DMatrix::new_random(2, 50);
+        let dist = |mu: f64| MultivariateNormal::new(vec![mu, mu], vec![1.0, 0.0, 0.0, 1.0]).unwrap();
+
+        let mut params = OwnedThinParams {
+            clusters: vec![dist(0.0), dist(1.0), dist(2.0)],
+            cluster_weights: vec![0.2, 0.3, 0.5],
+            clusters_aux: vec![[dist(0.0), dist(0.0)]; 3],
+            cluster_weights_aux: vec![[0.5, 0.5]; 3],
+        };
+        let mut ll = SuperMixtureParams(&params).log_likelihood(data.clone());
+
+        params.clusters[1] = dist(-1.0);
+        SuperMixtureParams(&params).update_log_likelihood(&mut ll, &data, 1);
+
+        test_almost_mat(&ll, &SuperMixtureParams(&params).log_likelihood(data), 1e-12);
     }
-}
\ No newline at end of file
+}
@@ -1,15 +1,63 @@
+use nalgebra::{DMatrix, DVector, RowDVector};
 use rand::Rng;
 use statrs::distribution::MultivariateNormal;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
 use crate::params::clusters::{ClusterParams, SuperClusterParams, SuperClusterStats};
 use crate::params::options::{ModelOptions, OutlierRemoval};
-use crate::params::thin::ThinParams;
-use crate::stats::{NormalConjugatePrior, SplitMerge, stick_breaking_sample};
-use crate::state::GlobalWorker;
+use crate::params::thin::{MixtureParams, SuperMixtureParams, ThinParams};
+use crate::stats::{AcceptanceStats, ContinuousBatchwise, FromData, NormalConjugatePrior, SplitMerge, SplitMergeEvent, SplitMergeKind, SplitRngState, stick_breaking_sample, SufficientStats};
+use crate::error::Error;
+use crate::metrics::soft_label_entropy;
+use crate::state::{GlobalWorker, LocalState, LocalWorker};
+use crate::utils::{argmax, col_normalize_log_weights, DataScalar, Label};
 
+/// Summary of a fitted (primary) cluster, see [`GlobalState::summarize`].
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClusterSummary {
+    /// Index of the cluster, i.e. its label
+    pub id: usize,
+    /// Mixture weight of the cluster
+    pub weight: f64,
+    /// Number of points assigned to the cluster in the last iteration
+    pub n_points: usize,
+    /// Mean of the cluster distribution
+    pub mean: DVector<f64>,
+    /// Covariance of the cluster distribution
+    pub covariance: DMatrix<f64>,
+}
+
+/// Per-point outlier scores computed by [`GlobalState::outlier_scores`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutlierScores {
+    /// Mahalanobis distance of each point to its assigned cluster, `f64::INFINITY` for points assigned to
+    /// the outlier cluster. (n_points)
+    pub scores: DVector<f64>,
+    /// Clusters with a numerically singular covariance (a condition number beyond `1 / f64::EPSILON`, e.g. of
+    /// collinear points kept invertible only by the jitter), whose points are scored by their euclidean distance
+    /// to the cluster mean instead.
+    pub singular_clusters: Vec<usize>,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(
+    serialize = "P::HyperParams: Serialize, P::SuffStats: Serialize",
+    deserialize = "P::HyperParams: Deserialize<'de>, P::SuffStats: Deserialize<'de>",
+)))]
 #[derive(Debug, Clone, PartialEq)]
 pub struct GlobalState<P: NormalConjugatePrior> {
     pub clusters: Vec<SuperClusterParams<P>>,
     pub weights: Vec<f64>,
+    /// Random stream for the split/merge proposals, stored so resumed fits make identical decisions
+    pub split_rng: SplitRngState,
+    /// Split/merge proposals made since the start of the last fit
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub acceptance: AcceptanceStats,
+    /// Split/merge proposals made since the events were last taken out, only recorded if set. The iteration
+    /// of the events is left to the caller.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub split_merge_events: Option<Vec<SplitMergeEvent>>,
 }
 
 impl<P: NormalConjugatePrior> GlobalState<P> {
@@ -30,9 +78,11 @@ impl<P: NormalConjugatePrior> GlobalState<P> {
         let mut clusters = Vec::new();
         let mut points_count = Vec::new();
 
-        for k in 0..n_clusters + options.outlier.is_some() as usize {
-            let (prior, stats) = match (k, &options.outlier) {
-                (0, Some(OutlierRemoval { dist, .. })) => (dist, data_stats.clone()), // TODO: use data stats
+        for k in 0..options.cluster_offset() + n_clusters {
+            let (prior, stats) = match &options.outlier {
+                Some(OutlierRemoval { dist, .. }) if options.outlier_index() == Some(k) => {
+                    (dist, data_stats.clone()) // TODO: use data stats
+                }
                 _ => (&options.data_dist, P::SuffStats::default())
             };
 
@@ -49,7 +99,7 @@ impl<P: NormalConjugatePrior> GlobalState<P> {
         }
 
         let weights = if let Some(OutlierRemoval { weight, .. }) = &options.outlier {
-            stick_breaking_sample(&points_count[1..], *weight, rng)
+            stick_breaking_sample(&points_count[options.cluster_offset()..], *weight, rng)
         } else {
             stick_breaking_sample(&points_count[..], 0.0, rng)
         };
@@ -57,10 +107,655 @@ impl<P: NormalConjugatePrior> GlobalState<P> {
         Self {
             clusters,
             weights,
+            split_rng: SplitRngState::new(rng.gen()),
+            acceptance: AcceptanceStats::default(),
+            split_merge_events: None,
+        }
+    }
+
+    /// Creates a new global state from the given full data statistics, validating the configuration first.
+    ///
+    /// # Arguments
+    ///
+    /// * `data_stats`: The full data statistics
+    /// * `n_clusters`: The number of clusters to initialize (excluding the outlier cluster)
+    /// * `options`: The model options
+    /// * `rng`: The random number generator
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error`] if no clusters are requested, the data is empty or the data dimension
+    /// does not match `options.dim`.
+    pub fn try_from_init<R: Rng>(
+        data_stats: &P::SuffStats,
+        n_clusters: usize,
+        options: &ModelOptions<P>,
+        rng: &mut R,
+    ) -> Result<Self, Error> {
+        if n_clusters == 0 {
+            return Err(Error::EmptyCluster);
+        }
+        if data_stats.n_points() == 0 {
+            return Err(Error::EmptyData);
+        }
+        if data_stats.dim() != options.dim {
+            return Err(Error::DimensionMismatch { expected: options.dim, got: data_stats.dim() });
+        }
+
+        Ok(Self::from_init(data_stats, n_clusters, options, rng))
+    }
+
+    /// Creates a global state from the parameters of a fitted scikit-learn `GaussianMixture`
+    /// (with `covariance_type="full"`). If outlier removal is enabled, an outlier cluster is prepended
+    /// and the component weights are scaled to make room for its weight.
+    ///
+    /// # Arguments
+    ///
+    /// * `means`: The component means `means_` (n_components, n_features)
+    /// * `covariances`: The component covariances `covariances_`, one (n_features, n_features) matrix per component
+    /// * `weights`: The component weights `weights_` (n_components)
+    /// * `options`: The model options
+    /// * `rng`: The random number generator
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error`] if the shapes are inconsistent with each other or `options.dim`, a covariance
+    /// is not symmetric positive definite, or the weights are invalid.
+    pub fn from_sklearn_params<R: Rng>(
+        means: &DMatrix<f64>,
+        covariances: &[DMatrix<f64>],
+        weights: &[f64],
+        options: &ModelOptions<P>,
+        rng: &mut R,
+    ) -> Result<Self, Error> {
+        let n_components = means.nrows();
+        if n_components == 0 {
+            return Err(Error::EmptyCluster);
+        }
+        if means.ncols() != options.dim {
+            return Err(Error::DimensionMismatch { expected: options.dim, got: means.ncols() });
+        }
+        for got in [covariances.len(), weights.len()] {
+            if got != n_components {
+                return Err(Error::ComponentCountMismatch { expected: n_components, got });
+            }
+        }
+
+        let weight_sum: f64 = weights.iter().sum();
+        if weights.iter().any(|w| !w.is_finite() || *w < 0.0) || weight_sum <= 0.0 {
+            return Err(Error::DegenerateWeights);
+        }
+
+        let mut clusters = Vec::with_capacity(options.cluster_offset() + n_components);
+        let mut cluster_weights = Vec::with_capacity(clusters.capacity());
+        let mut add_cluster = |prior: &P::HyperParams, dist: MultivariateNormal, weight: f64, rng: &mut R| {
+            let prim = ClusterParams::new(prior.clone(), prior.clone(), P::SuffStats::default(), dist);
+            clusters.push(SuperClusterParams::from_split_params(prim, options.alpha, options.burnout_period, rng));
+            cluster_weights.push(weight);
+        };
+
+        let outlier_weight = match &options.outlier {
+            Some(OutlierRemoval { weight, dist }) => {
+                add_cluster(dist, P::sample(dist, rng), *weight, rng);
+                *weight
+            }
+            None => 0.0,
+        };
+
+        for (k, cov) in covariances.iter().enumerate() {
+            if cov.nrows() != options.dim || cov.ncols() != options.dim {
+                let got = if cov.nrows() != options.dim { cov.nrows() } else { cov.ncols() };
+                return Err(Error::DimensionMismatch { expected: options.dim, got });
+            }
+            if (cov - cov.transpose()).amax() > 1e-8 * cov.amax().max(1.0) {
+                return Err(Error::NotPositiveDefinite { component: k });
+            }
+
+            let dist = MultivariateNormal::new(
+                means.row(k).iter().cloned().collect(),
+                cov.iter().cloned().collect(),
+            ).map_err(|_| Error::NotPositiveDefinite { component: k })?;
+
+            add_cluster(&options.data_dist, dist, (1.0 - outlier_weight) * weights[k] / weight_sum, rng);
         }
+
+        Ok(Self {
+            clusters,
+            weights: cluster_weights,
+            split_rng: SplitRngState::new(rng.gen()),
+            acceptance: AcceptanceStats::default(),
+            split_merge_events: None,
+        })
     }
 }
 
+impl<P: NormalConjugatePrior> GlobalState<P> {
+    /// Exports the sufficient statistics of each (primary) cluster, e.g. to combine statistics
+    /// computed on separate machines with [`GlobalState::import_cluster_stats`].
+    pub fn export_cluster_stats(&self) -> Vec<P::SuffStats> {
+        self.clusters.iter().map(|cluster| cluster.prim.stats.clone()).collect()
+    }
+
+    /// Creates a global state from the sufficient statistics of each cluster. Statistics exported from
+    /// separate data partitions (with the same cluster order) can be summed before importing.
+    /// If outlier removal is enabled, the first cluster is the outlier cluster.
+    ///
+    /// # Arguments
+    ///
+    /// * `stats`: The sufficient statistics of each cluster
+    /// * `options`: The model options
+    /// * `rng`: The random number generator
+    pub fn import_cluster_stats<R: Rng>(
+        stats: Vec<P::SuffStats>,
+        options: &ModelOptions<P>,
+        rng: &mut R,
+    ) -> Self {
+        let mut clusters = Vec::with_capacity(stats.len());
+        let mut points_count = Vec::with_capacity(stats.len());
+
+        for (k, stats) in stats.into_iter().enumerate() {
+            let prior = match &options.outlier {
+                Some(OutlierRemoval { dist, .. }) if options.outlier_index() == Some(k) => dist,
+                _ => &options.data_dist,
+            };
+
+            let post = P::posterior(prior, &stats);
+            let dist = P::sample(&post, rng);
+            let prim = ClusterParams::new(prior.clone(), post, stats, dist);
+            let cluster = SuperClusterParams::from_split_params(prim, options.alpha, options.burnout_period, rng);
+            points_count.push((cluster.n_points() as f64).max(1.0));
+            clusters.push(cluster);
+        }
+
+        let weights = if let Some(OutlierRemoval { weight, .. }) = &options.outlier {
+            stick_breaking_sample(&points_count[options.cluster_offset()..], *weight, rng)
+        } else {
+            stick_breaking_sample(&points_count[..], 0.0, rng)
+        };
+
+        Self {
+            clusters,
+            weights,
+            split_rng: SplitRngState::new(rng.gen()),
+            acceptance: AcceptanceStats::default(),
+            split_merge_events: None,
+        }
+    }
+
+    /// Dimensionality of the cluster distributions.
+    pub fn dim(&self) -> usize {
+        self.clusters.first().map_or(0, |cluster| cluster.prim.dist.mu().len())
+    }
+
+    /// Log-determinant of each (primary) cluster covariance, computed from the diagonal of its
+    /// Cholesky factor as `2 * sum(ln(diag(L)))`.
+    pub fn cluster_log_dets(&self) -> Vec<f64> {
+        self.clusters.iter().map(|cluster| {
+            let chol = cluster.prim.dist.cov().clone().cholesky()
+                .expect("Cluster covariance must be positive definite");
+            2.0 * chol.l_dirty().diagonal().iter().map(|x| x.ln()).sum::<f64>()
+        }).collect()
+    }
+
+    /// Merges cluster `source` wholesale into cluster `target`, e.g. to combine clusters known to be the same
+    /// without re-running inference: their statistics are combined, the merged distribution is resampled from
+    /// the combined posterior and their weights are summed. The two clusters become the auxiliary clusters of
+    /// the merged cluster and the clusters after `source` are re-indexed. If given, the labels of `local` are
+    /// updated accordingly.
+    ///
+    /// # Arguments
+    ///
+    /// * `target`: The cluster to merge into
+    /// * `source`: The cluster to merge, removed afterwards
+    /// * `local`: The local state holding the labels of the fitted data
+    /// * `options`: The model options
+    /// * `rng`: The random number generator
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidData`] if `target == source`, either index is out of range or either is the
+    /// outlier cluster.
+    pub fn merge_clusters<L: Label, T: DataScalar, R: Rng>(
+        &mut self,
+        target: usize,
+        source: usize,
+        local: Option<&mut LocalState<P, L, T>>,
+        options: &ModelOptions<P>,
+        rng: &mut R,
+    ) -> Result<(), Error> {
+        if target == source {
+            return Err(Error::InvalidData(format!("cannot merge cluster {} with itself", target)));
+        }
+        if let Some(k) = options.outlier_index().filter(|&k| k == target || k == source) {
+            return Err(Error::InvalidData(format!("cannot merge the outlier cluster {}", k)));
+        }
+        if let Some(&k) = [target, source].iter().find(|&&k| k >= self.clusters.len()) {
+            return Err(Error::InvalidData(format!(
+                "cluster {} out of range for {} clusters", k, self.clusters.len()
+            )));
+        }
+
+        let mut merged = SuperClusterParams::from_merge_params(
+            self.clusters[target].prim.clone(), self.clusters[source].prim.clone(),
+            options.alpha, options.burnout_period, rng,
+        );
+        merged.prim.dist = merged.prim.sample(rng);
+        self.clusters[target] = merged;
+        self.clusters.remove(source);
+
+        self.weights[target] += self.weights[source];
+        self.weights.remove(source);
+
+        if let Some(local) = local {
+            for (label, label_aux) in local.labels.iter_mut().zip(local.labels_aux.iter_mut()) {
+                let mut k = label.as_usize();
+                if k == target {
+                    *label_aux = L::from_usize(0);
+                } else if k == source {
+                    k = target;
+                    *label_aux = L::from_usize(1);
+                }
+                if k > source {
+                    k -= 1;
+                }
+                *label = L::from_usize(k);
+            }
+        }
+        Ok(())
+    }
+
+    /// Density mode of each (primary) cluster, a canonical exemplar of the cluster.
+    /// For the normal cluster distributions (as for a Student-t predictive) this is the location, i.e. the mean.
+    pub fn cluster_modes(&self) -> Vec<DVector<f64>> {
+        self.clusters.iter().map(|cluster| cluster.prim.dist.mu().clone()).collect()
+    }
+
+    /// Index of the outlier cluster of a state fitted with the given options, `None` if outlier removal is
+    /// disabled. See [`ModelOptions::outlier_index`].
+    pub fn outlier_index(&self, options: &ModelOptions<P>) -> Option<usize> {
+        options.outlier_index().filter(|&k| k < self.clusters.len())
+    }
+
+    /// Relabels the clusters in descending order of their number of points, ties broken by the first point
+    /// (in data order) assigned to them. The numbering of the clusters after a fit depends on the order in
+    /// which they were created, merged and removed, canonicalizing makes the labels of different fits comparable.
+    /// The outlier cluster keeps its index.
+    ///
+    /// # Arguments
+    ///
+    /// * `local`: The local state the clusters were fitted on, its primary labels are remapped accordingly
+    /// * `options`: The model options the state was fitted with
+    pub fn canonicalize<L: Label, T: DataScalar>(&mut self, local: &mut LocalState<P, L, T>, options: &ModelOptions<P>) {
+        let n_clusters = self.clusters.len();
+        let mut first_seen = vec![usize::MAX; n_clusters];
+        for (i, label) in local.labels.iter().enumerate() {
+            let k = label.as_usize();
+            first_seen[k] = first_seen[k].min(i);
+        }
+
+        let offset = options.cluster_offset().min(n_clusters);
+        let mut order: Vec<usize> = (offset..n_clusters).collect();
+        order.sort_by_key(|&k| (std::cmp::Reverse(self.clusters[k].n_points()), first_seen[k], k));
+        let order: Vec<usize> = (0..offset).chain(order).collect();
+
+        let mut new_index = vec![0; n_clusters];
+        for (new, &old) in order.iter().enumerate() {
+            new_index[old] = new;
+        }
+
+        let mut clusters: Vec<_> = std::mem::take(&mut self.clusters).into_iter().map(Some).collect();
+        self.clusters = order.iter().map(|&k| clusters[k].take().unwrap()).collect();
+        if self.weights.len() == n_clusters {
+            self.weights = order.iter().map(|&k| self.weights[k]).collect();
+        }
+        for label in local.labels.iter_mut() {
+            *label = L::from_usize(new_index[label.as_usize()]);
+        }
+    }
+
+    /// Number of points assigned to each (primary) cluster in the last iteration, including the outlier
+    /// cluster if outlier removal is enabled.
+    pub fn cluster_sizes(&self) -> Vec<usize> {
+        self.clusters.iter().map(|cluster| cluster.n_points()).collect()
+    }
+
+    /// Summarizes each (primary) cluster by its weight, size and the mean and covariance of its
+    /// (sampled) distribution.
+    ///
+    /// # Example
+    /// ```
+    /// use nalgebra::DMatrix;
+    /// use mixturs::{FitOptions, Model, ModelOptions, MonitoringCallback, NIW};
+    /// use mixturs::state::GlobalState;
+    ///
+    /// let data = DMatrix::new_random(2, 100);
+    /// let mut model = Model::from_options(ModelOptions::<NIW>::default(2));
+    /// model.fit(data, &FitOptions::default(), None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+    ///
+    /// let summary = model.params().summarize();
+    /// assert_eq!(summary.iter().map(|cluster| cluster.n_points).sum::<usize>(), 100);
+    /// ```
+    pub fn summarize(&self) -> Vec<ClusterSummary> {
+        self.clusters.iter().zip(&self.weights).enumerate().map(|(id, (cluster, &weight))| ClusterSummary {
+            id,
+            weight,
+            n_points: cluster.n_points(),
+            mean: cluster.prim.dist.mu().clone(),
+            covariance: cluster.prim.dist.cov().clone(),
+        }).collect()
+    }
+
+    /// Cluster assigned to points that lie far outside every cluster (zero density under all of them):
+    /// the outlier cluster if outlier removal is enabled, otherwise the cluster with the highest weight.
+    fn fallback_cluster(&self, options: &ModelOptions<P>) -> usize {
+        match self.outlier_index(options) {
+            Some(k) => k,
+            None => self.weights.iter().enumerate()
+                .max_by(|a, b| a.1.total_cmp(b.1))
+                .map_or(0, |(k, _)| k),
+        }
+    }
+
+    /// Flags the points whose density under their most likely non-outlier cluster falls below
+    /// [`ModelOptions::outlier_min_density`]. No points are flagged if outlier removal is disabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The data points (n_dim, n_points)
+    /// * `options`: The model options the state was fitted with
+    ///
+    /// # Returns
+    ///
+    /// Whether each point should be assigned to the outlier cluster. (n_points)
+    pub fn low_density_points(&self, data: &DMatrix<f64>, options: &ModelOptions<P>) -> Vec<bool> {
+        let min_density = match (options.outlier_min_density, &options.outlier) {
+            (Some(min_density), Some(_)) => min_density,
+            _ => return vec![false; data.ncols()],
+        };
+
+        let min_ln_density = min_density.ln();
+        let mut best = vec![f64::NEG_INFINITY; data.ncols()];
+        for cluster in &self.clusters[options.cluster_offset()..] {
+            let ll = cluster.prim.dist.batchwise_ln_pdf(data.clone_owned());
+            for (best, l) in best.iter_mut().zip(ll.iter()) {
+                *best = best.max(*l);
+            }
+        }
+
+        best.into_iter().map(|l| l < min_ln_density).collect()
+    }
+
+    /// Assigns each (new) data point to its most likely cluster together with the normalized responsibilities
+    /// of the clusters, see [`MixtureParams::predict`]. Points below [`ModelOptions::outlier_min_density`] are
+    /// assigned to the outlier cluster with full responsibility.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The data points (n_dim, n_points)
+    /// * `options`: The model options the state was fitted with
+    ///
+    /// # Returns
+    ///
+    /// * The responsibility matrix. (n_clusters, n_points)
+    /// * The cluster label of each point. (n_points)
+    pub fn predict_with_confidence(&self, data: DMatrix<f64>, options: &ModelOptions<P>) -> (DMatrix<f64>, RowDVector<usize>) {
+        let low_density = self.low_density_points(&data, options);
+        let (mut probs, mut labels) = SuperMixtureParams(self).predict(data);
+
+        if let Some(outlier) = options.outlier_index() {
+            for (i, _) in low_density.into_iter().enumerate().filter(|(_, low)| *low) {
+                labels[i] = outlier;
+                probs.column_mut(i).fill(0.0);
+                probs[(outlier, i)] = 1.0;
+            }
+        }
+
+        (probs, labels)
+    }
+
+    /// Assigns each (new) data point to its most likely cluster, i.e. the argmax over the weighted
+    /// cluster log densities, without sampling or touching any state. Points with a zero density under
+    /// every cluster are assigned to the outlier cluster if outlier removal is enabled, otherwise to the
+    /// cluster with the highest weight. Points below [`ModelOptions::outlier_min_density`] are assigned
+    /// to the outlier cluster.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The data points (n_dim, n_points)
+    /// * `options`: The model options the state was fitted with
+    ///
+    /// # Returns
+    ///
+    /// The cluster label of each point. (n_points)
+    pub fn predict(&self, data: &DMatrix<f64>, options: &ModelOptions<P>) -> DVector<usize> {
+        let fallback = self.fallback_cluster(options);
+        let outlier = options.outlier_index();
+        let low_density = self.low_density_points(data, options);
+        let ll = SuperMixtureParams(self).log_likelihood(data.clone_owned());
+        DVector::from_iterator(ll.ncols(), ll.column_iter().zip(low_density).map(|(col, low_density)| {
+            match outlier {
+                Some(outlier) if low_density => outlier,
+                _ if col.max() == f64::NEG_INFINITY => fallback,
+                _ => argmax(col.iter().cloned()).unwrap_or(fallback),
+            }
+        }))
+    }
+
+    /// Computes the responsibilities of the clusters for each (new) data point, normalized to sum to one
+    /// per point. Points with a zero density under every cluster are fully assigned to the cluster
+    /// [`GlobalState::predict`] falls back to, and points below [`ModelOptions::outlier_min_density`]
+    /// to the outlier cluster.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The data points (n_dim, n_points)
+    /// * `options`: The model options the state was fitted with
+    ///
+    /// # Returns
+    ///
+    /// The responsibility matrix. (n_points, n_clusters)
+    pub fn predict_proba(&self, data: &DMatrix<f64>, options: &ModelOptions<P>) -> DMatrix<f64> {
+        let fallback = self.fallback_cluster(options);
+        let outlier = options.outlier_index();
+        let low_density = self.low_density_points(data, options);
+        let ll = SuperMixtureParams(self).log_likelihood(data.clone_owned());
+
+        let mut probs = DMatrix::zeros(ll.ncols(), ll.nrows());
+        for (i, col) in col_normalize_log_weights(ll).column_iter().enumerate() {
+            if let (Some(outlier), true) = (outlier, low_density[i]) {
+                probs[(i, outlier)] = 1.0;
+                continue;
+            }
+
+            // Points with zero density under every cluster normalize to NaN
+            let sum = col.sum();
+            if sum.is_nan() {
+                probs[(i, fallback)] = 1.0;
+                continue;
+            }
+            probs.row_mut(i).copy_from(&(col / sum).transpose());
+        }
+        probs
+    }
+
+    /// Mean Shannon entropy (in nats) of the responsibilities of the data points, see [`GlobalState::predict_proba`].
+    /// Confident assignments to well separated clusters give an entropy near zero, points shared between
+    /// overlapping clusters increase it up to `ln(n_clusters)`. Zero if there are no points.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The data points (n_dim, n_points)
+    /// * `options`: The model options the state was fitted with
+    pub fn mean_assignment_entropy(&self, data: &DMatrix<f64>, options: &ModelOptions<P>) -> f64 {
+        if data.ncols() == 0 {
+            return 0.0;
+        }
+
+        soft_label_entropy(&self.predict_proba(data, options)).mean()
+    }
+
+    /// Scores how much each point is an outlier of the cluster it is assigned to (see [`GlobalState::predict`])
+    /// by its Mahalanobis distance `sqrt((x - mu)^T cov^-1 (x - mu))` to the cluster distribution.
+    /// Useful to threshold outliers post-hoc, in addition to [`ModelOptions::outlier`].
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The data points (n_dim, n_points)
+    /// * `options`: The model options the state was fitted with
+    pub fn outlier_scores(&self, data: &DMatrix<f64>, options: &ModelOptions<P>) -> OutlierScores {
+        let labels = self.predict(data, options);
+        let cholesky: Vec<_> = self.clusters.iter()
+            .map(|cluster| cluster.prim.dist.cov().clone().cholesky().filter(|chol| {
+                let pivots = chol.l_dirty().diagonal();
+                pivots.min() > pivots.max() * f64::EPSILON.sqrt()
+            }))
+            .collect();
+        let singular_clusters = (0..self.clusters.len())
+            .filter(|&k| cholesky[k].is_none() && options.outlier_index() != Some(k))
+            .collect();
+
+        let scores = DVector::from_iterator(data.ncols(), data.column_iter().zip(labels.iter()).map(|(x, &k)| {
+            if options.outlier_index() == Some(k) {
+                return f64::INFINITY;
+            }
+
+            let diff = x - self.clusters[k].prim.dist.mu();
+            match &cholesky[k] {
+                Some(chol) => chol.l_dirty().solve_lower_triangular(&diff)
+                    .map_or(f64::INFINITY, |z| z.norm()),
+                None => diff.norm(),
+            }
+        }));
+
+        OutlierScores { scores, singular_clusters }
+    }
+
+    /// Incrementally updates a fitted state with a batch of new points without revisiting the data it was
+    /// fitted on. The new points are labelled using [`GlobalState::predict`] and a few sampling sweeps are run
+    /// over them, during which clusters may be split (e.g. to cover a new mode) or merged. The statistics of
+    /// the previously seen points are kept fixed and added to those of the new points, so the counts of the
+    /// existing clusters grow rather than reset.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_data`: The new data points (n_features, n_points)
+    /// * `options`: The model options the state was fitted with
+    /// * `n_iters`: Number of sampling sweeps over the new points
+    /// * `rng`: The random number generator
+    ///
+    /// # Returns
+    ///
+    /// The labels of the new points.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error`] if the labels of a new point can not be sampled.
+    pub fn partial_fit<R: Rng + Clone + Send + Sync>(
+        &mut self,
+        new_data: &DMatrix<f64>,
+        options: &ModelOptions<P>,
+        n_iters: usize,
+        rng: &mut R,
+    ) -> Result<RowDVector<usize>, Error> {
+        let labels = self.predict(new_data, options);
+        let mut local = LocalState::<P>::from_data(new_data.clone_owned());
+        local.labels = labels.transpose();
+        local.labels_aux.apply(|v| *v = rng.gen_range(0..2));
+
+        // Statistics of the previously seen points, kept aligned with the clusters. They are attributed to the
+        // first auxiliary cluster so a split moves new points away from them instead of tearing them apart.
+        let empty = || P::SuffStats::from_data(&DMatrix::zeros(new_data.nrows(), 0));
+        let mut base: Vec<SuperClusterStats<P>> = self.clusters.iter()
+            .map(|c| SuperClusterStats::new(c.prim.stats.clone(), [c.prim.stats.clone(), empty()]))
+            .collect();
+
+        for _ in 0..n_iters {
+            self.update_sample_clusters(options, rng);
+            local.apply_label_sampling(&*self, false, rng)?;
+
+            let stats = local.collect_cluster_stats(GlobalWorker::n_clusters(self));
+            self.update_clusters_post(combine_stats(&base, stats));
+
+            let bad_clusters = self.collect_bad_clusters();
+            local.apply_cluster_reset(&bad_clusters, rng);
+
+            // The previously seen points of a dissolved cluster follow its new points
+            let small_clusters = self.collect_small_clusters(options);
+            if !small_clusters.is_empty() {
+                local.apply_cluster_reassign(&small_clusters, rng);
+                for &(from, to) in &small_clusters {
+                    let prim = add_stats(&base[to].prim, &base[from].prim);
+                    base[to] = SuperClusterStats::new(prim.clone(), [prim, empty()]);
+                    base[from] = SuperClusterStats::new(empty(), [empty(), empty()]);
+                }
+                let stats = local.collect_cluster_stats(GlobalWorker::n_clusters(self));
+                self.update_clusters_post(combine_stats(&base, stats));
+                let removed_idx = self.collect_remove_clusters(options);
+                local.apply_cluster_remove(&removed_idx);
+                remove_indices(&mut base, &removed_idx);
+            }
+
+            // The previously seen points of a split cluster follow its auxiliary clusters
+            let mut split_rng = self.split_rng.next_rng();
+            let split_idx = self.check_and_split(options, &mut split_rng);
+            local.apply_split(&split_idx, &mut split_rng);
+            for &(k, new_idx) in &split_idx {
+                let [aux_l, aux_r] = base[k].aux.clone();
+                base.push(SuperClusterStats::new(aux_r.clone(), [aux_r, empty()]));
+                base[k] = SuperClusterStats::new(aux_l.clone(), [aux_l, empty()]);
+                debug_assert_eq!(base.len(), new_idx + 1);
+            }
+            if !split_idx.is_empty() {
+                let stats = local.collect_cluster_stats(GlobalWorker::n_clusters(self));
+                self.update_clusters_post(combine_stats(&base, stats));
+            }
+
+            let merge_idx = self.check_and_merge(options, &mut split_rng);
+            local.apply_merge(&merge_idx);
+            for &(ki, kj) in &merge_idx {
+                let (prim_i, prim_j) = (base[ki].prim.clone(), base[kj].prim.clone());
+                base[ki] = SuperClusterStats::new(add_stats(&prim_i, &prim_j), [prim_i, prim_j]);
+                base[kj] = SuperClusterStats::new(empty(), [empty(), empty()]);
+            }
+
+            let removed_idx = self.collect_remove_clusters(options);
+            local.apply_cluster_remove(&removed_idx);
+            remove_indices(&mut base, &removed_idx);
+        }
+
+        Ok(local.collect_labels())
+    }
+}
+
+/// Removes the items at the given (sorted) indices, keeping the order of the others.
+fn remove_indices<T>(items: &mut Vec<T>, removed_idx: &[usize]) {
+    let mut k = 0;
+    items.retain(|_| {
+        k += 1;
+        !removed_idx.contains(&(k - 1))
+    });
+}
+
+/// Adds two sufficient statistics, either of which may be empty (of unknown dimension).
+fn add_stats<S: SufficientStats>(a: &S, b: &S) -> S {
+    match (a.n_points(), b.n_points()) {
+        (0, _) => b.clone(),
+        (_, 0) => a.clone(),
+        _ => a.clone() + b,
+    }
+}
+
+/// Adds the base statistics of each cluster to the given (new) statistics.
+fn combine_stats<P: NormalConjugatePrior>(
+    base: &[SuperClusterStats<P>],
+    stats: Vec<SuperClusterStats<P>>,
+) -> Vec<SuperClusterStats<P>> {
+    base.iter().zip(stats).map(|(base, stats)| SuperClusterStats::new(
+        add_stats(&base.prim, &stats.prim),
+        [add_stats(&base.aux[0], &stats.aux[0]), add_stats(&base.aux[1], &stats.aux[1])],
+    )).collect()
+}
+
 impl<P: NormalConjugatePrior> GlobalWorker<P> for GlobalState<P> {
     fn n_clusters(&self) -> usize {
         self.clusters.len()
@@ -92,11 +787,20 @@ impl<P: NormalConjugatePrior> GlobalWorker<P> for GlobalState<P> {
                 cluster.aux.iter().map(|c| c.marginal_log_likelihood()).sum::<f64>()
             );
             cluster.splittable = cluster.ll_history.converged(options.burnout_period);
+            cluster.split_warmup = if cluster.splittable { cluster.split_warmup + 1 } else { 0 };
             points_count.push(cluster.n_points() as f64);
         }
 
+        // Add the informative weight prior
+        if let Some(pseudo_counts) = &options.weight_pseudo_counts {
+            let offset = options.cluster_offset();
+            for (count, pseudo_count) in points_count[offset..].iter_mut().zip(pseudo_counts) {
+                *count += pseudo_count;
+            }
+        }
+
         self.weights = if let Some(OutlierRemoval { weight, .. }) = &options.outlier {
-            stick_breaking_sample(&points_count[1..], *weight, rng)
+            stick_breaking_sample(&points_count[options.cluster_offset()..], *weight, rng)
         } else {
             stick_breaking_sample(&points_count[..], 0.0, rng)
         };
@@ -108,6 +812,7 @@ impl<P: NormalConjugatePrior> GlobalWorker<P> for GlobalState<P> {
             if cluster.aux.iter().any(|c| c.n_points() == 0) {
                 cluster.ll_history.clear();
                 cluster.splittable = false;
+                cluster.split_warmup = 0;
                 bad_clusters.push(k);
             }
         }
@@ -120,8 +825,10 @@ impl<P: NormalConjugatePrior> GlobalWorker<P> for GlobalState<P> {
 
         for (k, cluster) in self.clusters.iter().enumerate() {
             if cluster.n_points() > 0
-                || (options.outlier.is_some() && k == 0)
-                || (options.outlier.is_some() && k == 1 && GlobalWorker::n_clusters(self) == 2)
+                || options.outlier_index() == Some(k)
+                || (options.outlier_index().is_some()
+                    && k == options.cluster_offset()
+                    && GlobalWorker::n_clusters(self) == options.cluster_offset() + 1)
             {
                 new_clusters.push(cluster.clone());
             } else {
@@ -135,12 +842,20 @@ impl<P: NormalConjugatePrior> GlobalWorker<P> for GlobalState<P> {
     fn check_and_split<R: Rng>(&mut self, options: &ModelOptions<P>, rng: &mut R) -> Vec<(usize, usize)> {
         let mut decisions = vec![false; GlobalWorker::n_clusters(self)];
         for (k, cluster) in self.clusters.iter().enumerate() {
-            if k == 0 && options.outlier.is_some() {
+            if options.outlier_index() == Some(k) {
                 continue;
             }
 
-            if cluster.splittable && cluster.n_points() > 1 {
-                decisions[k] = SplitMerge::should_split(cluster, options.alpha, rng);
+            // Keep refining the split proposal until it is warmed up
+            if cluster.splittable && cluster.split_warmup > options.split_warmup_iters && cluster.n_points() > 1 {
+                let proposal = SplitMerge::propose_split(cluster, options.alpha, options.split_merge_temperature, rng);
+                decisions[k] = matches!(proposal, Some((_, true)));
+                self.acceptance.splits_proposed += 1;
+                if let (Some(events), Some((log_ratio, accepted))) = (&mut self.split_merge_events, proposal) {
+                    events.push(SplitMergeEvent {
+                        iter: 0, kind: SplitMergeKind::Split, clusters: vec![k], log_ratio, accepted,
+                    });
+                }
             }
         }
 
@@ -160,14 +875,16 @@ impl<P: NormalConjugatePrior> GlobalWorker<P> for GlobalState<P> {
 
             split_idx.push((k, new_idx));
         }
+        self.acceptance.splits_accepted += split_idx.len();
 
         split_idx
     }
 
     fn check_and_merge<R: Rng>(&mut self, options: &ModelOptions<P>, rng: &mut R) -> Vec<(usize, usize)> {
+        let divergence = options.merge_threshold.map(|_| cluster_pairwise_divergence(self));
         let mut decisions = Vec::new();
         for ki in 0..GlobalWorker::n_clusters(self) {
-            if ki == 0 && options.outlier.is_some() {
+            if options.outlier_index() == Some(ki) {
                 continue;
             }
 
@@ -178,10 +895,25 @@ impl<P: NormalConjugatePrior> GlobalWorker<P> for GlobalState<P> {
                     || cluster_i.n_points() == 0 || cluster_j.n_points() == 0 {
                     continue;
                 }
+                if let (Some(divergence), Some(threshold)) = (&divergence, options.merge_threshold) {
+                    if divergence[(ki, kj)] > threshold {
+                        continue;
+                    }
+                }
 
-                if !SplitMerge::should_merge(&cluster_i.prim, &cluster_j.prim, options.alpha, rng) {
+                self.acceptance.merges_proposed += 1;
+                let (log_ratio, accepted) = SplitMerge::propose_merge(
+                    &cluster_i.prim, &cluster_j.prim, options.alpha, options.split_merge_temperature, rng,
+                );
+                if let Some(events) = &mut self.split_merge_events {
+                    events.push(SplitMergeEvent {
+                        iter: 0, kind: SplitMergeKind::Merge, clusters: vec![ki, kj], log_ratio, accepted,
+                    });
+                }
+                if !accepted {
                     continue;
                 }
+                self.acceptance.merges_accepted += 1;
 
                 let cluster = SuperClusterParams::from_merge_params(
                     cluster_i.prim.clone(), cluster_j.prim.clone(),
@@ -196,6 +928,85 @@ impl<P: NormalConjugatePrior> GlobalWorker<P> for GlobalState<P> {
 
         decisions
     }
+
+    fn collect_small_clusters(&self, options: &ModelOptions<P>) -> Vec<(usize, usize)> {
+        let first = options.cluster_offset();
+        let min_size = options.min_cluster_size.max(1);
+        let mut targets: Vec<usize> = (first..self.clusters.len())
+            .filter(|&k| self.clusters[k].n_points() >= min_size)
+            .collect();
+        if targets.is_empty() {
+            // Keep the largest cluster if all of them are too small
+            match (first..self.clusters.len()).max_by_key(|&k| self.clusters[k].n_points()) {
+                Some(k) if self.clusters[k].n_points() > 0 => targets.push(k),
+                _ => return Vec::new(),
+            }
+        }
+
+        (first..self.clusters.len())
+            .filter(|&k| self.clusters[k].n_points() > 0 && !targets.contains(&k))
+            .map(|k| {
+                let mu = self.clusters[k].prim.dist.mu();
+                let nearest = targets.iter().cloned()
+                    .min_by(|&a, &b| {
+                        let da = (self.clusters[a].prim.dist.mu() - mu).norm_squared();
+                        let db = (self.clusters[b].prim.dist.mu() - mu).norm_squared();
+                        da.total_cmp(&db)
+                    })
+                    .unwrap();
+                (k, nearest)
+            })
+            .collect()
+    }
+}
+
+/// Symmetric Kullback-Leibler divergence `KL(p || q) + KL(q || p)` between the (primary) gaussians of every
+/// pair of clusters. Pairs involving a cluster with a singular covariance get an infinite divergence.
+///
+/// # Example
+/// ```
+/// use nalgebra::{DMatrix, DVector};
+/// use rand::SeedableRng;
+/// use rand::rngs::StdRng;
+/// use mixturs::{ModelOptions, NIW};
+/// use mixturs::state::{cluster_pairwise_divergence, GlobalState};
+///
+/// let mut options = ModelOptions::<NIW>::default(1);
+/// options.outlier = None;
+/// let means = DMatrix::from_row_slice(2, 1, &[0.0, 10.0]);
+/// let covs = [DMatrix::identity(1, 1), DMatrix::identity(1, 1)];
+/// let global = GlobalState::from_sklearn_params(&means, &covs, &[0.5, 0.5], &options, &mut StdRng::seed_from_u64(42)).unwrap();
+///
+/// let divergence = cluster_pairwise_divergence(&global);
+/// assert_eq!(divergence[(0, 0)], 0.0);
+/// assert!((divergence[(0, 1)] - 100.0).abs() < 1e-9);
+/// ```
+pub fn cluster_pairwise_divergence<P: NormalConjugatePrior>(global: &GlobalState<P>) -> DMatrix<f64> {
+    let n_clusters = global.clusters.len();
+    let inverses: Vec<_> = global.clusters.iter()
+        .map(|cluster| cluster.prim.dist.cov().clone().cholesky().map(|chol| chol.inverse()))
+        .collect();
+
+    let mut divergence = DMatrix::zeros(n_clusters, n_clusters);
+    for i in 0..n_clusters {
+        for j in i + 1..n_clusters {
+            let (dist_i, dist_j) = (&global.clusters[i].prim.dist, &global.clusters[j].prim.dist);
+            let value = match (&inverses[i], &inverses[j]) {
+                (Some(inv_i), Some(inv_j)) => {
+                    // The log determinants cancel in the symmetric divergence
+                    let diff = dist_i.mu() - dist_j.mu();
+                    let trace = (inv_j * dist_i.cov()).trace() + (inv_i * dist_j.cov()).trace();
+                    let mahalanobis = (diff.transpose() * (inv_i + inv_j) * &diff)[0];
+                    0.5 * (trace + mahalanobis) - diff.len() as f64
+                }
+                _ => f64::INFINITY,
+            };
+            divergence[(i, j)] = value;
+            divergence[(j, i)] = value;
+        }
+    }
+
+    divergence
 }
 
 impl<P: NormalConjugatePrior> ThinParams for GlobalState<P> {
@@ -218,6 +1029,16 @@ impl<P: NormalConjugatePrior> ThinParams for GlobalState<P> {
     fn cluster_aux_weights(&self, cluster_id: usize) -> &[f64; 2] {
         &self.clusters[cluster_id].weights
     }
+
+    fn cluster_ln_pdf(&self, cluster_id: usize, data: DMatrix<f64>) -> DVector<f64> {
+        let cluster = &self.clusters[cluster_id].prim;
+        P::predictive_ln_pdf(&cluster.post, &cluster.dist, data)
+    }
+
+    fn cluster_aux_ln_pdf(&self, cluster_id: usize, aux_id: usize, data: DMatrix<f64>) -> DVector<f64> {
+        let cluster = &self.clusters[cluster_id].aux[aux_id];
+        P::predictive_ln_pdf(&cluster.post, &cluster.dist, data)
+    }
 }
 
 #[cfg(test)]
@@ -225,9 +1046,20 @@ mod tests {
     use std::fs::File;
     use bincode::deserialize_from;
     use nalgebra::{DMatrix, RowDVector};
+    use rand::prelude::StdRng;
+    use rand::{Rng, SeedableRng};
+    use statrs::distribution::MultivariateNormal;
+    use crate::params::thin::{MixtureParams, SuperMixtureParams};
     use crate::{AIC, FitOptions, Model, ModelOptions, MonitoringCallback, NIW, NMI};
+    use crate::error::Error;
     use crate::callback::EvalData;
+    use crate::metrics::normalized_mutual_info_score;
     use crate::plotting::PlotCallback;
+    use crate::state::{cluster_pairwise_divergence, GlobalState, GlobalWorker, LocalState, LocalWorker};
+    use crate::stats::{FromData, NIWStats};
+    use crate::stats::tests::test_almost_mat;
+    use crate::testing::make_blobs;
+    use crate::utils::Iterutils;
 
     #[test]
     fn test_global() {
@@ -259,6 +1091,592 @@ mod tests {
             x.clone_owned(),
             &fit_options,
             Some(callback),
+        ).unwrap();
+        assert_eq!(model.params().dim(), dim);
+    }
+
+    #[test]
+    fn test_try_from_init() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let data = DMatrix::from_fn(2, 50, |_, _| rng.gen_range(0.0..1.0));
+        let stats = NIWStats::from_data(&data);
+
+        let mut options = ModelOptions::<NIW>::default(2);
+        options.outlier = None;
+
+        assert_eq!(
+            GlobalState::try_from_init(&stats, 0, &options, &mut rng).unwrap_err(),
+            Error::EmptyCluster
+        );
+        assert_eq!(
+            GlobalState::try_from_init(&NIWStats::from_data(&DMatrix::<f64>::zeros(2, 0)), 1, &options, &mut rng).unwrap_err(),
+            Error::EmptyData
+        );
+        assert_eq!(
+            GlobalState::try_from_init(&stats, 1, &ModelOptions::<NIW>::default(3), &mut rng).unwrap_err(),
+            Error::DimensionMismatch { expected: 3, got: 2 }
+        );
+
+        let global = GlobalState::try_from_init(&stats, 2, &options, &mut rng).unwrap();
+        assert_eq!(global.clusters.len(), 2);
+    }
+
+    #[test]
+    fn test_from_sklearn_params() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut options = ModelOptions::<NIW>::default(2);
+        options.outlier = None;
+
+        let means = DMatrix::from_row_slice(2, 2, &[
+            0.0, 0.0,
+            5.0, 5.0,
+        ]);
+        let covariances = vec![
+            DMatrix::from_row_slice(2, 2, &[1.0, 0.2, 0.2, 1.0]),
+            DMatrix::from_row_slice(2, 2, &[0.5, 0.0, 0.0, 0.5]),
+        ];
+        let weights = [0.3, 0.7];
+
+        let global = GlobalState::from_sklearn_params(&means, &covariances, &weights, &options, &mut rng).unwrap();
+        assert_eq!(global.weights, vec![0.3, 0.7]);
+
+        let data = DMatrix::from_column_slice(2, 3, &[
+            0.1, -0.2,
+            4.8, 5.1,
+            1.0, 0.5,
+        ]);
+        let (_, labels) = SuperMixtureParams(&global).predict(data);
+        assert_eq!(labels, RowDVector::from_row_slice(&[0, 1, 0]));
+
+        let not_pd = vec![covariances[0].clone(), DMatrix::from_row_slice(2, 2, &[1.0, 2.0, 2.0, 1.0])];
+        assert_eq!(
+            GlobalState::from_sklearn_params(&means, &not_pd, &weights, &options, &mut rng).unwrap_err(),
+            Error::NotPositiveDefinite { component: 1 }
+        );
+        assert_eq!(
+            GlobalState::from_sklearn_params(&means, &covariances, &[1.0], &options, &mut rng).unwrap_err(),
+            Error::ComponentCountMismatch { expected: 2, got: 1 }
+        );
+        assert_eq!(
+            GlobalState::from_sklearn_params(&means, &covariances, &[0.0, 0.0], &options, &mut rng).unwrap_err(),
+            Error::DegenerateWeights
         );
+        assert_eq!(
+            GlobalState::<NIW>::from_sklearn_params(&means, &covariances, &weights, &ModelOptions::default(3), &mut rng).unwrap_err(),
+            Error::DimensionMismatch { expected: 3, got: 2 }
+        );
+    }
+
+    #[test]
+    fn test_cluster_log_dets() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut options = ModelOptions::<NIW>::default(2);
+        options.outlier = None;
+
+        let cov = DMatrix::from_row_slice(2, 2, &[2.0, 0.5, 0.5, 1.0]);
+        let global = GlobalState::from_sklearn_params(
+            &DMatrix::zeros(2, 2),
+            &[cov.clone(), DMatrix::identity(2, 2)],
+            &[0.5, 0.5],
+            &options,
+            &mut rng,
+        ).unwrap();
+
+        let log_dets = global.cluster_log_dets();
+        statrs::assert_almost_eq!(log_dets[0], cov.determinant().ln(), 1e-10);
+        statrs::assert_almost_eq!(log_dets[1], 0.0, 1e-10);
+    }
+
+    #[test]
+    fn test_export_import_cluster_stats() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut options = ModelOptions::<NIW>::default(2);
+        options.outlier = None;
+
+        let centers = DMatrix::from_column_slice(2, 2, &[0.0, 0.0, 10.0, 10.0]);
+        let (x, y) = make_blobs(100, &centers, 1.0, 42);
+        let partition_stats = |range: std::ops::Range<usize>| {
+            let local = LocalState::<NIW>::new(
+                x.columns_range(range.clone()).clone_owned(),
+                y.columns_range(range.clone()).clone_owned(),
+                RowDVector::zeros(range.len()),
+            );
+            local.collect_cluster_stats(2).into_iter().map(|stats| stats.prim).collect::<Vec<_>>()
+        };
+
+        // The right partition only holds points of the second blob
+        let left = GlobalState::import_cluster_stats(partition_stats(0..150), &options, &mut rng);
+        let right = GlobalState::import_cluster_stats(partition_stats(150..200), &options, &mut rng);
+        assert_eq!(right.export_cluster_stats()[0].n_points, 0);
+
+        let combined: Vec<_> = left.export_cluster_stats().into_iter()
+            .zip(right.export_cluster_stats())
+            .map(|(l, r)| l + &r)
+            .collect();
+        let merged = GlobalState::import_cluster_stats(combined, &options, &mut rng);
+        let full = GlobalState::import_cluster_stats(partition_stats(0..200), &options, &mut rng);
+
+        for (m, f) in merged.clusters.iter().zip(&full.clusters) {
+            assert_eq!(m.prim.stats.n_points, f.prim.stats.n_points);
+            test_almost_mat(&m.prim.post.mu, &f.prim.post.mu, 1e-8);
+            test_almost_mat(&m.prim.post.psi, &f.prim.post.psi, 1e-8);
+        }
+    }
+
+    #[test]
+    fn test_weight_pseudo_counts() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut options = ModelOptions::<NIW>::default(2);
+        options.outlier = None;
+        options.weight_pseudo_counts = Some(vec![200.0, 0.0]);
+
+        let centers = DMatrix::from_column_slice(2, 2, &[0.0, 0.0, 10.0, 10.0]);
+        let (x, y) = make_blobs(100, &centers, 1.0, 42);
+        let local = LocalState::<NIW>::new(x, y, RowDVector::zeros(200));
+        let stats = local.collect_cluster_stats(2).into_iter().map(|stats| stats.prim).collect();
+        let mut global = GlobalState::import_cluster_stats(stats, &options, &mut rng);
+
+        // Both clusters hold 100 points, the pseudo-counts favor the first one
+        let n_samples = 100;
+        let mut mean_weights = [0.0; 2];
+        for _ in 0..n_samples {
+            global.update_sample_clusters(&options, &mut rng);
+            for (mean, w) in mean_weights.iter_mut().zip(&global.weights) {
+                *mean += w / n_samples as f64;
+            }
+        }
+
+        assert!(mean_weights[0] > mean_weights[1]);
+        assert!((mean_weights[0] - 0.75).abs() < 0.05, "mean weight {}", mean_weights[0]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_cluster_modes() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut options = ModelOptions::<NIW>::default(2);
+        options.outlier = None;
+
+        let means = DMatrix::from_row_slice(2, 2, &[1.0, 2.0, -3.0, 4.0]);
+        let global = GlobalState::from_sklearn_params(
+            &means,
+            &[DMatrix::identity(2, 2), DMatrix::identity(2, 2)],
+            &[0.5, 0.5],
+            &options,
+            &mut rng,
+        ).unwrap();
+
+        let modes = global.cluster_modes();
+        assert_eq!(modes.len(), 2);
+        for (k, mode) in modes.iter().enumerate() {
+            test_almost_mat(mode, &means.row(k).transpose(), 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_split_warmup() {
+        let mut options = ModelOptions::<NIW>::default(2);
+        options.outlier = None;
+        options.burnout_period = 2;
+
+        // A single bimodal cluster whose auxiliary clusters halve each mode until they separate the two
+        // modes after `refine_iters` iterations
+        let centers = DMatrix::from_column_slice(2, 2, &[-3.0, 0.0, 3.0, 0.0]);
+        let (x, y) = make_blobs(100, &centers, 1.0, 42);
+        let unrefined = LocalState::<NIW>::new(x.clone(), RowDVector::zeros(200), RowDVector::from_fn(200, |_, i| i % 2));
+        let refined = LocalState::<NIW>::new(x, RowDVector::zeros(200), y);
+        let refine_iters = 3;
+
+        // Returns the iteration the cluster is split at and the (log ratio, accepted) of every proposal
+        let run = |split_warmup_iters: usize| {
+            let mut rng = StdRng::seed_from_u64(42);
+            let mut options = options.clone();
+            options.split_warmup_iters = split_warmup_iters;
+            let mut global = GlobalState::import_cluster_stats(
+                vec![unrefined.collect_data_stats()], &options, &mut rng,
+            );
+            global.split_merge_events = Some(Vec::new());
+            for i in 0..20 {
+                let local = if i < refine_iters { &unrefined } else { &refined };
+                global.update_clusters_post(local.collect_cluster_stats(1));
+                global.update_sample_clusters(&options, &mut rng);
+                if !global.check_and_split(&options, &mut rng).is_empty() {
+                    let proposals: Vec<_> = global.split_merge_events.unwrap().iter()
+                        .map(|e| (e.log_ratio, e.accepted))
+                        .collect();
+                    return (i, proposals);
+                }
+            }
+            panic!("cluster was not split with {} warmup iterations", split_warmup_iters);
+        };
+
+        // Without warmup the converged but unrefined subclusters are proposed and rejected first
+        let (split_iter, proposals) = run(0);
+        assert_eq!(split_iter, refine_iters + options.burnout_period);
+        assert_eq!(proposals.len(), 2);
+        assert!(proposals[0].0 < 0.0 && !proposals[0].1);
+        assert!(proposals[1].1);
+
+        // With warmup the proposal waits for the refined subclusters and is accepted on its first evaluation
+        let (split_iter, proposals) = run(refine_iters);
+        assert_eq!(split_iter, refine_iters + options.burnout_period + refine_iters);
+        assert_eq!(proposals.len(), 1);
+        assert!(proposals[0].0 > 0.0 && proposals[0].1);
+    }
+
+    #[test]
+    fn test_predict() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut options = ModelOptions::<NIW>::default(1);
+        options.outlier = None;
+
+        let means = DMatrix::from_row_slice(2, 1, &[-2.0, 2.0]);
+        let global = GlobalState::from_sklearn_params(
+            &means,
+            &[DMatrix::identity(1, 1), DMatrix::identity(1, 1)],
+            &[0.3, 0.7],
+            &options,
+            &mut rng,
+        ).unwrap();
+
+        // The last point lies so far away that its density underflows for every cluster
+        let data = DMatrix::from_row_slice(1, 4, &[-2.5, 0.1, 3.0, -1e200]);
+        let labels = global.predict(&data, &options);
+        assert_eq!(labels.as_slice(), &[0, 1, 1, 1]);
+
+        let probs = global.predict_proba(&data, &options);
+        assert_eq!(probs.shape(), (4, 2));
+        for (row, &label) in probs.row_iter().zip(labels.iter()) {
+            statrs::assert_almost_eq!(row.sum(), 1.0, 1e-12);
+            assert_eq!(row.transpose().argmax().0, label);
+        }
+        assert_eq!(probs[(3, 1)], 1.0);
+    }
+
+    #[test]
+    fn test_partial_fit() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let centers = DMatrix::from_column_slice(2, 2, &[0.0, 0.0, 10.0, 0.0]);
+        let (x, _) = make_blobs(200, &centers, 1.0, 42);
+
+        let mut model_options = ModelOptions::<NIW>::default(2);
+        model_options.outlier = None;
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 100;
+        let mut model = Model::from_options(model_options.clone());
+        model.fit(x, &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+        let mut global = model.params().clone();
+        assert_eq!(global.clusters.len(), 2);
+        let original: Vec<_> = global.clusters.iter().map(|c| (c.n_points(), c.prim.dist.mu().clone())).collect();
+
+        // The second batch comes from a new mode
+        let (batch, _) = make_blobs(200, &DMatrix::from_column_slice(2, 1, &[20.0, 20.0]), 1.0, 7);
+        let labels = global.partial_fit(&batch, &model_options, 100, &mut rng).unwrap();
+
+        assert_eq!(global.clusters.len(), 3);
+        for (cluster, (n_points, mu)) in global.clusters.iter().zip(&original) {
+            assert!(cluster.n_points() >= *n_points);
+            assert!((cluster.prim.dist.mu() - mu).norm() < 1.0);
+        }
+        assert!(labels.iter().all(|&l| l == 2));
+    }
+
+    #[test]
+    fn test_partial_fit_min_cluster_size() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let centers = DMatrix::from_column_slice(2, 2, &[0.0, 0.0, 10.0, 0.0]);
+        let (x, _) = make_blobs(200, &centers, 1.0, 42);
+
+        let mut model_options = ModelOptions::<NIW>::default(2);
+        model_options.outlier = None;
+        model_options.min_cluster_size = 10;
+        let mut model = Model::from_options(model_options.clone());
+        model.fit(x.clone(), &FitOptions::default(), None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+        let mut global = model.params().clone();
+
+        // A tiny isolated group of new points is absorbed by the nearest cluster
+        let batch = DMatrix::from_fn(2, 5, |r, c| if r == 0 { 60.0 + c as f64 * 0.01 } else { 0.0 });
+        let labels = global.partial_fit(&batch, &model_options, 50, &mut rng).unwrap();
+
+        let sizes = global.cluster_sizes();
+        assert!(sizes.iter().all(|&n| n >= 10), "sizes {:?}", sizes);
+        assert_eq!(sizes.iter().sum::<usize>(), 205);
+        assert!(labels.iter().all(|&l| l < global.clusters.len()));
+    }
+
+    #[test]
+    fn test_outlier_min_density() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut options = ModelOptions::<NIW>::default(1);
+        options.outlier_min_density = Some(0.01);
+
+        let means = DMatrix::from_row_slice(1, 1, &[0.0]);
+        let mut global = GlobalState::from_sklearn_params(
+            &means, &[DMatrix::identity(1, 1)], &[1.0], &options, &mut rng,
+        ).unwrap();
+        // A broad outlier cluster that never wins on its own density near the other cluster
+        global.clusters[0].prim.dist = MultivariateNormal::new(vec![0.0], vec![1e6]).unwrap();
+
+        // N(0, 1) has a density of ~0.054 at 2 and ~0.0044 at 3
+        let data = DMatrix::from_row_slice(1, 3, &[0.0, 2.0, 3.0]);
+        assert_eq!(global.low_density_points(&data, &options), vec![false, false, true]);
+        assert_eq!(global.predict(&data, &options).as_slice(), &[1, 1, 0]);
+        assert_eq!(global.predict_proba(&data, &options)[(2, 0)], 1.0);
+
+        options.outlier_min_density = None;
+        assert_eq!(global.predict(&data, &options).as_slice(), &[1, 1, 1]);
+    }
+
+    #[test]
+    fn test_merge_clusters() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut options = ModelOptions::<NIW>::default(2);
+        options.outlier = None;
+
+        // Two clusters describing the same blob
+        let (x, y) = make_blobs(50, &DMatrix::from_column_slice(2, 2, &[0.0, 0.0, 0.0, 0.0]), 1.0, 42);
+        let mut global = GlobalState::from_sklearn_params(
+            &DMatrix::zeros(2, 2),
+            &[DMatrix::identity(2, 2), DMatrix::identity(2, 2)],
+            &[0.5, 0.5],
+            &options,
+            &mut rng,
+        ).unwrap();
+        let mut local = LocalState::<NIW>::new(x, y, RowDVector::zeros(100));
+        global.update_clusters_post(local.collect_cluster_stats(2));
+        let counts: Vec<_> = global.clusters.iter().map(|c| c.n_points()).collect();
+
+        assert!(global.merge_clusters(0, 0, Some(&mut local), &options, &mut rng).is_err());
+        assert!(global.merge_clusters(0, 2, Some(&mut local), &options, &mut rng).is_err());
+
+        global.merge_clusters(0, 1, Some(&mut local), &options, &mut rng).unwrap();
+        assert_eq!(global.clusters.len(), 1);
+        assert_eq!(global.clusters[0].n_points(), counts[0] + counts[1]);
+        statrs::assert_almost_eq!(global.weights[0], 1.0, 1e-12);
+        assert!(local.labels.iter().all(|&l| l == 0));
+        assert_eq!(local.labels_aux.iter().filter(|&&l| l == 1).count(), counts[1]);
+    }
+
+    #[test]
+    fn test_merge_clusters_outlier() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let options = ModelOptions::<NIW>::default(2);
+
+        let mut global = GlobalState::from_sklearn_params(
+            &DMatrix::zeros(2, 2),
+            &[DMatrix::identity(2, 2), DMatrix::identity(2, 2)],
+            &[0.5, 0.5],
+            &options,
+            &mut rng,
+        ).unwrap();
+        assert_eq!(global.clusters.len(), 3);
+
+        assert!(matches!(
+            global.merge_clusters::<u16, f32, _>(0, 1, None, &options, &mut rng),
+            Err(Error::InvalidData(_))
+        ));
+        assert!(global.merge_clusters::<u16, f32, _>(1, 0, None, &options, &mut rng).is_err());
+        assert_eq!(global.clusters.len(), 3);
+
+        global.merge_clusters::<u16, f32, _>(1, 2, None, &options, &mut rng).unwrap();
+        assert_eq!(global.clusters.len(), 2);
+    }
+
+    #[test]
+    fn test_outlier_scores() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut options = ModelOptions::<NIW>::default(2);
+        options.outlier_min_density = Some(1e-6);
+
+        let means = DMatrix::from_row_slice(1, 2, &[0.0, 0.0]);
+        let mut global = GlobalState::from_sklearn_params(
+            &means, &[DMatrix::identity(2, 2)], &[1.0], &options, &mut rng,
+        ).unwrap();
+        global.clusters[0].prim.dist = MultivariateNormal::new(vec![0.0, 0.0], vec![1e6, 0.0, 0.0, 1e6]).unwrap();
+        global.clusters[1].prim.dist = MultivariateNormal::new(vec![0.0, 0.0], vec![4.0, 0.0, 0.0, 1.0]).unwrap();
+
+        // Distances are scaled by the standard deviation along each axis
+        let data = DMatrix::from_column_slice(2, 3, &[4.0, 0.0, 0.0, 1.0, 100.0, 100.0]);
+        let result = global.outlier_scores(&data, &options);
+        statrs::assert_almost_eq!(result.scores[0], 2.0, 1e-12);
+        statrs::assert_almost_eq!(result.scores[1], 1.0, 1e-12);
+        assert_eq!(result.scores[2], f64::INFINITY);
+        assert!(result.singular_clusters.is_empty());
+
+        // A numerically singular covariance falls back to the euclidean distance
+        global.clusters[1].prim.dist = MultivariateNormal::new(vec![0.0, 0.0], vec![1.0, 0.0, 0.0, 1e-20]).unwrap();
+        let result = global.outlier_scores(&DMatrix::from_column_slice(2, 1, &[3.0, 0.0]), &options);
+        assert_eq!(result.singular_clusters, vec![1]);
+        statrs::assert_almost_eq!(result.scores[0], 3.0, 1e-12);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_roundtrip() {
+        let centers = DMatrix::from_column_slice(2, 3, &[0.0, 0.0, 6.0, 0.0, 0.0, 6.0]);
+        let (x, _) = make_blobs(100, &centers, 1.0, 42);
+
+        let model_options = ModelOptions::<NIW>::default(2);
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 20;
+        let mut model = Model::from_options(model_options.clone());
+        model.fit(x.clone(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+        let global = model.params().clone();
+
+        let bytes = bincode::serialize(&global).unwrap();
+        let restored: GlobalState<NIW> = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(restored.weights, global.weights);
+        assert_eq!(restored.predict(&x, &model_options), global.predict(&x, &model_options));
+
+        let bytes = bincode::serialize(&model_options).unwrap();
+        let restored_options: ModelOptions<NIW> = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(restored_options, model_options);
+    }
+
+    #[test]
+    fn test_cluster_pairwise_divergence() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut options = ModelOptions::<NIW>::default(2);
+        options.outlier = None;
+
+        let means = DMatrix::from_row_slice(3, 2, &[0.0, 0.0, 20.0, 20.0, 0.5, 0.0]);
+        let covs = [DMatrix::identity(2, 2), DMatrix::identity(2, 2), DMatrix::identity(2, 2) * 2.0];
+        let mut global = GlobalState::from_sklearn_params(&means, &covs, &[1.0, 1.0, 1.0], &options, &mut rng).unwrap();
+
+        let divergence = cluster_pairwise_divergence(&global);
+        assert_eq!(divergence.diagonal().amax(), 0.0);
+        test_almost_mat(&divergence, &divergence.transpose(), 1e-12);
+        assert!(divergence[(0, 1)] > 100.0, "divergence {}", divergence[(0, 1)]);
+        assert!(divergence[(0, 2)] < 1.0, "divergence {}", divergence[(0, 2)]);
+
+        // Only the overlapping pair is proposed for merging
+        let (x, _) = make_blobs(50, &means.transpose(), 1.0, 42);
+        for cluster in &mut global.clusters {
+            cluster.prim.stats = NIWStats::from_data(&x);
+            cluster.splittable = true;
+        }
+        options.merge_threshold = Some(1.0);
+        global.check_and_merge(&options, &mut rng);
+        assert_eq!(global.acceptance.merges_proposed, 1);
+    }
+
+    #[test]
+    fn test_summarize() {
+        let centers = DMatrix::from_column_slice(2, 3, &[0.0, 0.0, 10.0, 0.0, 0.0, 10.0]);
+        let (x, _) = make_blobs(80, &centers, 1.0, 42);
+        let mut rng = StdRng::seed_from_u64(42);
+
+        let mut local = LocalState::<NIW>::from_data(x);
+        local.init(1, &mut rng);
+        let mut model = Model::from_options(ModelOptions::<NIW>::default(2));
+        model.fit_worker(&mut local, &FitOptions::default(), None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+
+        let global = model.params();
+        let counts = local.labels.iter().cloned().bincounts(global.clusters.len());
+        assert_eq!(global.cluster_sizes(), counts);
+
+        let summary = global.summarize();
+        assert_eq!(summary.iter().map(|cluster| cluster.n_points).sum::<usize>(), 240);
+        for (k, cluster) in summary.iter().enumerate() {
+            assert_eq!(cluster.id, k);
+            assert_eq!(cluster.n_points, counts[k]);
+            assert_eq!(cluster.weight, global.weights[k]);
+            assert_eq!(&cluster.mean, global.clusters[k].prim.dist.mu());
+            assert_eq!(&cluster.covariance, global.clusters[k].prim.dist.cov());
+        }
+    }
+
+    #[test]
+    fn test_outlier_index() {
+        let centers = DMatrix::from_column_slice(2, 3, &[0.0, 0.0, 10.0, 0.0, 0.0, 10.0]);
+        let (x, y) = make_blobs(80, &centers, 1.0, 42);
+
+        for with_outliers in [true, false] {
+            let mut options = ModelOptions::<NIW>::default(2);
+            if !with_outliers {
+                options.outlier = None;
+            }
+            let offset = options.cluster_offset();
+            assert_eq!(options.outlier_index(), if with_outliers { Some(0) } else { None });
+            assert_eq!(offset, with_outliers as usize);
+
+            let mut rng = StdRng::seed_from_u64(42);
+            let global = GlobalState::from_init(&NIWStats::from_data(&x), 3, &options, &mut rng);
+            assert_eq!(global.clusters.len(), offset + 3);
+            assert_eq!(global.weights.len(), offset + 3);
+
+            let mut model = Model::from_options(options.clone());
+            model.fit(x.clone(), &FitOptions::default(), None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+            let global = model.params();
+
+            // The outlier cluster keeps its own prior, the blobs are found among the regular clusters
+            assert_eq!(global.outlier_index(&options), options.outlier_index());
+            if let Some(k) = global.outlier_index(&options) {
+                assert_eq!(Some(&global.clusters[k].prim.prior), options.outlier.as_ref().map(|o| &o.dist));
+            }
+
+            let labels = global.predict(&x, &options);
+            assert!(labels.iter().filter(|&&k| global.outlier_index(&options) == Some(k)).count() < 5);
+            let regular: Vec<usize> = labels.iter().map(|&k| k.saturating_sub(offset)).collect();
+            assert!(normalized_mutual_info_score(y.as_slice(), &regular) > 0.95);
+        }
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let centers = DMatrix::from_column_slice(2, 3, &[0.0, 0.0, 20.0, 0.0, 0.0, 20.0]);
+        let (x, y) = make_blobs(60, &centers, 1.0, 42);
+        // Give the blobs distinct sizes
+        let sizes = [60, 40, 20];
+        let mut seen = [0; 3];
+        let keep: Vec<usize> = (0..x.ncols()).filter(|&i| {
+            seen[y[i]] += 1;
+            seen[y[i]] <= sizes[y[i]]
+        }).collect();
+        let x = x.select_columns(&keep);
+
+        let fit = |seed| {
+            let mut model = Model::from_options(ModelOptions::<NIW>::default(2));
+            let fit_options = FitOptions { seed, keep_local: true, ..FitOptions::default() };
+            model.fit(x.clone(), &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+            let (mut global, local, options, _) = model.into_parts();
+            let mut local = local.unwrap();
+            global.canonicalize(&mut local, &options);
+
+            let sizes = global.cluster_sizes();
+            assert!(sizes[options.cluster_offset()..].windows(2).all(|w| w[0] >= w[1]));
+            assert_eq!(global.weights.len(), global.clusters.len());
+            (local.labels, options.outlier_index())
+        };
+
+        let ((a, outlier), (b, _)) = (fit(1), fit(2));
+        for (la, lb) in a.iter().zip(b.iter()) {
+            if Some(*la) != outlier && Some(*lb) != outlier {
+                assert_eq!(la, lb);
+            }
+        }
+    }
+
+    #[test]
+    fn test_mean_assignment_entropy() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut options = ModelOptions::<NIW>::default(2);
+        options.outlier = None;
+        let covs = vec![DMatrix::identity(2, 2); 2];
+
+        let entropy = |distance: f64, rng: &mut StdRng| {
+            let means = DMatrix::from_row_slice(2, 2, &[0.0, 0.0, distance, 0.0]);
+            let global = GlobalState::from_sklearn_params(&means, &covs, &[0.5, 0.5], &options, rng).unwrap();
+            let (x, _) = make_blobs(100, &means.transpose(), 1.0, 42);
+            global.mean_assignment_entropy(&x, &options)
+        };
+
+        let separated = entropy(50.0, &mut rng);
+        let overlapping = entropy(1.0, &mut rng);
+        assert!(separated.abs() < 1e-6, "separated entropy {}", separated);
+        assert!(overlapping > 0.3, "overlapping entropy {}", overlapping);
+        assert!(overlapping <= 2f64.ln() + 1e-12);
+
+        let global = GlobalState::from_sklearn_params(&DMatrix::zeros(2, 2), &covs, &[0.5, 0.5], &options, &mut rng).unwrap();
+        assert_eq!(global.mean_assignment_entropy(&DMatrix::zeros(2, 0), &options), 0.0);
+    }
+}
@@ -1,25 +1,64 @@
 use itertools::izip;
 use std::marker::PhantomData;
-use nalgebra::{DMatrix, RowDVector};
-use rand::Rng;
-use crate::stats::{FromData, NormalConjugatePrior};
-use crate::utils::{col_scatter, group_sort};
+use nalgebra::{DMatrix, DVector, RowDVector};
+use rand::rngs::SmallRng;
+use rayon::prelude::*;
+use rand::{Rng, SeedableRng};
+use rand::distributions::Distribution;
+use statrs::distribution::Normal;
+use crate::stats::{FromData, NormalConjugatePrior, SufficientStats};
+use crate::utils::{bincount_vec, col_normalize_log_weights, col_scatter, DataScalar, f64_column_blocks, group_sort, kmeans_plusplus, Label, nearest_centers, unique_columns, validate_data};
 use crate::utils::Iterutils;
 use crate::params::clusters::{SuperClusterStats};
-use crate::params::thin::{AuxMixtureParams, hard_assignment, MixtureParams, soft_assignment, SuperMixtureParams, ThinParams};
+use crate::error::Error;
+use crate::params::constraints::PairwiseConstraints;
+use crate::params::options::{AuxInit, FitOptions, InitStrategy};
+use crate::params::thin::{AuxMixtureParams, hard_assignment, hard_assignment_random_ties, hard_assignment_weighted_ties, MixtureParams, soft_assignment, SuperMixtureParams, ThinParams};
 use crate::state::LocalWorker;
 
 
+/// Local state storing the data as `f32`, halving the memory used by the data matrix.
+pub type LocalStateF32<P, L = usize> = LocalState<P, L, f32>;
+
 /// Local state performs all computations on the locally on the data.
+/// Labels are stored as `L`, which can be narrowed (e.g. to `u32`) to save memory on large datasets.
+/// Likewise the data is stored as `T` (e.g. `f32`, see [`LocalStateF32`]), while all computations are done in `f64`.
 #[derive(Debug, Clone, PartialEq)]
-pub struct LocalState<P: NormalConjugatePrior> {
-    pub data: DMatrix<f64>,
-    pub labels: RowDVector<usize>,
-    pub labels_aux: RowDVector<usize>,
+pub struct LocalState<P: NormalConjugatePrior, L: Label = usize, T: DataScalar = f64> {
+    pub data: DMatrix<T>,
+    pub labels: RowDVector<L>,
+    pub labels_aux: RowDVector<L>,
+    /// Optional pairwise constraints respected when sampling primary labels.
+    pub constraints: Option<PairwiseConstraints>,
+    /// Whether hard assignment breaks exact ties randomly instead of picking the lowest cluster index.
+    pub random_tie_break: bool,
+    /// Estimate the statistics of (sub)cluster blocks larger than this from a random subsample of this size.
+    pub max_stats_points: Option<usize>,
+    /// Collect the statistics of (sub)cluster blocks in parallel chunks of this many points.
+    pub par_stats_chunk: Option<usize>,
+    /// Compute the primary label log-likelihoods in parallel chunks of this many points.
+    pub par_ll_chunk: Option<usize>,
+    /// Whether the statistics of the clusters are collected in parallel, one task per cluster.
+    pub par_cluster_stats: bool,
+    /// Whether exact-duplicate points are initialized to the same (sub)cluster.
+    pub dedup_init: bool,
+    /// How the auxiliary labels of new and reset clusters are initialized.
+    pub aux_init: AuxInit,
+    /// How the primary labels are initialized.
+    pub init_strategy: InitStrategy,
+    /// Optional multiplicity of each point, e.g. the number of identical observations an aggregated row
+    /// stands for. Sufficient statistics are accumulated weighted by it, labels are still sampled per point.
+    pub weights: Option<DVector<f64>>,
+    /// Optional mask of points (n_samples) whose primary label is fixed, e.g. known ground-truth labels.
+    /// Frozen points keep their label when sampling and stay in their cluster when it is split, but still
+    /// contribute to the sufficient statistics. Merged, dissolved or removed clusters relabel them as a whole.
+    /// Frozen labels take precedence over the [constraints](PairwiseConstraints::apply).
+    /// Set it with [`LocalState::set_frozen`] to check its length.
+    pub frozen: Option<Vec<bool>>,
     _phantoms: PhantomData<fn() -> P>,
 }
 
-impl<P: NormalConjugatePrior> LocalState<P> {
+impl<P: NormalConjugatePrior, L: Label, T: DataScalar> LocalState<P, L, T> {
     /// Create a new local state.
     ///
     /// # Arguments
@@ -28,11 +67,11 @@ impl<P: NormalConjugatePrior> LocalState<P> {
     /// * `labels`: Primary cluster labels.
     /// * `labels_aux`: Auxiliary cluster labels.
     pub fn new(
-        data: DMatrix<f64>,
-        labels: RowDVector<usize>,
-        labels_aux: RowDVector<usize>,
+        data: DMatrix<T>,
+        labels: RowDVector<L>,
+        labels_aux: RowDVector<L>,
     ) -> Self {
-        Self { data, labels, labels_aux, _phantoms: PhantomData }
+        Self { data, labels, labels_aux, constraints: None, random_tie_break: false, max_stats_points: None, par_stats_chunk: None, par_ll_chunk: None, par_cluster_stats: false, dedup_init: false, aux_init: AuxInit::Uniform, init_strategy: InitStrategy::Random, weights: None, frozen: None, _phantoms: PhantomData }
     }
 
     /// Create a new local state from data
@@ -41,18 +80,151 @@ impl<P: NormalConjugatePrior> LocalState<P> {
     /// # Arguments
     ///
     /// * `data`: Data points matrix (n_dims, n_samples).
-    pub fn from_data(data: DMatrix<f64>) -> Self {
-        let labels = RowDVector::zeros(data.ncols());
-        let labels_aux = RowDVector::zeros(data.ncols());
+    pub fn from_data(data: DMatrix<T>) -> Self {
+        let labels = RowDVector::from_element(data.ncols(), L::default());
+        let labels_aux = RowDVector::from_element(data.ncols(), L::default());
         Self::new(data, labels, labels_aux)
     }
 
+    /// Create a new local state warm-started from the given primary labels, e.g. the output of a cheaper
+    /// clustering algorithm. Only the auxiliary labels are randomized. The per-fit settings of the local state
+    /// are taken from `options`.
+    ///
+    /// Labels index the clusters of the global state directly, so if outlier removal is enabled label 0
+    /// is the outlier cluster. Set [`FitOptions::init_clusters`] accordingly before fitting with
+    /// [`crate::Model::fit_worker`].
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: Data points matrix (n_dims, n_samples).
+    /// * `labels`: Primary cluster labels (n_samples).
+    /// * `options`: The fit options
+    /// * `rng`: The random number generator
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::EmptyData`] or [`Error::NonFiniteData`] if the data is empty or contains NaN/infinite
+    /// values, and [`Error::InvalidData`] if the number of labels does not match the number of points, the labels
+    /// are not contiguous starting at 0, there are more clusters than [`FitOptions::max_clusters`] or the frozen
+    /// mask or the weights do not match the points.
+    ///
+    /// # Example
+    /// ```
+    /// use nalgebra::{DMatrix, DVector};
+    /// use rand::SeedableRng;
+    /// use rand::rngs::SmallRng;
+    /// use mixturs::{FitOptions, NIW};
+    /// use mixturs::state::LocalState;
+    ///
+    /// let data = DMatrix::new_random(2, 4);
+    /// let labels = DVector::from_vec(vec![0, 1, 1, 0]);
+    /// let mut rng = SmallRng::seed_from_u64(42);
+    ///
+    /// let local = LocalState::<NIW>::from_labels(data.clone(), labels, &FitOptions::default(), &mut rng).unwrap();
+    /// assert_eq!(local.labels[2], 1);
+    ///
+    /// let gap = DVector::from_vec(vec![0, 2, 2, 0]);
+    /// assert!(LocalState::<NIW>::from_labels(data, gap, &FitOptions::default(), &mut rng).is_err());
+    /// ```
+    pub fn from_labels<R: Rng>(
+        data: DMatrix<T>,
+        labels: DVector<usize>,
+        options: &FitOptions,
+        rng: &mut R,
+    ) -> Result<Self, Error> {
+        validate_data(&data, None)?;
+        if labels.len() != data.ncols() {
+            return Err(Error::InvalidData(format!(
+                "expected {} labels, got {}", data.ncols(), labels.len()
+            )));
+        }
+
+        let counts = bincount_vec(&labels);
+        let n_clusters = counts.len();
+        if let Some(missing) = counts.iter().position(|&c| c == 0) {
+            return Err(Error::InvalidData(format!(
+                "labels must be contiguous starting at 0, label {} is missing", missing
+            )));
+        }
+        if n_clusters > options.max_clusters {
+            return Err(Error::InvalidData(format!(
+                "labels describe {} clusters, at most {} are allowed", n_clusters, options.max_clusters
+            )));
+        }
+
+        let labels = RowDVector::from_iterator(labels.len(), labels.iter().map(|&l| L::from_usize(l)));
+        let labels_aux = RowDVector::from_fn(labels.len(), |_, _| L::from_usize(rng.gen_range(0..2)));
+        let mut local = Self::new(data, labels, labels_aux);
+        local.apply_fit_options(options);
+        local.set_frozen(options.frozen.clone())?;
+        validate_weights(options.weights.as_deref(), local.n_points())?;
+        local.weights = options.weights.clone().map(DVector::from_vec);
+
+        if local.aux_init == AuxInit::RandomHyperplane {
+            local.apply_hyperplane_aux_labels(&(0..n_clusters).collect::<Vec<_>>(), rng);
+        }
+        Ok(local)
+    }
+
+    /// Takes the per-fit settings of the local state (constraints, tie breaking, statistics collection and
+    /// initialization) from `options`. The [weights](FitOptions::weights) and [frozen](FitOptions::frozen)
+    /// mask are per point, so they are left to the caller to split along with the data.
+    pub fn apply_fit_options(&mut self, options: &FitOptions) {
+        self.constraints = options.constraints.clone();
+        self.random_tie_break = options.random_tie_break;
+        self.max_stats_points = options.max_stats_points;
+        self.par_stats_chunk = options.par_stats_chunk;
+        self.par_ll_chunk = options.par_ll_chunk;
+        self.par_cluster_stats = options.par_cluster_stats;
+        self.dedup_init = options.dedup_init;
+        self.aux_init = options.aux_init;
+        self.init_strategy = options.init_strategy;
+    }
+
+    /// Create a new local state from data where each point carries a multiplicity
+    /// all labels are set to 0.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: Data points matrix (n_dims, n_samples).
+    /// * `weights`: Multiplicity of each point (n_samples).
+    ///
+    /// # Example
+    /// ```
+    /// use nalgebra::{DMatrix, DVector};
+    /// use mixturs::NIW;
+    /// use mixturs::state::{LocalState, LocalWorker};
+    ///
+    /// let data = DMatrix::from_row_slice(1, 2, &[1.0, 2.0]);
+    /// let local = LocalState::<NIW>::from_weighted_data(data, DVector::from_vec(vec![3.0, 1.0]));
+    /// assert_eq!(local.collect_data_stats().n_points, 4);
+    /// ```
+    pub fn from_weighted_data(data: DMatrix<T>, weights: DVector<f64>) -> Self {
+        assert_eq!(data.ncols(), weights.len(), "Number of weights must match the number of points");
+        let mut local = Self::from_data(data);
+        local.weights = Some(weights);
+        local
+    }
+
+    /// Sets the mask of [frozen](LocalState::frozen) points.
+    ///
+    /// # Errors
+    /// [`Error::InvalidData`] if the length of the mask does not match the number of points.
+    pub fn set_frozen(&mut self, frozen: Option<Vec<bool>>) -> Result<(), Error> {
+        validate_frozen(frozen.as_deref(), self.n_points())?;
+        self.frozen = frozen;
+        Ok(())
+    }
+
     /// Number of points in the data.
     pub fn n_points(&self) -> usize {
         self.data.ncols()
     }
 
     /// Samples primary labels given cluster parameters.
+    /// If constraints are set, must-linked points are sampled jointly and cannot-linked points are
+    /// kept apart. [Frozen](LocalState::frozen) points keep their current label. Exact ties of a hard assignment go to the cluster with the largest weight, then to the
+    /// lowest index (unless ties are broken randomly).
     ///
     /// # Arguments
     ///
@@ -65,16 +237,45 @@ impl<P: NormalConjugatePrior> LocalState<P> {
         params: &impl ThinParams,
         hard_assign: bool,
         rng: &mut impl Rng,
-    ) {
+    ) -> Result<(), Error> {
         // Calculate log likelihood for each point
-        let ll = SuperMixtureParams(params).log_likelihood(self.data.clone_owned());
+        let ll = self.prim_log_likelihood(params);
+        let constrained_ll = self.constraints.as_ref().map(|_| ll.clone());
+        let previous = self.frozen.as_ref().map(|_| self.labels.clone());
 
         // Sample labels
-        if hard_assign {
-            hard_assignment(&ll, self.labels.as_mut_slice());
-        } else {
-            soft_assignment(ll, self.labels.as_mut_slice(), rng);
+        let weights = params.cluster_weights();
+        assign_labels(ll, self.labels.as_mut_slice(), Some(weights), hard_assign, self.random_tie_break, rng)?;
+
+        // Restore the labels of frozen points
+        if let (Some(frozen), Some(previous)) = (&self.frozen, previous) {
+            for (i, &is_frozen) in frozen.iter().enumerate() {
+                if is_frozen {
+                    self.labels[i] = previous[i];
+                }
+            }
+        }
+
+        // Resample constrained points around the frozen ones
+        if let (Some(constraints), Some(ll)) = (&self.constraints, constrained_ll) {
+            constraints.apply(&ll, weights, self.labels.as_mut_slice(), self.frozen.as_deref(), hard_assign, rng)?;
+        }
+        Ok(())
+    }
+
+    /// Log-likelihood of each point under each primary cluster, weighted by the cluster weights.
+    /// (n_clusters, n_points)
+    fn prim_log_likelihood(&self, params: &impl ThinParams) -> DMatrix<f64> {
+        if let Some(chunk_size) = self.par_ll_chunk {
+            return SuperMixtureParams(params).par_log_likelihood(&self.data, chunk_size);
+        }
+
+        let mut ll = DMatrix::zeros(params.n_clusters(), self.n_points());
+        for (start, block) in f64_column_blocks(&self.data) {
+            let block_ll = SuperMixtureParams(params).log_likelihood(block.into_owned());
+            ll.columns_mut(start, block_ll.ncols()).copy_from(&block_ll);
         }
+        ll
     }
 
     /// Samples auxiliary labels given cluster parameters and their assignment to primary clusters.
@@ -90,7 +291,7 @@ impl<P: NormalConjugatePrior> LocalState<P> {
         params: &impl ThinParams,
         hard_assign: bool,
         rng: &mut impl Rng,
-    ) {
+    ) -> Result<(), Error> {
         // Split data points into contiguous blocks (indexes only for now)
         let (indices, offsets) = self.sorted_indices(params.n_clusters());
 
@@ -99,18 +300,68 @@ impl<P: NormalConjugatePrior> LocalState<P> {
         let mut ll = DMatrix::zeros(2, self.n_points());
         for prim in 0..params.n_clusters() {
             let indices = &indices[offsets[prim * 2]..offsets[(prim + 1) * 2]];
-            let block = self.data.select_columns(indices);
+            let block = T::select_f64(&self.data, indices);
 
             let block_ll = AuxMixtureParams(params, prim).log_likelihood(block);
             col_scatter(&mut ll, indices, &block_ll);
         }
 
         // Sample labels
-        if hard_assign {
-            hard_assignment(&ll, self.labels_aux.as_mut_slice());
-        } else {
-            soft_assignment(ll, self.labels_aux.as_mut_slice(), rng);
+        assign_labels(ll, self.labels_aux.as_mut_slice(), None, hard_assign, self.random_tie_break, rng)
+    }
+
+    /// Samples auxiliary labels of only the given points, leaving the auxiliary labels of all other points
+    /// untouched. Useful after a targeted proposal that only affects a few clusters.
+    ///
+    /// # Arguments
+    ///
+    /// * `params`: The cluster parameters
+    /// * `indices`: Indices of the points to resample
+    /// * `hard_assign`: Whether to perform hard assignment or soft assignment (i.e. sampling strategy)
+    /// * `rng`: The random number generator
+    ///
+    pub fn apply_sample_labels_aux_subset(
+        &mut self,
+        params: &impl ThinParams,
+        indices: &[usize],
+        hard_assign: bool,
+        rng: &mut impl Rng,
+    ) -> Result<(), Error> {
+        // Group the positions of the points (in `indices`) by their primary label
+        let mut groups = vec![Vec::new(); params.n_clusters()];
+        for (j, &i) in indices.iter().enumerate() {
+            groups[self.labels[i].as_usize()].push(j);
+        }
+
+        let mut ll = DMatrix::zeros(2, indices.len());
+        for (prim, positions) in groups.iter().enumerate().filter(|(_, positions)| !positions.is_empty()) {
+            let points: Vec<usize> = positions.iter().map(|&j| indices[j]).collect();
+            let block_ll = AuxMixtureParams(params, prim).log_likelihood(T::select_f64(&self.data, &points));
+            col_scatter(&mut ll, positions, &block_ll);
+        }
+
+        // Sample labels
+        let mut labels = vec![L::default(); indices.len()];
+        assign_labels(ll, &mut labels, None, hard_assign, self.random_tie_break, rng)?;
+        for (&i, label) in indices.iter().zip(labels) {
+            self.labels_aux[i] = label;
         }
+        Ok(())
+    }
+
+    /// Samples auxiliary labels of only the points of the given primary cluster.
+    /// See [`LocalState::apply_sample_labels_aux_subset`].
+    pub fn apply_sample_labels_aux_cluster(
+        &mut self,
+        params: &impl ThinParams,
+        cluster_id: usize,
+        hard_assign: bool,
+        rng: &mut impl Rng,
+    ) -> Result<(), Error> {
+        let indices: Vec<usize> = (0..self.n_points())
+            .filter(|&i| self.labels[i].as_usize() == cluster_id)
+            .collect();
+        self.apply_sample_labels_aux_subset(params, &indices, hard_assign, rng)
     }
 
     /// Group sorts the data points by primary and auxiliary labels in O(n)
@@ -130,51 +381,233 @@ impl<P: NormalConjugatePrior> LocalState<P> {
         // Split data points into contiguous blocks
         let n_blocks = n_clusters * 2;
         let counts = izip!(&self.labels, &self.labels_aux)
-            .map(|(&prim, &aux)| prim * 2 + aux)
+            .map(|(&prim, &aux)| prim.as_usize() * 2 + aux.as_usize())
             .bincounts(n_blocks);
         group_sort(
             &counts,
             izip!(&self.labels, &self.labels_aux),
-            |(&prim, &aux)| prim * 2 + aux,
+            |(&prim, &aux)| prim.as_usize() * 2 + aux.as_usize(),
         )
     }
+
+    /// Splits the points of each of the given clusters by a random hyperplane: the points are projected onto
+    /// a random gaussian direction and assigned to the second auxiliary cluster if their projection lies
+    /// above the median.
+    ///
+    /// # Arguments
+    ///
+    /// * `cluster_ids`: The primary clusters to split
+    /// * `rng`: The random number generator
+    pub fn apply_hyperplane_aux_labels(&mut self, cluster_ids: &[usize], rng: &mut impl Rng) {
+        let normal = Normal::new(0.0, 1.0).unwrap();
+        for &k in cluster_ids {
+            let indices: Vec<usize> = (0..self.n_points())
+                .filter(|&i| self.labels[i].as_usize() == k)
+                .collect();
+            if indices.is_empty() {
+                continue;
+            }
+
+            let direction = DVector::from_fn(self.data.nrows(), |_, _| normal.sample(rng));
+            let projections: Vec<f64> = indices.iter()
+                .map(|&i| self.data.column(i).iter().zip(direction.iter()).map(|(x, d)| x.to_f64() * d).sum::<f64>())
+                .collect();
+            let mut sorted = projections.clone();
+            let (_, &mut median, _) = sorted.select_nth_unstable_by((indices.len() - 1) / 2, f64::total_cmp);
+
+            for (&i, &projection) in indices.iter().zip(&projections) {
+                self.labels_aux[i] = L::from_usize((projection > median) as usize);
+            }
+        }
+    }
+
+    /// Assigns each point to the cluster of its nearest center and initializes the auxiliary labels
+    /// as configured by `aux_init`.
+    ///
+    /// # Arguments
+    ///
+    /// * `centers`: The initial cluster centers (n_dim, n_clusters)
+    /// * `rng`: The random number generator
+    pub fn init_from_centers(&mut self, centers: &DMatrix<f64>, rng: &mut impl Rng) {
+        for (start, block) in f64_column_blocks(&self.data) {
+            for (i, k) in nearest_centers(&block, centers).into_iter().enumerate() {
+                self.labels[start + i] = L::from_usize(k);
+            }
+        }
+        self.labels_aux.apply(|v| *v = L::from_usize(rng.gen_range(0..2)));
+
+        if self.aux_init == AuxInit::RandomHyperplane {
+            self.apply_hyperplane_aux_labels(&(0..centers.ncols()).collect::<Vec<_>>(), rng);
+        }
+    }
+
+    /// Collects the statistics of a block of points. Blocks larger than `max_stats_points` are estimated
+    /// from a random subsample, scaled up to the block size (its total weight for weighted points). The
+    /// subsample is seeded by the block id and size so the estimate is reproducible. Otherwise, if
+    /// `par_stats_chunk` is set, the statistics are collected in parallel chunks.
+    fn block_stats(&self, block_id: usize, indices: &[usize]) -> P::SuffStats {
+        match (self.max_stats_points, self.par_stats_chunk) {
+            (Some(max_points), _) if indices.len() > max_points => {
+                let mut rng = SmallRng::seed_from_u64(((block_id as u64) << 32) ^ indices.len() as u64);
+                let sample: Vec<usize> = rand::seq::index::sample(&mut rng, indices.len(), max_points)
+                    .into_iter()
+                    .map(|i| indices[i])
+                    .collect();
+
+                let n_points = match &self.weights {
+                    Some(weights) => indices.iter().map(|&i| weights[i]).sum::<f64>().round() as usize,
+                    None => indices.len(),
+                };
+                self.indices_stats(&sample).rescaled(n_points)
+            }
+            (_, Some(chunk_size)) => indices.par_chunks(chunk_size.max(1))
+                .map(|chunk| self.indices_stats(chunk))
+                .reduce_with(|acc, stats| acc + &stats)
+                .unwrap_or_else(|| self.indices_stats(&[])),
+            _ => self.indices_stats(indices),
+        }
+    }
+
+    /// Collects the statistics of the given points, weighted by their [weights](LocalState::weights) if set.
+    fn indices_stats(&self, indices: &[usize]) -> P::SuffStats {
+        match &self.weights {
+            Some(weights) => {
+                let weights: Vec<f64> = indices.iter().map(|&i| weights[i]).collect();
+                P::SuffStats::from_weighted_data(&T::select_f64(&self.data, indices), &weights)
+            }
+            None => T::stats_from_indices(&self.data, indices),
+        }
+    }
+}
+
+/// Checks that a mask of [frozen](LocalState::frozen) points covers exactly `n_points` points.
+pub(crate) fn validate_frozen(frozen: Option<&[bool]>, n_points: usize) -> Result<(), Error> {
+    match frozen {
+        Some(frozen) if frozen.len() != n_points => Err(Error::InvalidData(format!(
+            "frozen mask has {} entries for {} points", frozen.len(), n_points
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Checks that the [weights](LocalState::weights) of the points cover exactly `n_points` points and are
+/// finite and non-negative.
+pub(crate) fn validate_weights(weights: Option<&[f64]>, n_points: usize) -> Result<(), Error> {
+    match weights {
+        Some(weights) if weights.len() != n_points => Err(Error::InvalidData(format!(
+            "weights have {} entries for {} points", weights.len(), n_points
+        ))),
+        Some(weights) if weights.iter().any(|w| !w.is_finite() || *w < 0.0) => {
+            Err(Error::InvalidData("weights must be finite and non-negative".to_string()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Assigns labels from the log-likelihoods. Exact ties of a hard assignment go to the cluster with the largest
+/// weight if `weights` are given (see [`hard_assignment_weighted_ties`]), otherwise to the lowest index.
+fn assign_labels<L: Label>(
+    ll: DMatrix<f64>,
+    labels: &mut [L],
+    weights: Option<&[f64]>,
+    hard_assign: bool,
+    random_tie_break: bool,
+    rng: &mut impl Rng,
+) -> Result<(), Error> {
+    match (hard_assign, random_tie_break) {
+        (true, false) => match weights {
+            Some(weights) => hard_assignment_weighted_ties(&ll, weights, labels),
+            None => hard_assignment(&ll, labels),
+        },
+        (true, true) => hard_assignment_random_ties(&ll, labels, rng),
+        (false, _) => soft_assignment(ll, labels, rng)?,
+    }
+    Ok(())
 }
 
-impl<P: NormalConjugatePrior> LocalWorker<P> for LocalState<P> {
+impl<P: NormalConjugatePrior, L: Label, T: DataScalar> LocalWorker<P> for LocalState<P, L, T> {
     fn init<R: Rng + Clone + Send + Sync>(&mut self, n_clusters: usize, rng: &mut R) {
-        self.labels.apply(|v| *v = rng.gen_range(0..n_clusters));
-        self.labels_aux.apply(|v| *v = rng.gen_range(0..2));
+        if let InitStrategy::KMeansPlusPlus { n } = self.init_strategy {
+            let centers = kmeans_plusplus(&self.data, n_clusters, n, rng);
+            self.init_from_centers(&centers, rng);
+            return;
+        }
+
+        if self.dedup_init {
+            // Draw the labels once per unique point
+            let (unique, indices) = unique_columns(&self.data);
+            let labels: Vec<L> = (0..unique.len()).map(|_| L::from_usize(rng.gen_range(0..n_clusters))).collect();
+            let labels_aux: Vec<L> = (0..unique.len()).map(|_| L::from_usize(rng.gen_range(0..2))).collect();
+            for (i, &u) in indices.iter().enumerate() {
+                self.labels[i] = labels[u];
+                self.labels_aux[i] = labels_aux[u];
+            }
+        } else {
+            self.labels.apply(|v| *v = L::from_usize(rng.gen_range(0..n_clusters)));
+            self.labels_aux.apply(|v| *v = L::from_usize(rng.gen_range(0..2)));
+        }
+
+        if self.aux_init == AuxInit::RandomHyperplane {
+            self.apply_hyperplane_aux_labels(&(0..n_clusters).collect::<Vec<_>>(), rng);
+        }
     }
 
     fn n_points(&self) -> usize {
         self.data.ncols()
     }
 
+    fn collect_labels(&self) -> RowDVector<usize> {
+        self.labels.map(|l| l.as_usize())
+    }
+
     fn collect_data_stats(&self) -> P::SuffStats {
-        P::SuffStats::from_data(&self.data)
+        f64_column_blocks(&self.data)
+            .map(|(start, block)| match &self.weights {
+                Some(weights) => P::SuffStats::from_weighted_data(&*block, &weights.as_slice()[start..start + block.ncols()]),
+                None => P::SuffStats::from_data(&*block),
+            })
+            .reduce(|acc, stats| acc + &stats)
+            .unwrap_or_else(|| P::SuffStats::from_data(&DMatrix::<f64>::zeros(self.data.nrows(), 0)))
     }
 
     fn collect_cluster_stats(&self, n_clusters: usize) -> Vec<SuperClusterStats<P>> {
-        // Split data points into contiguous blocks (indexes only for now)
+        // Split data points into contiguous blocks (indexes only for now). A single pass over the labels
+        // buckets all (sub)clusters at once, instead of scanning the labels once per cluster.
         let (indices, offsets) = self.sorted_indices(n_clusters);
 
-        // Gather data from sorted indices
-        let data = self.data.select_columns(&indices);
-
-        let mut stats = Vec::with_capacity(n_clusters);
-        for i in (0..n_clusters * 2).step_by(2) {
-            stats.push(SuperClusterStats {
-                prim: P::SuffStats::from_data(
-                    &data.columns_range(offsets[i]..offsets[i + 2])
-                ),
-                aux: [
-                    P::SuffStats::from_data(&data.columns_range(offsets[i]..offsets[i + 1])),
-                    P::SuffStats::from_data(&data.columns_range(offsets[i + 1]..offsets[i + 2])),
-                ],
+        // The statistics of each subcluster are accumulated in place, the primary ones are their sum
+        let cluster_stats = |prim: usize| {
+            let aux = [0, 1].map(|aux| {
+                let block = prim * 2 + aux;
+                self.block_stats(block, &indices[offsets[block]..offsets[block + 1]])
             });
+            SuperClusterStats::new(aux[0].clone() + &aux[1], aux)
+        };
+        if self.par_cluster_stats {
+            (0..n_clusters).into_par_iter().map(cluster_stats).collect()
+        } else {
+            (0..n_clusters).map(cluster_stats).collect()
         }
+    }
 
-        stats
+    fn log_likelihood(&self, params: &impl ThinParams) -> f64 {
+        let (indices, offsets) = self.sorted_indices(params.n_clusters());
+        (0..params.n_clusters())
+            .filter(|&k| offsets[k * 2 + 2] > offsets[k * 2])
+            .map(|k| {
+                let data = T::select_f64(&self.data, &indices[offsets[k * 2]..offsets[k * 2 + 2]]);
+                params.cluster_ln_pdf(k, data).sum()
+            })
+            .sum()
+    }
+
+    fn collect_responsibilities(&self, params: &impl ThinParams) -> DMatrix<f64> {
+        let mut probs = col_normalize_log_weights(self.prim_log_likelihood(params));
+        for mut col in probs.column_iter_mut() {
+            let sum = col.sum();
+            col /= sum;
+        }
+        probs.transpose()
     }
 
     fn apply_label_sampling<R: Rng + Clone + Send + Sync>(
@@ -182,9 +615,9 @@ impl<P: NormalConjugatePrior> LocalWorker<P> for LocalState<P> {
         params: &impl ThinParams,
         hard_assign: bool,
         rng: &mut R,
-    ) {
-        self.apply_sample_labels_prim(params, hard_assign, rng);
-        self.apply_sample_labels_aux(params, false, rng);
+    ) -> Result<(), Error> {
+        self.apply_sample_labels_prim(params, hard_assign, rng)?;
+        self.apply_sample_labels_aux(params, false, rng)
     }
 
     fn apply_cluster_reset<R: Rng + Clone + Send + Sync>(
@@ -192,10 +625,15 @@ impl<P: NormalConjugatePrior> LocalWorker<P> for LocalState<P> {
         cluster_ids: &[usize],
         rng: &mut R,
     ) {
+        if self.aux_init == AuxInit::RandomHyperplane {
+            self.apply_hyperplane_aux_labels(cluster_ids, rng);
+            return;
+        }
+
         for &k in cluster_ids {
             for i in 0..self.n_points() {
-                if self.labels[i] == k {
-                    self.labels_aux[i] = rng.gen_range(0..2);
+                if self.labels[i].as_usize() == k {
+                    self.labels_aux[i] = L::from_usize(rng.gen_range(0..2));
                 }
             }
         }
@@ -207,8 +645,8 @@ impl<P: NormalConjugatePrior> LocalWorker<P> for LocalState<P> {
     ) {
         for (removed, &k) in cluster_ids.iter().enumerate() {
             for l in self.labels.iter_mut() {
-                if *l > k - removed {
-                    *l -= 1;
+                if l.as_usize() > k - removed {
+                    *l = L::from_usize(l.as_usize() - 1);
                 }
             }
         }
@@ -220,13 +658,19 @@ impl<P: NormalConjugatePrior> LocalWorker<P> for LocalState<P> {
         rng: &mut R,
     ) {
         for &(kl, kr) in split_decisions {
-            for (label, label_aux) in izip!(self.labels.iter_mut(), self.labels_aux.iter_mut()) {
-                if *label == kl {
-                    *label = if *label_aux == 0 { kl } else { kr };
-                    *label_aux = rng.gen_range(0..2);
+            for (i, (label, label_aux)) in izip!(self.labels.iter_mut(), self.labels_aux.iter_mut()).enumerate() {
+                if label.as_usize() == kl {
+                    let is_frozen = matches!(&self.frozen, Some(frozen) if frozen[i]);
+                    *label = L::from_usize(if label_aux.as_usize() == 0 || is_frozen { kl } else { kr });
+                    *label_aux = L::from_usize(rng.gen_range(0..2));
                 }
             }
         }
+
+        if self.aux_init == AuxInit::RandomHyperplane {
+            let cluster_ids: Vec<usize> = split_decisions.iter().flat_map(|&(kl, kr)| [kl, kr]).collect();
+            self.apply_hyperplane_aux_labels(&cluster_ids, rng);
+        }
     }
 
     fn apply_merge(
@@ -235,15 +679,35 @@ impl<P: NormalConjugatePrior> LocalWorker<P> for LocalState<P> {
     ) {
         for &(kl, kr) in merge_decisions {
             for (label, label_aux) in izip!(self.labels.iter_mut(), self.labels_aux.iter_mut()) {
-                if *label == kl {
-                    *label_aux = 0;
-                } else if *label == kr {
-                    *label = kl;
-                    *label_aux = 2;
+                if label.as_usize() == kl {
+                    *label_aux = L::from_usize(0);
+                } else if label.as_usize() == kr {
+                    *label = L::from_usize(kl);
+                    *label_aux = L::from_usize(2);
                 }
             }
         }
     }
+
+    fn apply_cluster_reassign<R: Rng + Clone + Send + Sync>(
+        &mut self,
+        reassign_decisions: &[(usize, usize)],
+        rng: &mut R,
+    ) {
+        for &(from, to) in reassign_decisions {
+            for (label, label_aux) in izip!(self.labels.iter_mut(), self.labels_aux.iter_mut()) {
+                if label.as_usize() == from {
+                    *label = L::from_usize(to);
+                    *label_aux = L::from_usize(rng.gen_range(0..2));
+                }
+            }
+        }
+
+        if self.aux_init == AuxInit::RandomHyperplane {
+            let cluster_ids: Vec<usize> = reassign_decisions.iter().map(|&(_, to)| to).collect();
+            self.apply_hyperplane_aux_labels(&cluster_ids, rng);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -251,12 +715,144 @@ mod tests {
     use nalgebra::{DMatrix, DVector, RowDVector};
     use rand::prelude::StdRng;
     use rand::{Rng, SeedableRng};
-    use statrs::distribution::MultivariateNormal;
+    use rand::distributions::Distribution;
+    use statrs::distribution::{MultivariateNormal, Normal};
     use crate::params::clusters::SuperClusterStats;
+    use crate::params::constraints::PairwiseConstraints;
+    use crate::params::options::{AuxInit, InitStrategy};
     use crate::params::thin::{OwnedThinParams, ThinParams};
-    use crate::state::{LocalState, LocalWorker};
+    use crate::state::{LocalState, LocalStateF32, LocalWorker};
     use crate::stats::{FromData, NIW, NIWStats};
     use crate::stats::tests::test_almost_mat;
+    use crate::{Error, FitOptions, Model, ModelOptions, MonitoringCallback};
+    use crate::metrics::normalized_mutual_info_score;
+    use crate::state::GlobalState;
+    use crate::testing::make_blobs;
+
+    #[test]
+    fn test_f32_data() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let centers = DMatrix::from_column_slice(2, 3, &[0.0, 0.0, 10.0, 0.0, 0.0, 10.0]);
+        let (x, y) = make_blobs(100, &centers, 1.0, 42);
+
+        let mut local = LocalStateF32::<NIW>::from_data(x.map(|v| v as f32));
+        let mut reference = LocalState::<NIW>::from_data(x);
+        local.labels = y.clone();
+        reference.labels = y.clone();
+        let stats = local.collect_cluster_stats(3);
+        for (stats, expected) in stats.iter().zip(reference.collect_cluster_stats(3)) {
+            test_almost_mat(&stats.prim.mean, &expected.prim.mean, 1e-3);
+        }
+
+        let mut model_options = ModelOptions::<NIW>::default(2);
+        model_options.outlier = None;
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 50;
+        local.init(1, &mut rng);
+        let mut model = Model::from_options(model_options);
+        model.fit_worker(&mut local, &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+
+        let nmi = normalized_mutual_info_score(y.as_slice(), local.labels.as_slice());
+        assert!(nmi > 0.9, "nmi {}", nmi);
+    }
+
+    #[test]
+    fn test_from_labels() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let centers = DMatrix::from_column_slice(2, 3, &[0.0, 0.0, 10.0, 0.0, 0.0, 10.0]);
+        let (x, y) = make_blobs(50, &centers, 1.0, 42);
+        let labels = DVector::from_iterator(y.len(), y.iter().cloned());
+
+        let mut fit_options = FitOptions::default();
+        fit_options.max_clusters = 2;
+        assert!(matches!(
+            LocalState::<NIW>::from_labels(x.clone(), labels.clone(), &fit_options, &mut rng),
+            Err(Error::InvalidData(_))
+        ));
+        assert!(LocalState::<NIW>::from_labels(x.clone(), labels.rows(0, 10).into_owned(), &FitOptions::default(), &mut rng).is_err());
+
+        let mut nan = x.clone();
+        nan[(0, 7)] = f64::NAN;
+        assert_eq!(
+            LocalState::<NIW>::from_labels(nan, labels.clone(), &FitOptions::default(), &mut rng).unwrap_err(),
+            Error::NonFiniteData { feature: 0, point: 7 }
+        );
+        assert_eq!(
+            LocalState::<NIW>::from_labels(DMatrix::zeros(2, 0), DVector::zeros(0), &FitOptions::default(), &mut rng)
+                .unwrap_err(),
+            Error::EmptyData
+        );
+
+        // Warm start from the true labels
+        fit_options.max_clusters = usize::MAX;
+        fit_options.init_clusters = 3;
+        fit_options.iters = 10;
+        let mut local = LocalState::<NIW>::from_labels(x, labels, &fit_options, &mut rng).unwrap();
+        assert_eq!(local.labels, y);
+        assert!(local.labels_aux.iter().all(|&l| l < 2));
+
+        let mut model_options = ModelOptions::<NIW>::default(2);
+        model_options.outlier = None;
+        let mut model = Model::from_options(model_options);
+        model.fit_worker(&mut local, &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+
+        let nmi = normalized_mutual_info_score(y.as_slice(), local.labels.as_slice());
+        assert!(nmi > 0.95, "nmi {}", nmi);
+    }
+
+    #[test]
+    fn test_apply_fit_options() {
+        let fit_options = FitOptions::builder()
+            .constraints(PairwiseConstraints::new(vec![(0, 1)], vec![]))
+            .random_tie_break(true)
+            .dedup_init(true)
+            .aux_init(AuxInit::RandomHyperplane)
+            .init_strategy(InitStrategy::KMeansPlusPlus { n: 2 })
+            .frozen(vec![true; 4])
+            .weights(vec![2.0; 4])
+            .build()
+            .unwrap();
+
+        let mut local = LocalState::<NIW>::from_data(DMatrix::zeros(2, 4));
+        local.apply_fit_options(&fit_options);
+        assert_eq!(local.constraints, fit_options.constraints);
+        assert!(local.random_tie_break && local.dedup_init);
+        assert_eq!(local.aux_init, AuxInit::RandomHyperplane);
+        assert_eq!(local.init_strategy, InitStrategy::KMeansPlusPlus { n: 2 });
+
+        // The per-point options are left to the caller
+        assert_eq!(local.frozen, None);
+        assert_eq!(local.weights, None);
+    }
+
+    #[test]
+    fn test_weighted_collect_stats() {
+        let unique = DMatrix::from_column_slice(2, 3, &[0.5, 1.0, -2.0, 3.0, 4.0, 0.25]);
+        let counts = [2, 3, 1];
+        let mut weighted = LocalState::<NIW>::from_weighted_data(
+            unique.clone(),
+            DVector::from_iterator(3, counts.iter().map(|&c| c as f64)),
+        );
+        weighted.labels = RowDVector::from_row_slice(&[0, 1, 0]);
+        weighted.labels_aux = RowDVector::from_row_slice(&[0, 1, 1]);
+
+        // Replicate every unique point by its count
+        let repeated: Vec<usize> = counts.iter().enumerate().flat_map(|(i, &c)| vec![i; c]).collect();
+        let mut expanded = LocalState::<NIW>::from_data(unique.select_columns(&repeated));
+        expanded.labels = RowDVector::from_iterator(repeated.len(), repeated.iter().map(|&i| weighted.labels[i]));
+        expanded.labels_aux = RowDVector::from_iterator(repeated.len(), repeated.iter().map(|&i| weighted.labels_aux[i]));
+
+        let expected = expanded.collect_cluster_stats(2);
+        for (stats, expected) in weighted.collect_cluster_stats(2).iter().zip(&expected) {
+            for (stats, expected) in [&stats.prim, &stats.aux[0], &stats.aux[1]].into_iter()
+                .zip([&expected.prim, &expected.aux[0], &expected.aux[1]]) {
+                assert_eq!(stats.n_points, expected.n_points);
+                test_almost_mat(&stats.mean, &expected.mean, 1e-10);
+                test_almost_mat(&stats.scatter, &expected.scatter, 1e-10);
+            }
+        }
+        assert_eq!(weighted.collect_data_stats().n_points, 6);
+    }
 
     #[test]
     fn test_collect_stats() {
@@ -275,17 +871,50 @@ mod tests {
             ];
 
             assert_eq!(prim.n_points, prim_og.n_points);
-            test_almost_mat(&prim.mean_sum, &prim_og.mean_sum, 1e-4);
-            test_almost_mat(&prim.cov_sum, &prim_og.cov_sum, 1e-4);
+            test_almost_mat(&prim.mean, &prim_og.mean, 1e-4);
+            test_almost_mat(&prim.scatter, &prim_og.scatter, 1e-4);
 
             for a in 0..2 {
-                test_almost_mat(&aux[a].mean_sum, &aux_og[a].mean_sum, 1e-4);
-                test_almost_mat(&aux[a].cov_sum, &aux_og[a].cov_sum, 1e-4);
+                test_almost_mat(&aux[a].mean, &aux_og[a].mean, 1e-4);
+                test_almost_mat(&aux[a].scatter, &aux_og[a].scatter, 1e-4);
                 assert_eq!(aux[a].n_points, aux_og[a].n_points);
             }
         }
     }
 
+    #[test]
+    fn test_collect_stats_matches_per_cluster_scan() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let n_clusters = 25;
+        let data = DMatrix::from_fn(3, 2000, |_, _| rng.gen_range(-5.0..5.0));
+        let labels = RowDVector::from_fn(2000, |_, _| rng.gen_range(0..n_clusters));
+        let labels_aux = RowDVector::from_fn(2000, |_, _| rng.gen_range(0..2));
+        let local = LocalState::<NIW>::new(data.clone(), labels.clone(), labels_aux.clone());
+
+        let stats = local.collect_cluster_stats(n_clusters);
+        for (k, cluster) in stats.iter().enumerate() {
+            // Reference: filter the points of each (sub)cluster with a full scan of the labels
+            let scan = |aux: Option<usize>| {
+                let indices: Vec<usize> = (0..data.ncols())
+                    .filter(|&i| labels[i] == k && (aux.is_none() || aux == Some(labels_aux[i])))
+                    .collect();
+                NIWStats::from_data(&data.select_columns(&indices))
+            };
+
+            // Statistics are accumulated point by point and summed in block order (aux 0 then aux 1), so only
+            // match up to rounding
+            let prim = scan(None);
+            assert_eq!(cluster.prim.n_points, prim.n_points);
+            test_almost_mat(&cluster.prim.mean, &prim.mean, 1e-9);
+            test_almost_mat(&cluster.prim.scatter, &prim.scatter, 1e-9);
+            for (aux, scanned) in cluster.aux.iter().zip([scan(Some(0)), scan(Some(1))]) {
+                assert_eq!(aux.n_points, scanned.n_points);
+                test_almost_mat(&aux.mean, &scanned.mean, 1e-9);
+                test_almost_mat(&aux.scatter, &scanned.scatter, 1e-9);
+            }
+        }
+    }
+
     #[test]
     fn test_sorted_indices() {
         let mut rng = StdRng::seed_from_u64(42);
@@ -327,7 +956,7 @@ mod tests {
         let labels_aux = RowDVector::zeros(120);
 
         let mut local = LocalState::<NIW>::new(data.clone(), labels, labels_aux);
-        local.apply_sample_labels_prim(&params, true, &mut rng);
+        local.apply_sample_labels_prim(&params, true, &mut rng).unwrap();
 
         for (i, point) in data.column_iter().enumerate() {
             assert_eq!(
@@ -389,7 +1018,7 @@ mod tests {
         let labels_aux = RowDVector::zeros(120);
 
         let mut local = LocalState::<NIW>::new(data.clone(), labels.clone_owned(), labels_aux);
-        local.apply_sample_labels_aux(&params, true, &mut rng);
+        local.apply_sample_labels_aux(&params, true, &mut rng).unwrap();
 
         for (i, point) in data.column_iter().enumerate() {
             let label = labels[i];
@@ -405,4 +1034,336 @@ mod tests {
             );
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_sample_labels_constrained() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let params = OwnedThinParams {
+            clusters: vec![
+                MultivariateNormal::new(
+                    DVector::from_vec(vec![0.0, 0.0]).data.into(),
+                    DMatrix::from_diagonal_element(2, 2, 1.0).data.into(),
+                ).unwrap(),
+                MultivariateNormal::new(
+                    DVector::from_vec(vec![5.0, 5.0]).data.into(),
+                    DMatrix::from_diagonal_element(2, 2, 1.0).data.into(),
+                ).unwrap(),
+            ],
+            cluster_weights: vec![0.5, 0.5],
+            clusters_aux: vec![],
+            cluster_weights_aux: vec![],
+        };
+
+        let data = DMatrix::from_vec(2, 4, vec![
+            0.0, 0.0,
+            0.1, 0.0,
+            5.0, 5.0,
+            0.0, 0.1,
+        ]);
+        let mut local = LocalState::<NIW>::from_data(data);
+        local.constraints = Some(PairwiseConstraints::new(vec![(2, 3)], vec![(0, 1)]));
+
+        for hard_assign in [true, false] {
+            for _ in 0..50 {
+                local.apply_sample_labels_prim(&params, hard_assign, &mut rng).unwrap();
+                assert_ne!(local.labels[0], local.labels[1]);
+                assert_eq!(local.labels[2], local.labels[3]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sample_labels_frozen() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let params = OwnedThinParams {
+            clusters: vec![
+                MultivariateNormal::new(
+                    DVector::from_vec(vec![0.0, 0.0]).data.into(),
+                    DMatrix::from_diagonal_element(2, 2, 1.0).data.into(),
+                ).unwrap(),
+                MultivariateNormal::new(
+                    DVector::from_vec(vec![10.0, 10.0]).data.into(),
+                    DMatrix::from_diagonal_element(2, 2, 1.0).data.into(),
+                ).unwrap(),
+            ],
+            cluster_weights: vec![0.5, 0.5],
+            clusters_aux: vec![],
+            cluster_weights_aux: vec![],
+        };
+
+        // All points lie in cluster 1, the first half is frozen to cluster 0
+        let centers = DMatrix::from_column_slice(2, 1, &[10.0, 10.0]);
+        let (x, _) = make_blobs(100, &centers, 1.0, 42);
+        let mut local = LocalState::<NIW>::from_data(x);
+        assert!(matches!(local.set_frozen(Some(vec![true; 10])), Err(Error::InvalidData(_))));
+        local.set_frozen(Some((0..100).map(|i| i < 50).collect())).unwrap();
+        let mut constrained = local.clone();
+
+        for hard_assign in [true, false] {
+            for _ in 0..20 {
+                local.apply_sample_labels_prim(&params, hard_assign, &mut rng).unwrap();
+                assert!(local.labels.iter().take(50).all(|&l| l == 0));
+                assert!(local.labels.iter().skip(50).all(|&l| l == 1));
+            }
+        }
+
+        // Frozen points still count towards the statistics and stay put when their cluster is split
+        let stats = local.collect_cluster_stats(2);
+        assert_eq!((stats[0].prim.n_points, stats[1].prim.n_points), (50, 50));
+        local.labels_aux.fill(1);
+        local.apply_split(&[(0, 2)], &mut rng);
+        assert!(local.labels.iter().take(50).all(|&l| l == 0));
+
+        // Points must-linked to a frozen point follow its label, cannot-linked ones avoid it
+        constrained.constraints = Some(PairwiseConstraints::new(vec![(0, 60)], vec![(1, 70)]));
+        for hard_assign in [true, false] {
+            constrained.apply_sample_labels_prim(&params, hard_assign, &mut rng).unwrap();
+            assert!(constrained.labels.iter().take(50).all(|&l| l == 0));
+            assert_eq!((constrained.labels[60], constrained.labels[70]), (0, 1));
+        }
+    }
+
+    #[test]
+    fn test_cluster_reassign() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let data = DMatrix::new_random(2, 100);
+        let labels = RowDVector::from_fn(100, |_, i| if i < 90 { 0 } else { 1 });
+        let mut local = LocalState::<NIW>::new(data, labels, RowDVector::zeros(100));
+
+        local.apply_cluster_reassign(&[(1, 0)], &mut rng);
+        assert!(local.labels.iter().all(|&l| l == 0));
+        // Only the moved points get new auxiliary labels
+        assert!(local.labels_aux.columns_range(0..90).iter().all(|&l| l == 0));
+        assert!(local.labels_aux.columns_range(90..100).iter().all(|&l| l < 2));
+        assert!(local.labels_aux.columns_range(90..100).iter().any(|&l| l == 1));
+    }
+
+    #[test]
+    fn test_sample_labels_random_tie_break() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let params = OwnedThinParams {
+            clusters: vec![
+                MultivariateNormal::new(
+                    DVector::from_vec(vec![-1.0, 0.0]).data.into(),
+                    DMatrix::from_diagonal_element(2, 2, 1.0).data.into(),
+                ).unwrap(),
+                MultivariateNormal::new(
+                    DVector::from_vec(vec![1.0, 0.0]).data.into(),
+                    DMatrix::from_diagonal_element(2, 2, 1.0).data.into(),
+                ).unwrap(),
+            ],
+            cluster_weights: vec![0.5, 0.5],
+            clusters_aux: vec![],
+            cluster_weights_aux: vec![],
+        };
+
+        // Points on the symmetry axis are equally likely under both clusters
+        let data = DMatrix::from_fn(2, 200, |i, j| if i == 0 { 0.0 } else { j as f64 / 100.0 });
+        let mut local = LocalState::<NIW>::from_data(data);
+
+        local.apply_sample_labels_prim(&params, true, &mut rng).unwrap();
+        assert!(local.labels.iter().all(|&l| l == 0));
+
+        local.random_tie_break = true;
+        let mut counts = [0usize; 2];
+        for _ in 0..10 {
+            local.apply_sample_labels_prim(&params, true, &mut rng).unwrap();
+            for &l in local.labels.iter() {
+                counts[l] += 1;
+            }
+        }
+        let frac = counts[0] as f64 / 2000.0;
+        assert!((frac - 0.5).abs() < 0.05, "fraction assigned to cluster 0: {}", frac);
+    }
+
+    #[test]
+    fn test_collect_cluster_stats_subsampled() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let normal = Normal::new(0.0, 1.0).unwrap();
+        let z = DMatrix::from_fn(2, 50000, |_, _| normal.sample(&mut rng));
+        let data = DMatrix::from_fn(2, 50000, |i, j| if i == 0 { 2.0 * z[(0, j)] } else { z[(0, j)] + z[(1, j)] });
+
+        let labels = RowDVector::zeros(50000);
+        let labels_aux = RowDVector::from_fn(50000, |_, _| rng.gen_range(0..2));
+        let mut local = LocalState::<NIW>::new(data, labels, labels_aux);
+
+        let full = local.collect_cluster_stats(1).remove(0);
+        local.max_stats_points = Some(5000);
+        let subsampled = local.collect_cluster_stats(1).remove(0);
+
+        let cov = |stats: &NIWStats| &stats.scatter / stats.n_points as f64;
+
+        assert_eq!(subsampled.prim.n_points, full.prim.n_points);
+        assert_eq!(subsampled.aux[0].n_points + subsampled.aux[1].n_points, 50000);
+        test_almost_mat(&cov(&subsampled.prim), &cov(&full.prim), 0.3);
+    }
+
+    #[test]
+    fn test_collect_cluster_stats_par() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let data = DMatrix::from_fn(3, 20000, |_, _| rng.gen_range(-1.0..1.0));
+        let labels = RowDVector::zeros(20000);
+        let labels_aux = RowDVector::from_fn(20000, |_, _| rng.gen_range(0..2));
+        let mut local = LocalState::<NIW>::new(data, labels, labels_aux);
+
+        let serial = local.collect_cluster_stats(1).remove(0);
+        local.par_stats_chunk = Some(1000);
+        let parallel = local.collect_cluster_stats(1).remove(0);
+
+        for (s, p) in [(&serial.prim, &parallel.prim), (&serial.aux[0], &parallel.aux[0]), (&serial.aux[1], &parallel.aux[1])] {
+            assert_eq!(s.n_points, p.n_points);
+            test_almost_mat(&p.mean, &s.mean, 1e-6);
+            test_almost_mat(&p.scatter, &s.scatter, 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_collect_weighted_stats_par_subsampled() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let data = DMatrix::from_fn(3, 20000, |_, _| rng.gen_range(-1.0..1.0));
+        let weights = DVector::from_fn(20000, |_, _| rng.gen_range(1..4) as f64);
+        let mut local = LocalState::<NIW>::from_weighted_data(data, weights.clone());
+        local.labels_aux = RowDVector::from_fn(20000, |_, _| rng.gen_range(0..2));
+        let total = weights.sum() as usize;
+
+        let serial = local.collect_cluster_stats(1).remove(0);
+        local.par_stats_chunk = Some(1000);
+        let parallel = local.collect_cluster_stats(1).remove(0);
+        for (s, p) in [(&serial.prim, &parallel.prim), (&serial.aux[0], &parallel.aux[0]), (&serial.aux[1], &parallel.aux[1])] {
+            assert_eq!(s.n_points, p.n_points);
+            test_almost_mat(&p.mean, &s.mean, 1e-6);
+            test_almost_mat(&p.scatter, &s.scatter, 1e-6);
+        }
+        assert_eq!(parallel.prim.n_points, total);
+
+        // Subsampled blocks are scaled up to their total weight
+        local.par_stats_chunk = None;
+        local.max_stats_points = Some(2000);
+        let subsampled = local.collect_cluster_stats(1).remove(0);
+        assert_eq!(subsampled.prim.n_points, total);
+        assert_eq!(subsampled.aux[0].n_points, serial.aux[0].n_points);
+        let cov = |stats: &NIWStats| &stats.scatter / stats.n_points as f64;
+        test_almost_mat(&cov(&subsampled.prim), &cov(&serial.prim), 0.05);
+    }
+
+    #[test]
+    fn test_collect_cluster_stats_par_clusters() {
+        let centers = DMatrix::from_fn(3, 10, |r, c| if r == c % 3 { c as f64 * 5.0 } else { -(c as f64) });
+        let (data, y) = make_blobs(500, &centers, 1.0, 42);
+        let mut rng = StdRng::seed_from_u64(42);
+        let labels_aux = RowDVector::from_fn(y.len(), |_, _| rng.gen_range(0..2));
+        let mut local = LocalState::<NIW>::new(data, y, labels_aux);
+
+        for par_stats_chunk in [None, Some(300)] {
+            local.par_stats_chunk = par_stats_chunk;
+            local.par_cluster_stats = false;
+            let serial = local.collect_cluster_stats(10);
+            local.par_cluster_stats = true;
+            let parallel = local.collect_cluster_stats(10);
+            for (s, p) in serial.iter().zip(&parallel) {
+                assert_eq!(s.prim, p.prim);
+                assert_eq!(s.aux, p.aux);
+            }
+        }
+    }
+
+    #[test]
+    fn test_init_dedup() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let points = DMatrix::from_fn(2, 5, |_, _| rng.gen_range(-1.0..1.0));
+        let data = DMatrix::from_fn(2, 500, |i, j| points[(i, j % 5)]);
+
+        let mut local = LocalState::<NIW>::from_data(data);
+        local.dedup_init = true;
+        local.init(4, &mut rng);
+
+        for j in 5..500 {
+            assert_eq!(local.labels[j], local.labels[j % 5]);
+            assert_eq!(local.labels_aux[j], local.labels_aux[j % 5]);
+        }
+    }
+
+    #[test]
+    fn test_sample_labels_aux_cluster() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let params = OwnedThinParams {
+            clusters: vec![
+                MultivariateNormal::new(
+                    DVector::from_vec(vec![0.0, 0.0]).data.into(),
+                    DMatrix::from_diagonal_element(2, 2, 1.0).data.into(),
+                ).unwrap(),
+                MultivariateNormal::new(
+                    DVector::from_vec(vec![1.0, 1.0]).data.into(),
+                    DMatrix::from_diagonal_element(2, 2, 1.0).data.into(),
+                ).unwrap(),
+            ],
+            cluster_weights: vec![0.5, 0.5],
+            clusters_aux: vec![
+                [
+                    MultivariateNormal::new(
+                        DVector::from_vec(vec![0.0, 0.0]).data.into(),
+                        DMatrix::from_diagonal_element(2, 2, 1.0).data.into(),
+                    ).unwrap(),
+                    MultivariateNormal::new(
+                        DVector::from_vec(vec![1.0, 1.0]).data.into(),
+                        DMatrix::from_diagonal_element(2, 2, 1.0).data.into(),
+                    ).unwrap(),
+                ],
+                [
+                    MultivariateNormal::new(
+                        DVector::from_vec(vec![0.0, 4.0]).data.into(),
+                        DMatrix::from_diagonal_element(2, 2, 1.0).data.into(),
+                    ).unwrap(),
+                    MultivariateNormal::new(
+                        DVector::from_vec(vec![4.0, 0.0]).data.into(),
+                        DMatrix::from_diagonal_element(2, 2, 1.0).data.into(),
+                    ).unwrap(),
+                ],
+            ],
+            cluster_weights_aux: vec![
+                [0.5, 0.5],
+                [0.5, 0.5],
+            ],
+        };
+
+        let data = DMatrix::from_fn(2, 120, |_, _| rng.gen_range(0.0..1.0));
+        let labels = RowDVector::from_fn(120, |_, _| rng.gen_range(0..2));
+        let labels_aux = RowDVector::from_fn(120, |_, _| rng.gen_range(0..2));
+
+        let mut local = LocalState::<NIW>::new(data.clone(), labels.clone_owned(), labels_aux.clone_owned());
+        local.apply_sample_labels_aux_cluster(&params, 1, true, &mut rng).unwrap();
+
+        for (i, point) in data.column_iter().enumerate() {
+            if labels[i] != 1 {
+                assert_eq!(local.labels_aux[i], labels_aux[i]);
+                continue;
+            }
+
+            assert_eq!(
+                local.labels_aux[i],
+                if (point - params.cluster_aux_dist(1, 0).mu()).norm()
+                    < (point - params.cluster_aux_dist(1, 1).mu()).norm() {
+                    0
+                } else {
+                    1
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn test_hyperplane_aux_init() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let data = DMatrix::from_fn(3, 1000, |_, _| rng.gen_range(-1.0..1.0));
+
+        let mut local = LocalState::<NIW>::from_data(data);
+        local.aux_init = AuxInit::RandomHyperplane;
+        local.init(2, &mut rng);
+
+        for k in 0..2 {
+            let aux: Vec<usize> = (0..1000).filter(|&i| local.labels[i] == k).map(|i| local.labels_aux[i]).collect();
+            let n_right = aux.iter().filter(|&&a| a == 1).count();
+            assert!((n_right as i64 - (aux.len() / 2) as i64).abs() <= 1, "{} of {}", n_right, aux.len());
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use std::cell::RefCell;
+use nalgebra::{DMatrix, RowDVector};
+use rand::Rng;
+use crate::error::Error;
+use crate::params::{ThinParams, SuperClusterStats};
+use crate::state::{LocalState, LocalWorker};
+use crate::stats::{FromData, NormalConjugatePrior};
+use crate::utils::validate_data;
+
+/// A local state over data that does not fit in memory at once, streamed as mini-batches of points.
+/// Only the labels of the points are kept in memory, one [`LocalState`] per batch without its data.
+/// Operations that need the points re-read the batches and run on the shard of each batch in turn,
+/// operations on the labels alone (merges and removals) do not touch the data.
+///
+/// The cluster statistics are collected in the same pass as the labels are sampled, so a sampling
+/// sweep followed by the maximization step reads the data only once.
+///
+/// Reading errors can not be returned by the [`LocalWorker`] methods. A batch that does not match its
+/// shard is skipped and the first such error is kept until [`BatchedState::take_error`].
+pub struct BatchedState<P: NormalConjugatePrior, F> {
+    pub shards: Vec<LocalState<P>>,
+    batches: RefCell<F>,
+    dim: usize,
+    error: RefCell<Option<Error>>,
+    stats_cache: Option<Vec<SuperClusterStats<P>>>,
+}
+
+impl<P, I, F> BatchedState<P, F>
+    where
+        P: NormalConjugatePrior,
+        I: IntoIterator<Item=DMatrix<f64>>,
+        F: FnMut() -> I,
+{
+    /// Creates a new batched state from a single pass over the batches, with all labels set to 0.
+    ///
+    /// # Arguments
+    ///
+    /// * `batches`: Called at the start of every pass over the data to iterate over the batches
+    /// (n_dims, n_points). Every pass must yield the same points.
+    /// * `dim`: The dimension of the data
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error`] if a batch is invalid (see [`validate_data`]) or there are no batches.
+    pub fn from_batches(mut batches: F, dim: usize) -> Result<Self, Error> {
+        let mut shards = Vec::new();
+        for data in batches() {
+            validate_data(&data, Some(dim))?;
+            let n_points = data.ncols();
+            shards.push(LocalState::new(
+                DMatrix::zeros(dim, 0),
+                RowDVector::from_element(n_points, 0),
+                RowDVector::from_element(n_points, 0),
+            ));
+        }
+        if shards.is_empty() {
+            return Err(Error::EmptyData);
+        }
+
+        Ok(Self { shards, batches: RefCell::new(batches), dim, error: RefCell::new(None), stats_cache: None })
+    }
+
+    /// Returns the first error encountered while reading the batches, if any.
+    pub fn take_error(&mut self) -> Result<(), Error> {
+        match self.error.get_mut().take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Runs `f` on the shard of every batch of a new pass over the data.
+    fn for_each_batch(&self, mut f: impl FnMut(&LocalState<P>)) {
+        let mut batches = self.batches.borrow_mut();
+        let mut n_batches = 0;
+        for (i, data) in (*batches)().into_iter().enumerate() {
+            n_batches += 1;
+            if let Some(shard) = self.shards.get(i) {
+                match check_batch(i, &data, shard, self.dim) {
+                    Ok(()) => {
+                        let mut local = shard.clone();
+                        local.data = data;
+                        f(&local);
+                    }
+                    Err(e) => {
+                        self.error.borrow_mut().get_or_insert(e);
+                    }
+                }
+            }
+        }
+        if n_batches != self.shards.len() {
+            self.error.borrow_mut().get_or_insert(batch_count_error(n_batches, self.shards.len()));
+        }
+    }
+
+    /// Runs `f` on the shard of every batch of a new pass over the data, with the data of the batch loaded.
+    fn for_each_batch_mut(&mut self, mut f: impl FnMut(&mut LocalState<P>)) {
+        self.stats_cache = None;
+        let batches = self.batches.get_mut();
+        let error = self.error.get_mut();
+        let mut n_batches = 0;
+        for (i, data) in batches().into_iter().enumerate() {
+            n_batches += 1;
+            if let Some(shard) = self.shards.get_mut(i) {
+                match check_batch(i, &data, shard, self.dim) {
+                    Ok(()) => {
+                        shard.data = data;
+                        f(shard);
+                        shard.data = DMatrix::zeros(self.dim, 0);
+                    }
+                    Err(e) => {
+                        error.get_or_insert(e);
+                    }
+                }
+            }
+        }
+        if n_batches != self.shards.len() {
+            error.get_or_insert(batch_count_error(n_batches, self.shards.len()));
+        }
+    }
+}
+
+/// Checks that a batch is valid and holds as many points as the shard it belongs to.
+fn check_batch<P: NormalConjugatePrior>(
+    i: usize,
+    data: &DMatrix<f64>,
+    shard: &LocalState<P>,
+    dim: usize,
+) -> Result<(), Error> {
+    validate_data(data, Some(dim))?;
+    if data.ncols() != shard.labels.len() {
+        return Err(Error::InvalidData(format!(
+            "batch {} has {} points, expected {} as in the first pass", i, data.ncols(), shard.labels.len()
+        )));
+    }
+    Ok(())
+}
+
+fn batch_count_error(got: usize, expected: usize) -> Error {
+    Error::InvalidData(format!("pass yielded {} batches, expected {} as in the first pass", got, expected))
+}
+
+/// Adds the statistics of `stats` to those of `acc` cluster by cluster.
+fn accumulate<P: NormalConjugatePrior>(
+    acc: Option<Vec<SuperClusterStats<P>>>,
+    stats: Vec<SuperClusterStats<P>>,
+) -> Option<Vec<SuperClusterStats<P>>> {
+    Some(match acc {
+        Some(mut acc) => {
+            for (stat, next) in acc.iter_mut().zip(stats.iter()) {
+                *stat += next;
+            }
+            acc
+        }
+        None => stats,
+    })
+}
+
+impl<P, I, F> LocalWorker<P> for BatchedState<P, F>
+    where
+        P: NormalConjugatePrior,
+        I: IntoIterator<Item=DMatrix<f64>>,
+        F: FnMut() -> I,
+{
+    fn init<R: Rng + Clone + Send + Sync>(&mut self, n_clusters: usize, rng: &mut R) {
+        self.for_each_batch_mut(|shard| shard.init(n_clusters, rng));
+    }
+
+    fn n_points(&self) -> usize {
+        self.shards.iter().map(|shard| shard.labels.len()).sum()
+    }
+
+    fn collect_labels(&self) -> RowDVector<usize> {
+        RowDVector::from_iterator(
+            self.n_points(),
+            self.shards.iter().flat_map(|shard| shard.labels.iter().cloned()),
+        )
+    }
+
+    fn collect_data_stats(&self) -> P::SuffStats {
+        let mut stats: Option<P::SuffStats> = None;
+        self.for_each_batch(|local| {
+            let batch_stats = local.collect_data_stats();
+            stats = Some(match stats.take() {
+                Some(acc) => acc + &batch_stats,
+                None => batch_stats,
+            });
+        });
+        stats.unwrap_or_else(|| P::SuffStats::from_data(&DMatrix::<f64>::zeros(self.dim, 0)))
+    }
+
+    fn collect_cluster_stats(&self, n_clusters: usize) -> Vec<SuperClusterStats<P>> {
+        if let Some(stats) = &self.stats_cache {
+            if stats.len() == n_clusters {
+                return stats.clone();
+            }
+        }
+
+        let mut stats = None;
+        self.for_each_batch(|local| stats = accumulate(stats.take(), local.collect_cluster_stats(n_clusters)));
+        stats.unwrap_or_else(|| {
+            let empty = || P::SuffStats::from_data(&DMatrix::<f64>::zeros(self.dim, 0));
+            (0..n_clusters).map(|_| SuperClusterStats::new(empty(), [empty(), empty()])).collect()
+        })
+    }
+
+    fn log_likelihood(&self, params: &impl ThinParams) -> f64 {
+        let mut ll = 0.0;
+        self.for_each_batch(|local| ll += local.log_likelihood(params));
+        ll
+    }
+
+    fn collect_responsibilities(&self, params: &impl ThinParams) -> DMatrix<f64> {
+        let mut probs = DMatrix::zeros(self.n_points(), params.n_clusters());
+        let mut offset = 0;
+        self.for_each_batch(|local| {
+            let batch_probs = local.collect_responsibilities(params);
+            probs.rows_mut(offset, batch_probs.nrows()).copy_from(&batch_probs);
+            offset += batch_probs.nrows();
+        });
+        probs
+    }
+
+    fn apply_label_sampling<R: Rng + Clone + Send + Sync>(
+        &mut self,
+        params: &impl ThinParams,
+        hard_assignment: bool,
+        rng: &mut R,
+    ) -> Result<(), Error> {
+        let n_clusters = params.n_clusters();
+        let mut result = Ok(());
+        let mut stats = None;
+        self.for_each_batch_mut(|shard| {
+            if result.is_ok() {
+                result = shard.apply_label_sampling(params, hard_assignment, rng);
+                stats = accumulate(stats.take(), shard.collect_cluster_stats(n_clusters));
+            }
+        });
+        if result.is_ok() {
+            self.stats_cache = stats;
+        }
+        result
+    }
+
+    fn apply_cluster_reset<R: Rng + Clone + Send + Sync>(
+        &mut self,
+        cluster_ids: &[usize],
+        rng: &mut R,
+    ) {
+        if !cluster_ids.is_empty() {
+            self.for_each_batch_mut(|shard| shard.apply_cluster_reset(cluster_ids, rng));
+        }
+    }
+
+    fn apply_cluster_remove(
+        &mut self,
+        cluster_ids: &[usize],
+    ) {
+        self.stats_cache = None;
+        for shard in &mut self.shards {
+            shard.apply_cluster_remove(cluster_ids);
+        }
+    }
+
+    fn apply_split<R: Rng + Clone + Send + Sync>(
+        &mut self,
+        split_decisions: &[(usize, usize)],
+        rng: &mut R,
+    ) {
+        if !split_decisions.is_empty() {
+            self.for_each_batch_mut(|shard| shard.apply_split(split_decisions, rng));
+        }
+    }
+
+    fn apply_merge(
+        &mut self,
+        merge_decisions: &[(usize, usize)],
+    ) {
+        self.stats_cache = None;
+        for shard in &mut self.shards {
+            shard.apply_merge(merge_decisions);
+        }
+    }
+
+    fn apply_cluster_reassign<R: Rng + Clone + Send + Sync>(
+        &mut self,
+        reassign_decisions: &[(usize, usize)],
+        rng: &mut R,
+    ) {
+        if !reassign_decisions.is_empty() {
+            self.for_each_batch_mut(|shard| shard.apply_cluster_reassign(reassign_decisions, rng));
+        }
+    }
+}
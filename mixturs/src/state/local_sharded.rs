@@ -1,18 +1,27 @@
-use nalgebra::DMatrix;
-use rand::Rng;
+use nalgebra::{DMatrix, DVector, RowDVector};
+use rand::{Rng, SeedableRng};
+use rand::rngs::SmallRng;
 use rayon::prelude::*;
+use crate::error::Error;
 use crate::params::{ThinParams, SuperClusterStats};
+use crate::params::options::{FitOptions, InitStrategy};
 use crate::state::{LocalState, LocalWorker};
 use crate::stats::NormalConjugatePrior;
+use crate::utils::{DataScalar, kmeans_plusplus, Label};
 
 /// A parallel variant of local state that splits data into equally sized shards
 /// and distributes computations across threads
-pub struct ShardedState<P: NormalConjugatePrior> {
-    pub shards: Vec<LocalState<P>>,
+///
+/// Every shard samples from its own random stream derived from the given random number generator and the
+/// shard index, so results only depend on the seed and the number of shards, not on the size of the thread pool.
+///
+/// Like [`LocalState`], the shards can store their labels and data in narrower types (e.g. `u32` and `f32`).
+pub struct ShardedState<P: NormalConjugatePrior, L: Label = usize, T: DataScalar = f64> {
+    pub shards: Vec<LocalState<P, L, T>>,
 }
 
-impl<P: NormalConjugatePrior> ShardedState<P> {
-    pub fn new(shards: Vec<LocalState<P>>) -> Self {
+impl<P: NormalConjugatePrior, L: Label, T: DataScalar> ShardedState<P, L, T> {
+    pub fn new(shards: Vec<LocalState<P, L, T>>) -> Self {
         Self { shards }
     }
 
@@ -23,7 +32,7 @@ impl<P: NormalConjugatePrior> ShardedState<P> {
     /// * `data`: The data to create the sharded state from (n_dims, n_points)
     /// * `n_shards`: The number of shards to split the data into
     ///
-    pub fn from_data(data: DMatrix<f64>, n_shards: usize) -> Self {
+    pub fn from_data(data: DMatrix<T>, n_shards: usize) -> Self {
         let shard_size = (data.ncols() as f64 / n_shards as f64).ceil() as usize;
 
         let mut shards = vec![];
@@ -39,10 +48,75 @@ impl<P: NormalConjugatePrior> ShardedState<P> {
     pub fn n_shards(&self) -> usize {
         self.shards.len()
     }
+
+    /// Derives an independent random number generator for each shard from a single draw of `rng`.
+    fn shard_rngs<R: Rng>(&self, rng: &mut R) -> Vec<SmallRng> {
+        let seed: u64 = rng.gen();
+        (0..self.n_shards() as u64)
+            .map(|i| SmallRng::seed_from_u64(seed ^ (i + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15)))
+            .collect()
+    }
+
+    /// Merges the shards back into a single local state, keeping the original point order.
+    /// The weights and frozen points of every shard are carried over, the per-fit settings are taken from
+    /// `options` (see [`LocalState::apply_fit_options`]).
+    pub fn into_local(self, options: &FitOptions) -> LocalState<P, L, T> {
+        let n_points = self.n_points();
+        let dim = self.shards.first().map_or(0, |shard| shard.data.nrows());
+
+        let mut data = DMatrix::from_element(dim, n_points, T::default());
+        let mut labels = RowDVector::from_element(n_points, L::default());
+        let mut labels_aux = RowDVector::from_element(n_points, L::default());
+        let mut offset = 0;
+        for shard in &self.shards {
+            let range = offset..offset + shard.n_points();
+            data.columns_range_mut(range.clone()).copy_from(&shard.data);
+            labels.columns_range_mut(range.clone()).copy_from(&shard.labels);
+            labels_aux.columns_range_mut(range).copy_from(&shard.labels_aux);
+            offset += shard.n_points();
+        }
+
+        let weights = if !self.shards.is_empty() && self.shards.iter().all(|shard| shard.weights.is_some()) {
+            Some(DVector::from_iterator(
+                n_points,
+                self.shards.iter().flat_map(|shard| shard.weights.as_ref().unwrap().iter().cloned()),
+            ))
+        } else {
+            None
+        };
+
+        let frozen = if self.shards.iter().any(|shard| shard.frozen.is_some()) {
+            Some(self.shards.iter().flat_map(|shard| match &shard.frozen {
+                Some(frozen) => frozen.clone(),
+                None => vec![false; shard.n_points()],
+            }).collect())
+        } else {
+            None
+        };
+
+        let mut local = LocalState::new(data, labels, labels_aux);
+        local.weights = weights;
+        local.frozen = frozen;
+        local.apply_fit_options(options);
+        local
+    }
 }
 
-impl<P: NormalConjugatePrior> LocalWorker<P> for ShardedState<P> {
+impl<P: NormalConjugatePrior, L: Label, T: DataScalar> LocalWorker<P> for ShardedState<P, L, T> {
     fn init<R: Rng + Clone + Send + Sync>(&mut self, n_clusters: usize, rng: &mut R) {
+        // Seed the centers over all shards, so the shards agree on the initial clusters
+        let init_strategy = self.shards.first().map(|shard| shard.init_strategy);
+        if let Some(InitStrategy::KMeansPlusPlus { n }) = init_strategy {
+            let columns: Vec<DVector<T>> = self.shards.iter()
+                .flat_map(|shard| shard.data.column_iter().map(|x| x.into_owned()))
+                .collect();
+            if !columns.is_empty() {
+                let centers = kmeans_plusplus(&DMatrix::from_columns(&columns), n_clusters, n, rng);
+                self.shards.iter_mut().for_each(|shard| shard.init_from_centers(&centers, rng));
+                return;
+            }
+        }
+
         self.shards.iter_mut().for_each(|shard| {
             shard.init(n_clusters, rng);
         });
@@ -52,8 +126,17 @@ impl<P: NormalConjugatePrior> LocalWorker<P> for ShardedState<P> {
         self.shards.iter().map(|shard| shard.n_points()).sum()
     }
 
+    fn collect_labels(&self) -> RowDVector<usize> {
+        RowDVector::from_iterator(
+            self.n_points(),
+            self.shards.iter().flat_map(|shard| shard.labels.iter().cloned()),
+        )
+    }
+
     fn collect_data_stats(&self) -> P::SuffStats {
-        self.shards.par_iter().map(LocalWorker::<P>::collect_data_stats).sum()
+        // Sum in shard order so the result does not depend on how rayon splits the work
+        let stats: Vec<_> = self.shards.par_iter().map(LocalWorker::<P>::collect_data_stats).collect();
+        stats.into_iter().sum()
     }
 
     fn collect_cluster_stats(&self, n_clusters: usize) -> Vec<SuperClusterStats<P>> {
@@ -71,15 +154,35 @@ impl<P: NormalConjugatePrior> LocalWorker<P> for ShardedState<P> {
         })
     }
 
+    fn log_likelihood(&self, params: &impl ThinParams) -> f64 {
+        let ll: Vec<f64> = self.shards.par_iter().map(|shard| shard.log_likelihood(params)).collect();
+        ll.into_iter().sum()
+    }
+
+    fn collect_responsibilities(&self, params: &impl ThinParams) -> DMatrix<f64> {
+        let shard_probs: Vec<_> = self.shards.par_iter()
+            .map(|shard| shard.collect_responsibilities(params))
+            .collect();
+
+        let mut probs = DMatrix::zeros(self.n_points(), params.n_clusters());
+        let mut offset = 0;
+        for shard in shard_probs {
+            probs.rows_mut(offset, shard.nrows()).copy_from(&shard);
+            offset += shard.nrows();
+        }
+        probs
+    }
+
     fn apply_label_sampling<R: Rng + Clone + Send + Sync>(
         &mut self,
         params: &impl ThinParams,
         hard_assignment: bool,
         rng: &mut R,
-    ) {
-        self.shards.par_iter_mut().for_each_with(rng.clone(), |rng, shard| {
-            shard.apply_label_sampling(params, hard_assignment, rng);
-        });
+    ) -> Result<(), Error> {
+        let rngs = self.shard_rngs(rng);
+        self.shards.par_iter_mut().zip(rngs).map(|(shard, mut rng)| {
+            shard.apply_label_sampling(params, hard_assignment, &mut rng)
+        }).collect()
     }
 
     fn apply_cluster_reset<R: Rng + Clone + Send + Sync>(
@@ -87,8 +190,9 @@ impl<P: NormalConjugatePrior> LocalWorker<P> for ShardedState<P> {
         cluster_ids: &[usize],
         rng: &mut R,
     ) {
-        self.shards.par_iter_mut().for_each_with(rng.clone(), |rng, shard| {
-            shard.apply_cluster_reset(cluster_ids, rng);
+        let rngs = self.shard_rngs(rng);
+        self.shards.par_iter_mut().zip(rngs).for_each(|(shard, mut rng)| {
+            shard.apply_cluster_reset(cluster_ids, &mut rng);
         });
     }
 
@@ -106,8 +210,9 @@ impl<P: NormalConjugatePrior> LocalWorker<P> for ShardedState<P> {
         split_decisions: &[(usize, usize)],
         rng: &mut R,
     ) {
-        self.shards.par_iter_mut().for_each_with(rng.clone(), |rng, shard| {
-            shard.apply_split(split_decisions, rng);
+        let rngs = self.shard_rngs(rng);
+        self.shards.par_iter_mut().zip(rngs).for_each(|(shard, mut rng)| {
+            shard.apply_split(split_decisions, &mut rng);
         });
     }
 
@@ -119,4 +224,15 @@ impl<P: NormalConjugatePrior> LocalWorker<P> for ShardedState<P> {
             shard.apply_merge(merge_decisions);
         });
     }
+
+    fn apply_cluster_reassign<R: Rng + Clone + Send + Sync>(
+        &mut self,
+        reassign_decisions: &[(usize, usize)],
+        rng: &mut R,
+    ) {
+        let rngs = self.shard_rngs(rng);
+        self.shards.par_iter_mut().zip(rngs).for_each(|(shard, mut rng)| {
+            shard.apply_cluster_reassign(reassign_decisions, &mut rng);
+        });
+    }
 }
\ No newline at end of file
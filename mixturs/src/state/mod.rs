@@ -1,12 +1,19 @@
 mod global;
 mod local;
+mod local_batched;
 mod local_sharded;
+mod postprocess;
 
-pub use global::GlobalState;
-pub use local::{LocalState};
+pub use global::{cluster_pairwise_divergence, ClusterSummary, GlobalState, OutlierScores};
+pub use local::{LocalState, LocalStateF32};
+pub(crate) use local::{validate_frozen, validate_weights};
+pub use local_batched::BatchedState;
 pub use local_sharded::ShardedState;
+pub use postprocess::merge_small_into_nearest;
 
+use nalgebra::{DMatrix, RowDVector};
 use rand::Rng;
+use crate::error::Error;
 use crate::params::clusters::SuperClusterStats;
 use crate::params::options::ModelOptions;
 use crate::params::thin::ThinParams;
@@ -66,6 +73,21 @@ pub trait GlobalWorker<P: NormalConjugatePrior> {
     /// A vector of tuples where first element is index of the merged cluster and the second element is the index of
     /// the cluster that was merged into the first cluster
     fn check_and_merge<R: Rng>(&mut self, options: &ModelOptions<P>, rng: &mut R) -> Vec<(usize, usize)>;
+
+    /// Collects the (non-outlier) clusters with fewer than [`ModelOptions::min_cluster_size`] points and the
+    /// nearest (by mean) large enough cluster their points should be reassigned to
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: The model options
+    ///
+    /// # Returns
+    ///
+    /// A vector of tuples where first element is index of the small cluster and the second element is the index of
+    /// the cluster its points are reassigned to. Defaults to none, i.e. small clusters are kept.
+    fn collect_small_clusters(&self, _options: &ModelOptions<P>) -> Vec<(usize, usize)> {
+        Vec::new()
+    }
 }
 
 /// Local worker keeps track of data and is responsible to calculate the sufficient statistics over these points
@@ -87,6 +109,9 @@ pub trait LocalWorker<P: NormalConjugatePrior> {
     /// Returns the number of points in the local state
     fn n_points(&self) -> usize;
 
+    /// Collects the primary labels of all of the data points in their original order
+    fn collect_labels(&self) -> RowDVector<usize>;
+
     /// Collects the sufficient statistics over all of the data points
     fn collect_data_stats(&self) -> P::SuffStats;
 
@@ -97,6 +122,22 @@ pub trait LocalWorker<P: NormalConjugatePrior> {
     /// * `n_clusters`: The number of clusters present in the model
     fn collect_cluster_stats(&self, n_clusters: usize) -> Vec<SuperClusterStats<P>>;
 
+    /// Returns the log-likelihood of the data points under their assigned primary clusters, i.e. the sum of
+    /// `ln_pdf` of each point under the distribution of its cluster
+    ///
+    /// # Arguments
+    ///
+    /// * `params`: The cluster parameters
+    fn log_likelihood(&self, params: &impl ThinParams) -> f64;
+
+    /// Returns the responsibilities of the clusters for each data point in their original order, i.e. the
+    /// probabilities label sampling draws from, normalized to sum to one. (n_points, n_clusters)
+    ///
+    /// # Arguments
+    ///
+    /// * `params`: The cluster parameters
+    fn collect_responsibilities(&self, params: &impl ThinParams) -> DMatrix<f64>;
+
     /// Assigns points to clusters based on the cluster parameters and sampling strategy
     ///
     /// # Arguments
@@ -104,12 +145,17 @@ pub trait LocalWorker<P: NormalConjugatePrior> {
     /// * `params`: The cluster parameters
     /// * `hard_assignment`: Whether to perform hard assignment or soft assignment (i.e. sampling strategy)
     /// * `rng`: The random number generator
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error`] if the labels of a point can not be sampled, e.g. because it has zero
+    /// likelihood under every cluster.
     fn apply_label_sampling<R: Rng + Clone + Send + Sync>(
         &mut self,
         params: &impl ThinParams,
         hard_assignment: bool,
         rng: &mut R,
-    );
+    ) -> Result<(), Error>;
 
     /// Resets the auxiliary cluster assignments of the given clusters
     fn apply_cluster_reset<R: Rng + Clone + Send + Sync>(
@@ -151,4 +197,24 @@ pub trait LocalWorker<P: NormalConjugatePrior> {
         &mut self,
         merge_decisions: &[(usize, usize)],
     );
+
+    /// Moves the points of the given clusters to other clusters and redraws their auxiliary labels
+    ///
+    /// Defaults to merging each cluster into its target and resetting the auxiliary labels of the targets.
+    ///
+    /// # Arguments
+    ///
+    /// * `reassign_decisions`: A vector of tuples where first element is index of the cluster to empty and the second
+    /// element is the index of the cluster its points are moved to
+    /// * `rng`: The random number generator
+    fn apply_cluster_reassign<R: Rng + Clone + Send + Sync>(
+        &mut self,
+        reassign_decisions: &[(usize, usize)],
+        rng: &mut R,
+    ) {
+        let merge_decisions: Vec<_> = reassign_decisions.iter().map(|&(from, to)| (to, from)).collect();
+        self.apply_merge(&merge_decisions);
+        let targets: Vec<_> = reassign_decisions.iter().map(|&(_, to)| to).collect();
+        self.apply_cluster_reset(&targets, rng);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,104 @@
+use rand::Rng;
+use crate::params::options::ModelOptions;
+use crate::state::{GlobalState, LocalState};
+use crate::stats::NormalConjugatePrior;
+
+/// Post-processing pass merging each cluster with fewer than `min_size` points wholesale into the cluster with
+/// the nearest mean. Unlike reassigning the points one by one, the statistics (and thus the covariance
+/// contribution) of the small cluster are kept in the merged cluster.
+/// Clusters are merged smallest first until all remaining clusters are large enough or only one is left.
+/// The outlier cluster is never merged. Empty clusters are left for the regular cluster removal.
+///
+/// # Arguments
+///
+/// * `global`: The fitted global state
+/// * `local`: The local state holding the labels of the fitted data
+/// * `min_size`: Minimum number of points of a cluster
+/// * `options`: The model options
+/// * `rng`: The random number generator
+///
+/// # Returns
+///
+/// The number of merged clusters.
+pub fn merge_small_into_nearest<P: NormalConjugatePrior, R: Rng>(
+    global: &mut GlobalState<P>,
+    local: &mut LocalState<P>,
+    min_size: usize,
+    options: &ModelOptions<P>,
+    rng: &mut R,
+) -> usize {
+    let first = options.cluster_offset();
+    let mut n_merged = 0;
+
+    while global.clusters.len() > first + 1 {
+        let small = match (first..global.clusters.len())
+            .filter(|&k| (1..min_size).contains(&global.clusters[k].n_points()))
+            .min_by_key(|&k| global.clusters[k].n_points()) {
+            Some(small) => small,
+            None => break,
+        };
+
+        let mu = global.clusters[small].prim.dist.mu();
+        let nearest = (first..global.clusters.len())
+            .filter(|&k| k != small)
+            .min_by(|&a, &b| {
+                let da = (global.clusters[a].prim.dist.mu() - mu).norm_squared();
+                let db = (global.clusters[b].prim.dist.mu() - mu).norm_squared();
+                da.total_cmp(&db)
+            })
+            .unwrap();
+
+        global.merge_clusters(nearest, small, Some(&mut *local), options, rng)
+            .expect("Merged clusters are distinct and in range");
+        n_merged += 1;
+    }
+
+    n_merged
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, RowDVector};
+    use rand::prelude::StdRng;
+    use rand::SeedableRng;
+    use crate::{ModelOptions, NIW};
+    use crate::state::{GlobalState, LocalState, LocalWorker, merge_small_into_nearest};
+    use crate::stats::tests::test_almost_mat;
+    use crate::testing::make_blobs;
+
+    #[test]
+    fn test_merge_small_into_nearest() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut options = ModelOptions::<NIW>::default(2);
+        options.outlier = None;
+
+        // A small blob next to the first large blob
+        let centers = DMatrix::from_column_slice(2, 3, &[0.0, 0.0, 20.0, 20.0, 3.0, 0.0]);
+        let (x, y) = make_blobs(100, &centers, 1.0, 42);
+        let small: Vec<usize> = (0..x.ncols()).filter(|&i| y[i] != 2).chain((0..x.ncols()).filter(|&i| y[i] == 2).take(5)).collect();
+        let (x, y) = (x.select_columns(&small), RowDVector::from_fn(small.len(), |_, i| y[small[i]]));
+
+        let mut local = LocalState::<NIW>::new(x, y, RowDVector::zeros(small.len()));
+        let stats = local.collect_cluster_stats(3).into_iter().map(|stats| stats.prim).collect();
+        let mut global = GlobalState::import_cluster_stats(stats, &options, &mut rng);
+        let before = global.export_cluster_stats();
+
+        assert_eq!(merge_small_into_nearest(&mut global, &mut local, 10, &options, &mut rng), 1);
+        assert_eq!(global.clusters.len(), 2);
+        assert_eq!(global.weights.len(), 2);
+
+        // The small cluster is merged into the first one
+        let merged = &global.clusters[0].prim.stats;
+        let combined = before[0].clone() + &before[2];
+        assert_eq!(merged.n_points, 105);
+        test_almost_mat(&merged.mean, &combined.mean, 1e-8);
+        test_almost_mat(&merged.scatter, &combined.scatter, 1e-8);
+        assert_eq!(local.labels.iter().filter(|&&l| l == 0).count(), 105);
+
+        // The local statistics agree with the merged clusters
+        let local_stats = local.collect_cluster_stats(2);
+        assert_eq!(local_stats[0].prim.n_points, 105);
+        assert_eq!(local_stats[0].aux[1].n_points, 5);
+        assert_eq!(local_stats[1].prim.n_points, 100);
+    }
+}
@@ -1,4 +1,4 @@
-use nalgebra::{DefaultAllocator, DMatrix, DVector, Dynamic, Matrix, StorageMut};
+use nalgebra::{DefaultAllocator, DMatrix, DVector, Dynamic, Matrix, Storage, StorageMut};
 use nalgebra::allocator::Allocator;
 #[cfg(feature = "serde")]
 use serde::{Serialize, Deserialize};
@@ -59,18 +59,52 @@ where
     /// Calculates the log probability density function for the multivariate
     /// normal distribution for each `x` (column) in `xs`. Equivalent to pdf(x).ln().
     fn batchwise_ln_pdf(&self, xs: Matrix<f64, Dynamic, Dynamic, S>) -> DVector<f64> {
-        let n_points = xs.ncols();
-        let dvs = col_broadcast_sub(xs, self.mu()); // broadcast subtract?
+        let dvs = col_broadcast_sub(xs, self.mu());
+        centered_ln_pdf(self, &dvs)
+    }
+}
 
-        let mut left = self.precision() * &dvs;
-        left.component_mul_assign(&dvs);
+/// Number of points centered at once when the data is borrowed, bounding the memory used next to it.
+pub(crate) const BORROWED_CHUNK_SIZE: usize = 1024;
 
-        let pdf_const = self.pdf_const().ln();
-        let exp_term = DVector::from_iterator(
-            n_points,
-            left.column_iter().map(|col| -0.5 * col.sum() + pdf_const)
-        );
-        exp_term
+/// Log density of each centered point (column) in `dvs`, using the precision matrix and normalization
+/// constant the distribution caches from the Cholesky factor of its covariance.
+fn centered_ln_pdf<S>(mvn: &MultivariateNormal, dvs: &Matrix<f64, Dynamic, Dynamic, S>) -> DVector<f64>
+where
+    S: Storage<f64, Dynamic, Dynamic>,
+{
+    let mut left = mvn.precision() * dvs;
+    left.component_mul_assign(dvs);
+
+    let pdf_const = mvn.pdf_const().ln();
+    DVector::from_iterator(
+        dvs.ncols(),
+        left.column_iter().map(|col| -0.5 * col.sum() + pdf_const)
+    )
+}
+
+impl<'a, S> ContinuousBatchwise<&'a Matrix<f64, Dynamic, Dynamic, S>> for MultivariateNormal
+where
+    S: Storage<f64, Dynamic, Dynamic>,
+{
+    /// Calculates the probability density function for each `x` (column) in `xs` without taking ownership
+    /// of the data.
+    fn batchwise_pdf(&self, xs: &'a Matrix<f64, Dynamic, Dynamic, S>) -> DVector<f64> {
+        self.batchwise_ln_pdf(xs).map(f64::exp)
+    }
+
+    /// Calculates the log probability density function for each `x` (column) in `xs` without taking
+    /// ownership of the data. The points are centered in chunks of [`BORROWED_CHUNK_SIZE`] columns
+    /// instead of copying the whole matrix.
+    fn batchwise_ln_pdf(&self, xs: &'a Matrix<f64, Dynamic, Dynamic, S>) -> DVector<f64> {
+        let n_points = xs.ncols();
+        let mut ln_pdf = DVector::zeros(n_points);
+        for start in (0..n_points).step_by(BORROWED_CHUNK_SIZE) {
+            let n = BORROWED_CHUNK_SIZE.min(n_points - start);
+            let dvs: DMatrix<f64> = col_broadcast_sub(xs.columns(start, n).clone_owned(), self.mu());
+            ln_pdf.rows_mut(start, n).copy_from(&centered_ln_pdf(self, &dvs));
+        }
+        ln_pdf
     }
 }
 
@@ -99,7 +133,10 @@ impl From<MultivariateNormalDef> for MultivariateNormal {
 #[cfg(test)]
 mod tests {
     use nalgebra::{DMatrix, DVector};
-    use statrs::distribution::MultivariateNormal;
+    use rand::prelude::StdRng;
+    use rand::SeedableRng;
+    use rand::distributions::Distribution;
+    use statrs::distribution::{Continuous, MultivariateNormal, Normal};
     use crate::stats::batch_mvn::{ContinuousBatchwise};
     use crate::stats::tests::test_almost_mat;
 
@@ -154,4 +191,19 @@ mod tests {
 
         test_almost_mat(&actual, &expected, 1e-6);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_ln_pdf_matches_per_point() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let normal = Normal::new(0.0, 1.0).unwrap();
+        let a = DMatrix::from_fn(3, 3, |_, _| normal.sample(&mut rng));
+        let cov = &a * a.transpose() + DMatrix::identity(3, 3) * 0.1;
+        let mvn = MultivariateNormal::new(vec![1.0, -2.0, 0.5], cov.data.into()).unwrap();
+        let data = DMatrix::from_fn(3, 100, |_, _| 3.0 * normal.sample(&mut rng));
+
+        let expected = DVector::from_iterator(100, data.column_iter().map(|x| mvn.ln_pdf(&x.clone_owned())));
+
+        test_almost_mat(&mvn.batchwise_ln_pdf(&data), &expected, 1e-10);
+        test_almost_mat(&mvn.batchwise_ln_pdf(data.clone()), &expected, 1e-10);
+    }
+}
@@ -1,7 +1,10 @@
+use std::collections::HashMap;
 use nalgebra::{DVector};
 use rand::distributions::{Distribution};
 use rand::Rng;
 use statrs::distribution::{Dirichlet};
+use statrs::function::gamma::digamma;
+use crate::utils::Iterutils;
 
 /// Samples the dirichlet process using the stick breaking approach.
 ///
@@ -44,8 +47,67 @@ pub fn stick_breaking_sample(
     }
 }
 
+/// Computes the marginal posterior over the number of occupied clusters from a chain of samples.
+///
+/// # Arguments:
+///
+/// * `samples`: the number of occupied clusters at each (post burn-in) iteration of the chain
+///
+/// # Returns:
+///
+/// A map from cluster count to its posterior probability (normalized frequency in the chain).
+///
+/// # Example:
+/// ```
+/// use mixturs::stats::cluster_count_posterior;
+///
+/// let samples = vec![3, 3, 4, 3];
+/// let posterior = cluster_count_posterior(&samples);
+/// assert_eq!(posterior[&3], 0.75);
+/// assert_eq!(posterior[&4], 0.25);
+/// ```
+pub fn cluster_count_posterior(samples: &[usize]) -> HashMap<usize, f64> {
+    let n_bins = samples.iter().max().map_or(0, |&k| k + 1);
+    let counts = samples.iter().cloned().bincounts(n_bins);
+    let n_samples = samples.len() as f64;
+
+    counts.into_iter()
+        .enumerate()
+        .filter(|(_, c)| *c > 0)
+        .map(|(k, c)| (k, c as f64 / n_samples))
+        .collect()
+}
+
+/// Computes the expected number of clusters of a Chinese Restaurant Process (the dirichlet process
+/// prior over partitions) with concentration `alpha` over `n_points` points:
+/// `alpha * (digamma(alpha + n) - digamma(alpha))`.
+/// Useful for choosing an `alpha` that targets a desired number of clusters.
+///
+/// # Arguments:
+///
+/// * `alpha`: the concentration parameter of the dirichlet process
+/// * `n_points`: the number of observations
+///
+/// # Example:
+/// ```
+/// use mixturs::stats::expected_cluster_count;
+///
+/// // With alpha = 1 the expectation is the harmonic number H_n
+/// let expected = expected_cluster_count(1.0, 3);
+/// assert!((expected - (1.0 + 1.0 / 2.0 + 1.0 / 3.0)).abs() < 1e-9);
+/// ```
+pub fn expected_cluster_count(alpha: f64, n_points: usize) -> f64 {
+    if n_points == 0 {
+        return 0.0;
+    }
+
+    alpha * (digamma(alpha + n_points as f64) - digamma(alpha))
+}
+
 #[cfg(test)]
 mod tests {
+    use rand::prelude::StdRng;
+    use rand::{Rng, SeedableRng};
     use statrs::assert_almost_eq;
 
     #[test]
@@ -62,4 +124,39 @@ mod tests {
         assert_eq!(weights[0], 0.5);
         assert!(weights[1] < weights[2]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_cluster_count_posterior() {
+        let samples = vec![2, 3, 3, 5, 3, 2, 3, 3];
+        let posterior = super::cluster_count_posterior(&samples);
+
+        assert_eq!(posterior.len(), 3);
+        assert_almost_eq!(posterior.values().sum::<f64>(), 1.0, 1e-12);
+        assert_almost_eq!(posterior[&2], 2.0 / 8.0, 1e-12);
+        assert_almost_eq!(posterior[&3], 5.0 / 8.0, 1e-12);
+        assert_almost_eq!(posterior[&5], 1.0 / 8.0, 1e-12);
+        assert!(!posterior.contains_key(&4));
+
+        assert!(super::cluster_count_posterior(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_expected_cluster_count() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let n_points = 200;
+        let n_runs = 2000;
+
+        for alpha in [1.0, 10.0] {
+            // Seat customers one by one: the i-th opens a new table with probability alpha / (alpha + i)
+            let mean = (0..n_runs).map(|_| {
+                (0..n_points).filter(|&i| rng.gen_bool(alpha / (alpha + i as f64))).count()
+            }).sum::<usize>() as f64 / n_runs as f64;
+
+            let expected = super::expected_cluster_count(alpha, n_points);
+            assert!((mean - expected).abs() < 0.03 * expected, "alpha {}: {} vs {}", alpha, mean, expected);
+        }
+
+        assert_eq!(super::expected_cluster_count(1.0, 0), 0.0);
+        assert_almost_eq!(super::expected_cluster_count(2.0, 1), 1.0, 1e-9);
+    }
+}
@@ -8,5 +8,6 @@ pub use covariance::*;
 pub use priors::*;
 pub use dp::*;
 pub use batch_mvn::*;
+pub(crate) use batch_mvn::BORROWED_CHUNK_SIZE;
 pub use split_merge::*;
 pub use statrs::distribution::MultivariateNormal;
\ No newline at end of file
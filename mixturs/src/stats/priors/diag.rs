@@ -0,0 +1,352 @@
+use std::iter::Sum;
+use std::ops::{Add, AddAssign};
+use nalgebra::{DMatrix, DVector, Dynamic, Matrix, Storage};
+use rand::distributions::Distribution;
+use rand::Rng;
+use statrs::consts::LN_PI;
+use statrs::distribution::{Continuous, Gamma, MultivariateNormal, Normal, StudentsT};
+use statrs::function::gamma::ln_gamma;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+use crate::stats::{ConjugatePrior, FromData, NormalConjugatePrior, PriorHyperParams, SufficientStats, weighted_welford};
+
+
+/// The sufficient statistics needed to compute the posterior of the [`DiagNIW`] prior distribution.
+/// Only the mean and the per-dimension sums of squares around it are accumulated.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiagNIWStats {
+    pub n_points: usize,
+    pub mean: DVector<f64>,
+    pub scatter: DVector<f64>,
+}
+
+impl DiagNIWStats {
+    fn empty(dim: usize) -> Self {
+        Self { n_points: 0, mean: DVector::zeros(dim), scatter: DVector::zeros(dim) }
+    }
+}
+
+impl Sum for DiagNIWStats {
+    fn sum<I: Iterator<Item=Self>>(mut iter: I) -> Self {
+        let res = iter.next().unwrap_or_default();
+        iter.fold(res, |acc, x| {
+            if acc.n_points > 0 {
+                acc + &x
+            } else {
+                x
+            }
+        })
+    }
+}
+
+impl Default for DiagNIWStats {
+    #[cfg(not(tarpaulin_include))]
+    fn default() -> Self {
+        Self::empty(1)
+    }
+}
+
+impl FromData for DiagNIWStats {
+    fn from_data<S: Storage<f64, Dynamic, Dynamic>>(data: &Matrix<f64, Dynamic, Dynamic, S>) -> Self {
+        if data.ncols() == 0 {
+            return Self::empty(data.nrows());
+        }
+
+        let mean = data.column_mean();
+        let mut centered = data.clone_owned();
+        for mut col in centered.column_iter_mut() {
+            col -= &mean;
+        }
+        let scatter = centered.component_mul(&centered).column_sum();
+        Self { n_points: data.ncols(), mean, scatter }
+    }
+
+    /// The statistics are accumulated around the weighted mean (see [`weighted_welford`]) and the number of
+    /// points is the total weight rounded to the nearest integer.
+    fn from_weighted_data<S: Storage<f64, Dynamic, Dynamic>>(
+        data: &Matrix<f64, Dynamic, Dynamic, S>,
+        weights: &[f64],
+    ) -> Self {
+        let (total, mean, scatter) = weighted_welford(data, weights);
+        Self { n_points: total.round() as usize, mean, scatter: scatter.diagonal() }
+    }
+}
+
+impl SufficientStats for DiagNIWStats {
+    fn n_points(&self) -> usize {
+        self.n_points
+    }
+
+    fn dim(&self) -> usize {
+        self.mean.nrows()
+    }
+
+    fn rescaled(&self, n_points: usize) -> Self {
+        if self.n_points == 0 {
+            return self.clone();
+        }
+
+        let factor = n_points as f64 / self.n_points as f64;
+        Self {
+            n_points,
+            mean: self.mean.clone(),
+            scatter: &self.scatter * factor,
+        }
+    }
+}
+
+/// Merges the means and sums of squares per dimension, as for [`crate::stats::NIWStats`].
+impl<'a> AddAssign<&'a DiagNIWStats> for DiagNIWStats {
+    fn add_assign(&mut self, rhs: &'a DiagNIWStats) {
+        if rhs.n_points == 0 {
+            return;
+        }
+        if self.n_points == 0 {
+            *self = rhs.clone();
+            return;
+        }
+
+        let (n_lhs, n_rhs) = (self.n_points as f64, rhs.n_points as f64);
+        let n = n_lhs + n_rhs;
+        let delta = &rhs.mean - &self.mean;
+        self.scatter += &rhs.scatter + delta.component_mul(&delta) * (n_lhs * n_rhs / n);
+        self.mean += delta * (n_rhs / n);
+        self.n_points += rhs.n_points;
+    }
+}
+
+impl<'a> Add<&'a DiagNIWStats> for DiagNIWStats {
+    type Output = DiagNIWStats;
+
+    fn add(mut self, rhs: &'a DiagNIWStats) -> Self::Output {
+        self += rhs;
+        self
+    }
+}
+
+/// The hyperparameters of the [`DiagNIW`] prior distribution. `psi` holds the diagonal of the
+/// (scaled) inverse wishart scale matrix.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiagNIWParams {
+    pub kappa: f64,
+    pub mu: DVector<f64>,
+    pub nu: f64,
+    pub psi: DVector<f64>,
+}
+
+impl PriorHyperParams for DiagNIWParams {
+    /// Every dimension gets the prior a one dimensional [`NIWParams`](crate::stats::NIWParams) default would.
+    #[cfg(not(tarpaulin_include))]
+    fn default(dim: usize) -> Self {
+        Self {
+            kappa: 1.0,
+            mu: DVector::zeros(dim),
+            nu: 4.0,
+            psi: DVector::from_element(dim, 1.0),
+        }
+    }
+
+    fn dim(&self) -> usize {
+        self.mu.len()
+    }
+
+    fn is_proper(&self) -> bool {
+        self.kappa > 0.0
+            && self.nu > 0.0
+            && self.mu.iter().all(|x| x.is_finite())
+            && self.psi.iter().all(|x| *x > 0.0 && x.is_finite())
+    }
+}
+
+impl DiagNIWParams {
+    pub fn new(kappa: f64, mu: DVector<f64>, nu: f64, psi: DVector<f64>) -> Self {
+        DiagNIWParams { kappa, mu, nu, psi }
+    }
+
+    pub fn from_data<S: Storage<f64, Dynamic, Dynamic>>(
+        kappa: f64,
+        nu: f64,
+        data: &Matrix<f64, Dynamic, Dynamic, S>,
+    ) -> Self {
+        let mu = data.column_mean();
+        let psi = data.column_variance();
+        Self { kappa, mu, nu, psi }
+    }
+}
+
+/// A [Normal-Inverse-Wishart](https://en.wikipedia.org/wiki/Normal-inverse-Wishart_distribution) prior
+/// restricted to diagonal covariance matrices, i.e. an independent one dimensional NIW prior per dimension.
+/// Cheaper than [`NIW`](crate::stats::NIW) in high dimensions and a drop-in replacement for it:
+/// ```
+/// use mixturs::ModelOptions;
+/// use mixturs::stats::DiagNIW;
+///
+/// let model_options = ModelOptions::<DiagNIW>::default(2);
+/// assert_eq!(model_options.data_dist.psi.len(), 2);
+/// ```
+#[derive(Clone, Debug)]
+pub struct DiagNIW;
+
+impl ConjugatePrior for DiagNIW {
+    type HyperParams = DiagNIWParams;
+    type SuffStats = DiagNIWStats;
+
+    fn posterior(
+        prior: &Self::HyperParams,
+        stats: &Self::SuffStats,
+    ) -> Self::HyperParams {
+        let n_points = stats.n_points as f64;
+        let kappa = prior.kappa + n_points;
+        let nu = prior.nu + n_points;
+        let mu = (&prior.mu * prior.kappa + &stats.mean * n_points) / kappa;
+        let diff = &stats.mean - &prior.mu;
+        let psi =
+            (prior.nu * &prior.psi
+                + &stats.scatter
+                + diff.component_mul(&diff) * (prior.kappa * n_points / kappa)
+            ) / nu;
+
+        DiagNIWParams { kappa, mu, nu, psi }
+    }
+
+    /// Sum of the one dimensional NIW marginal log likelihoods of each dimension.
+    fn marginal_log_likelihood(
+        prior: &Self::HyperParams,
+        post: &Self::HyperParams,
+        stats: &Self::SuffStats,
+    ) -> f64 {
+        let dim = stats.mean.nrows() as f64;
+        -(stats.n_points as f64) * dim * 0.5 * LN_PI
+            + dim * (ln_gamma(post.nu / 2.0) - ln_gamma(prior.nu / 2.0))
+            + (prior.nu / 2.0) * (dim * prior.nu.ln() + prior.psi.iter().map(|x| x.ln()).sum::<f64>())
+            - (post.nu / 2.0) * (dim * post.nu.ln() + post.psi.iter().map(|x| x.ln()).sum::<f64>())
+            + (dim / 2.0) * (prior.kappa / post.kappa).ln()
+    }
+
+    /// Sum over the points of the product of per-dimension Student-t predictive densities.
+    fn posterior_predictive<S: Storage<f64, Dynamic, Dynamic>>(
+        post: &Self::HyperParams,
+        data: &Matrix<f64, Dynamic, Dynamic, S>,
+    ) -> f64 {
+        let scale_factor = (post.kappa + 1.0) / post.kappa;
+        data.row_iter().enumerate()
+            .map(|(d, row)| {
+                let t = StudentsT::new(post.mu[d], (post.psi[d] * scale_factor).sqrt(), post.nu).unwrap();
+                row.iter().map(|x| t.ln_pdf(*x)).sum::<f64>()
+            })
+            .sum()
+    }
+}
+
+impl NormalConjugatePrior for DiagNIW {
+    fn sample<R: Rng + ?Sized>(prior: &Self::HyperParams, rng: &mut R) -> MultivariateNormal {
+        prior.sample(rng)
+    }
+}
+
+impl Distribution<MultivariateNormal> for DiagNIWParams {
+    /// Sample parameters of a normal distribution with diagonal covariance from the prior distribution.
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> MultivariateNormal {
+        // Per dimension: sigma^2 ~ InvGamma(nu / 2, nu * psi / 2), mu ~ N(mu, sigma^2 / kappa)
+        let sigma = DVector::from_fn(self.dim(), |d, _| {
+            1.0 / Gamma::new(self.nu / 2.0, self.nu * self.psi[d] / 2.0).unwrap().sample(rng)
+        });
+        let mu = DVector::from_fn(self.dim(), |d, _| {
+            Normal::new(self.mu[d], (sigma[d] / self.kappa).sqrt()).unwrap().sample(rng)
+        });
+
+        MultivariateNormal::new(
+            mu.data.into(),
+            DMatrix::from_diagonal(&sigma).data.into(),
+        ).unwrap()
+    }
+}
+
+
+#[rustfmt::skip]
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, DVector};
+    use rand::distributions::Distribution;
+    use rand::prelude::StdRng;
+    use rand::SeedableRng;
+    use statrs::assert_almost_eq;
+    use crate::{FitOptions, Model, ModelOptions};
+    use crate::callback::MonitoringCallback;
+    use crate::metrics::normalized_mutual_info_score;
+    use crate::state::GlobalState;
+    use crate::stats::{ConjugatePrior, DiagNIW, DiagNIWParams, DiagNIWStats, FromData, NIW, NIWParams, NIWStats, SufficientStats};
+    use crate::stats::tests::{points1, test_almost_mat};
+    use crate::testing::make_blobs;
+
+    #[test]
+    fn test_stats() {
+        let points = points1();
+        let stats = DiagNIWStats::from_data(&points);
+        let full = NIWStats::from_data(&points);
+
+        assert_eq!(stats.n_points(), 10);
+        test_almost_mat(&stats.mean, &full.mean, 1e-12);
+        test_almost_mat(&stats.scatter, &full.scatter.diagonal(), 1e-12);
+
+        let weighted = DiagNIWStats::from_weighted_data(&points, &[1.0; 10]);
+        assert_eq!(weighted.n_points, 10);
+        test_almost_mat(&weighted.scatter, &stats.scatter, 1e-10);
+    }
+
+    #[test]
+    fn test_matches_niw_in_one_dim() {
+        let points = points1().rows(0, 1).clone_owned();
+        let prior = DiagNIWParams::from_data(1.0, 4.0, &points);
+        let full_prior = NIWParams::from_data(1.0, 4.0, &points);
+
+        let stats = DiagNIWStats::from_data(&points);
+        let full_stats = NIWStats::from_data(&points);
+        let post = DiagNIW::posterior(&prior, &stats);
+        let full_post = NIW::posterior(&full_prior, &full_stats);
+
+        assert_almost_eq!(post.psi[0], full_post.psi[(0, 0)], 1e-12);
+        assert_almost_eq!(
+            DiagNIW::marginal_log_likelihood(&prior, &post, &stats),
+            NIW::marginal_log_likelihood(&full_prior, &full_post, &full_stats),
+            1e-10
+        );
+    }
+
+    #[test]
+    fn test_sample() {
+        let prior = DiagNIWParams::from_data(1.0, 4.0, &points1());
+
+        let mut rng = StdRng::seed_from_u64(42);
+        for _ in 0..100 {
+            let dist = prior.sample(&mut rng);
+            let cov = dist.cov();
+            assert!(cov.iter().enumerate().all(|(i, x)| i % 4 == 0 || *x == 0.0));
+        }
+    }
+
+    #[test]
+    fn test_fit_axis_aligned() {
+        let centers = DMatrix::from_column_slice(3, 3, &[
+            0.0, 0.0, 0.0,
+            20.0, 0.0, 0.0,
+            0.0, 0.0, 20.0,
+        ]);
+        let (x, y) = make_blobs(100, &centers, 1.0, 42);
+
+        let mut model_options = ModelOptions::<DiagNIW>::default(3);
+        model_options.outlier = None;
+        model_options.data_dist.mu = DVector::from_element(3, 5.0);
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 100;
+
+        let mut model = Model::from_options(model_options);
+        model.fit(x.clone_owned(), &fit_options, None::<MonitoringCallback<GlobalState<DiagNIW>>>).unwrap();
+
+        let (_, labels) = model.predict(x);
+        let nmi = normalized_mutual_info_score(y.as_slice(), labels.as_slice());
+        assert!(nmi > 0.9, "nmi {}", nmi);
+    }
+}
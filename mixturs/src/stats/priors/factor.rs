@@ -0,0 +1,328 @@
+use std::f64::consts::PI;
+use nalgebra::{DMatrix, DVector, Dynamic, Matrix, Storage};
+use rand::distributions::Distribution;
+use rand::Rng;
+use statrs::distribution::{MultivariateNormal, Normal};
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+use crate::stats::{ConjugatePrior, NIW, NIWParams, NIWStats, NormalConjugatePrior, PriorHyperParams};
+use crate::utils::col_broadcast_sub;
+
+/// Smallest variance kept on the diagonal of a factor covariance to keep it positive definite.
+const MIN_VARIANCE: f64 = 1e-6;
+
+/// Covariance with a "factor analysis" structure `diag(d) + W W^T` where `W` has a low rank.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FactorCovariance {
+    /// Per-feature residual variances. (n_dim)
+    pub diag: DVector<f64>,
+    /// Factor loadings. (n_dim, rank)
+    pub loadings: DMatrix<f64>,
+}
+
+impl FactorCovariance {
+    pub fn new(diag: DVector<f64>, loadings: DMatrix<f64>) -> Self {
+        assert_eq!(diag.len(), loadings.nrows());
+        Self { diag, loadings }
+    }
+
+    /// Approximates a full covariance matrix by its `rank` principal components plus the
+    /// residual diagonal. The diagonal of the approximation matches the diagonal of `cov`.
+    pub fn from_covariance(cov: &DMatrix<f64>, rank: usize) -> Self {
+        let dim = cov.nrows();
+        let rank = rank.min(dim);
+
+        let eigen = cov.clone().symmetric_eigen();
+        let mut order: Vec<usize> = (0..dim).collect();
+        order.sort_by(|&a, &b| eigen.eigenvalues[b].total_cmp(&eigen.eigenvalues[a]));
+
+        let loadings = DMatrix::from_fn(dim, rank, |i, j| {
+            let k = order[j];
+            eigen.eigenvectors[(i, k)] * eigen.eigenvalues[k].max(0.0).sqrt()
+        });
+        let diag = DVector::from_fn(dim, |i, _| {
+            (cov[(i, i)] - loadings.row(i).norm_squared()).max(MIN_VARIANCE)
+        });
+
+        Self { diag, loadings }
+    }
+
+    /// Rank of the factor loadings.
+    pub fn rank(&self) -> usize {
+        self.loadings.ncols()
+    }
+
+    /// Dense covariance matrix `diag(d) + W W^T`.
+    pub fn to_dense(&self) -> DMatrix<f64> {
+        DMatrix::from_diagonal(&self.diag) + &self.loadings * self.loadings.transpose()
+    }
+
+    /// Draws a point from a normal distribution with mean `mu` and this covariance scaled by `scale`,
+    /// using only the diagonal and the loadings (`x = mu + sqrt(scale) (sqrt(d) z_1 + W z_2)`).
+    pub fn sample_normal<R: Rng + ?Sized>(&self, mu: &DVector<f64>, scale: f64, rng: &mut R) -> DVector<f64> {
+        let normal = Normal::new(0.0, 1.0).unwrap();
+        let z = DVector::from_fn(self.rank(), |_, _| normal.sample(rng));
+        let factor = &self.loadings * z;
+        DVector::from_fn(mu.len(), |i, _| {
+            mu[i] + scale.sqrt() * (self.diag[i].sqrt() * normal.sample(rng) + factor[i])
+        })
+    }
+
+    /// Log probability density of a normal distribution with mean `mu` and this covariance for each
+    /// `x` (column) in `xs`.
+    /// Uses the Woodbury identity and matrix determinant lemma so only a `rank x rank` matrix is factorized.
+    pub fn ln_pdf_batch<S: Storage<f64, Dynamic, Dynamic>>(
+        &self,
+        mu: &DVector<f64>,
+        xs: &Matrix<f64, Dynamic, Dynamic, S>,
+    ) -> DVector<f64> {
+        let dim = mu.len();
+        let n_points = xs.ncols();
+        let d_inv = self.diag.map(|d| 1.0 / d);
+        let dvs = col_broadcast_sub(xs.clone_owned(), mu);
+
+        // Low rank correction of the quadratic form and log determinant
+        let (log_det_m, correction) = if self.rank() > 0 {
+            let scaled = DMatrix::from_fn(dim, self.rank(), |i, j| self.loadings[(i, j)] * d_inv[i]);
+            let m = DMatrix::identity(self.rank(), self.rank()) + self.loadings.transpose() * &scaled;
+            let l = m.cholesky().expect("Factor covariance must be positive definite").l();
+            let proj = l.solve_lower_triangular(&(scaled.transpose() * &dvs)).unwrap();
+
+            (
+                2.0 * l.diagonal().iter().map(|x| x.ln()).sum::<f64>(),
+                DVector::from_iterator(n_points, proj.column_iter().map(|col| col.norm_squared())),
+            )
+        } else {
+            (0.0, DVector::zeros(n_points))
+        };
+
+        let log_det = log_det_m + self.diag.iter().map(|d| d.ln()).sum::<f64>();
+        let ln_const = -0.5 * (dim as f64 * (2.0 * PI).ln() + log_det);
+
+        DVector::from_iterator(n_points, dvs.column_iter().zip(correction.iter()).map(|(dv, c)| {
+            let quad = dv.iter().zip(d_inv.iter()).map(|(v, di)| v * v * di).sum::<f64>() - c;
+            ln_const - 0.5 * quad
+        }))
+    }
+}
+
+/// The hyperparameters of the [`FactorNIW`] prior.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FactorNIWParams {
+    niw: NIWParams,
+    rank: usize,
+    factor: FactorCovariance,
+}
+
+impl FactorNIWParams {
+    /// Creates the hyperparameters and estimates the [`FactorCovariance`] of rank `rank` of the
+    /// covariance scale `psi` (plus jitter) of `niw`, which the sampled cluster covariances use.
+    pub fn new(niw: NIWParams, rank: usize) -> Self {
+        let cov = &niw.psi + DMatrix::from_diagonal_element(niw.dim(), niw.dim(), niw.jitter);
+        let factor = FactorCovariance::from_covariance(&cov, rank);
+        Self { niw, rank, factor }
+    }
+
+    /// Normal-Inverse-Wishart hyperparameters
+    pub fn niw(&self) -> &NIWParams {
+        &self.niw
+    }
+
+    /// Rank of the low rank part of the cluster covariances (0 = diagonal)
+    pub fn rank(&self) -> usize {
+        self.rank
+    }
+
+    /// Factor covariance estimated from the covariance scale of the hyperparameters.
+    pub fn factor(&self) -> &FactorCovariance {
+        &self.factor
+    }
+}
+
+impl PriorHyperParams for FactorNIWParams {
+    #[cfg(not(tarpaulin_include))]
+    fn default(dim: usize) -> Self {
+        Self::new(NIWParams::default(dim), dim.min(2))
+    }
+
+    fn dim(&self) -> usize {
+        self.niw.dim()
+    }
+
+    fn is_proper(&self) -> bool {
+        self.niw.is_proper()
+    }
+}
+
+/// Normal-Inverse-Wishart prior whose cluster covariances are constrained to the
+/// `diag(d) + W W^T` ([`FactorCovariance`]) structure with a configurable rank.
+///
+/// The covariance of a cluster is not drawn from the inverse-Wishart posterior but estimated as the factor
+/// approximation of its covariance scale once per posterior (see [`FactorNIWParams::new`]), only the mean is
+/// sampled. The points are assigned to clusters with the Woodbury identity on that factor
+/// (see [`FactorNIW::predictive_ln_pdf`]), so no `n_dim x n_dim` matrix is factorized per point batch.
+#[derive(Clone, Debug)]
+pub struct FactorNIW;
+
+impl ConjugatePrior for FactorNIW {
+    type HyperParams = FactorNIWParams;
+    type SuffStats = NIWStats;
+
+    fn posterior(
+        prior: &Self::HyperParams,
+        stats: &Self::SuffStats,
+    ) -> Self::HyperParams {
+        FactorNIWParams::new(NIW::posterior(&prior.niw, stats), prior.rank)
+    }
+
+    fn marginal_log_likelihood(
+        prior: &Self::HyperParams,
+        post: &Self::HyperParams,
+        stats: &Self::SuffStats,
+    ) -> f64 {
+        NIW::marginal_log_likelihood(&prior.niw, &post.niw, stats)
+    }
+
+    fn posterior_predictive<S: Storage<f64, Dynamic, Dynamic>>(
+        post: &Self::HyperParams,
+        data: &Matrix<f64, Dynamic, Dynamic, S>,
+    ) -> f64 {
+        NIW::posterior_predictive(&post.niw, data)
+    }
+}
+
+impl NormalConjugatePrior for FactorNIW {
+    /// Samples the mean from `N(mu, factor / kappa)` and keeps the factor covariance of the hyperparameters.
+    fn sample<R: Rng + ?Sized>(prior: &Self::HyperParams, rng: &mut R) -> MultivariateNormal {
+        let mu = prior.factor.sample_normal(&prior.niw.mu, 1.0 / prior.niw.kappa, rng);
+        MultivariateNormal::new(mu.data.into(), prior.factor.to_dense().data.into())
+            .expect("Factor covariance must be positive definite")
+    }
+
+    /// Log density under the [`FactorCovariance`] of `post` around the mean of `dist`, evaluated with the
+    /// Woodbury identity (see [`FactorCovariance::ln_pdf_batch`]).
+    fn predictive_ln_pdf(post: &Self::HyperParams, dist: &MultivariateNormal, data: DMatrix<f64>) -> DVector<f64> {
+        post.factor.ln_pdf_batch(dist.mu(), &data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, DVector};
+    use rand::distributions::Distribution;
+    use rand::prelude::StdRng;
+    use rand::SeedableRng;
+    use statrs::distribution::{MultivariateNormal, Normal};
+    use crate::{FitOptions, Model, ModelOptions, MonitoringCallback};
+    use crate::metrics::normalized_mutual_info_score;
+    use crate::state::GlobalState;
+    use crate::stats::{ContinuousBatchwise, Covariance, DiagNIW, FactorCovariance, FactorNIW, FactorNIWParams, NormalConjugatePrior, PriorHyperParams};
+    use crate::stats::tests::test_almost_mat;
+    use crate::testing::make_blobs;
+
+    fn factor_data() -> DMatrix<f64> {
+        let mut rng = StdRng::seed_from_u64(42);
+        let normal = Normal::new(0.0, 1.0).unwrap();
+        let loadings = DMatrix::from_fn(6, 2, |_, _| normal.sample(&mut rng));
+
+        let z = DMatrix::from_fn(2, 2000, |_, _| normal.sample(&mut rng));
+        let noise = DMatrix::from_fn(6, 2000, |_, _| 0.3 * normal.sample(&mut rng));
+        loadings * z + noise
+    }
+
+    #[test]
+    fn test_ln_pdf_woodbury() {
+        let data = factor_data();
+        let mean = data.column_mean();
+        let cov = FactorCovariance::from_covariance(&data.column_cov(), 2);
+
+        let dense = MultivariateNormal::new(
+            mean.clone().data.into(),
+            cov.to_dense().data.into(),
+        ).unwrap();
+
+        test_almost_mat(
+            &cov.ln_pdf_batch(&mean, &data.columns_range(0..100)),
+            &dense.batchwise_ln_pdf(data.columns_range(0..100).clone_owned()),
+            1e-6,
+        );
+    }
+
+    #[test]
+    fn test_from_covariance_rank_zero() {
+        let cov = factor_data().column_cov();
+        let diag = FactorCovariance::from_covariance(&cov, 0);
+        test_almost_mat(&diag.diag, &DVector::from_fn(6, |i, _| cov[(i, i)]), 1e-10);
+    }
+
+    #[test]
+    fn test_factor_fits_better_than_diagonal() {
+        // Two clusters generated from rank 2 factor models
+        let data = factor_data();
+        let mut shifted = data.clone();
+        shifted.add_scalar_mut(20.0);
+        let x = DMatrix::from_fn(6, 2 * data.ncols(), |i, j| {
+            if j < data.ncols() { data[(i, j)] } else { shifted[(i, j - data.ncols())] }
+        });
+
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 50;
+
+        let mut factor_options = ModelOptions::<FactorNIW>::default(6);
+        factor_options.outlier = None;
+        let mut factor = Model::from_options(factor_options);
+        factor.fit(x.clone(), &fit_options, None::<MonitoringCallback<GlobalState<FactorNIW>>>).unwrap();
+
+        let mut diag_options = ModelOptions::<DiagNIW>::default(6);
+        diag_options.outlier = None;
+        let mut diag = Model::from_options(diag_options);
+        diag.fit(x.clone(), &fit_options, None::<MonitoringCallback<GlobalState<DiagNIW>>>).unwrap();
+
+        let ll_factor = factor.mean_log_likelihood(x.clone());
+        let ll_diag = diag.mean_log_likelihood(x);
+        assert!(ll_factor > ll_diag, "factor {} <= diagonal {}", ll_factor, ll_diag);
+    }
+
+    #[test]
+    fn test_sample() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let prior = FactorNIWParams::default(4);
+        let dist = FactorNIW::sample(&prior, &mut rng);
+        assert_eq!(dist.mu().len(), 4);
+    }
+
+    #[test]
+    fn test_predictive_ln_pdf() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let data = factor_data();
+        let prior = FactorNIWParams::default(6);
+        let dist = FactorNIW::sample(&prior, &mut rng);
+        test_almost_mat(dist.cov(), &prior.factor().to_dense(), 1e-12);
+
+        let points = data.columns_range(0..100).clone_owned();
+        test_almost_mat(
+            &FactorNIW::predictive_ln_pdf(&prior, &dist, points.clone()),
+            &dist.batchwise_ln_pdf(points),
+            1e-6,
+        );
+    }
+
+    #[test]
+    fn test_fit() {
+        let centers = DMatrix::from_fn(6, 2, |i, j| if i == j { 10.0 } else { 0.0 });
+        let (x, y) = make_blobs(200, &centers, 1.0, 42);
+
+        let mut model_options = ModelOptions::<FactorNIW>::default(6);
+        model_options.outlier = None;
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 50;
+        let mut model = Model::from_options(model_options);
+        model.fit(x.clone(), &fit_options, None::<MonitoringCallback<GlobalState<FactorNIW>>>).unwrap();
+
+        let (_, labels) = model.predict(x);
+        let nmi = normalized_mutual_info_score(y.as_slice(), labels.as_slice());
+        assert!(nmi > 0.9, "nmi {}", nmi);
+    }
+}
@@ -0,0 +1,266 @@
+use nalgebra::{DMatrix, DVector};
+use rand::distributions::Distribution;
+use rand::Rng;
+use statrs::distribution::{Dirichlet, MultivariateNormal};
+use statrs::function::gamma::ln_gamma;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+use crate::error::Error;
+use crate::stats::{ConjugatePrior, ContinuousBatchwise, FromData, NIW, NIWParams, NIWStats, NormalConjugatePrior, PriorHyperParams};
+
+/// Assignment of the features (rows) of mixed-type data to the continuous and categorical groups.
+/// Categorical features hold category indices (`0.0, 1.0, ..`).
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MixedLayout {
+    /// Rows of the continuous features
+    pub continuous: Vec<usize>,
+    /// Rows of the categorical features and their number of categories
+    pub categorical: Vec<(usize, usize)>,
+}
+
+impl MixedLayout {
+    pub fn new(continuous: Vec<usize>, categorical: Vec<(usize, usize)>) -> Self {
+        Self { continuous, categorical }
+    }
+
+    /// Checks that the features of the layout exist in `data` and that the categorical features hold
+    /// category indices below their number of categories.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidData`] for a feature row out of bounds or an invalid category index, e.g. a
+    /// negative, fractional or non-finite value.
+    pub fn validate(&self, data: &DMatrix<f64>) -> Result<(), Error> {
+        let mut rows = self.continuous.iter().chain(self.categorical.iter().map(|(row, _)| row));
+        if let Some(row) = rows.find(|&&row| row >= data.nrows()) {
+            return Err(Error::InvalidData(format!("feature {} does not exist in data with {} features", row, data.nrows())));
+        }
+
+        for &(row, n_categories) in &self.categorical {
+            let invalid = data.row(row).iter().enumerate()
+                .find(|&(_, &x)| !(x >= 0.0 && x < n_categories as f64 && x.fract() == 0.0));
+            if let Some((point, x)) = invalid {
+                return Err(Error::InvalidData(format!(
+                    "categorical feature {} of point {} is {}, expected a category index below {}", row, point, x, n_categories
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The hyperparameters of the [`MixedPrior`]: a Normal-Inverse-Wishart prior over the continuous
+/// features and a Dirichlet prior over each categorical feature.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MixedParams {
+    pub layout: MixedLayout,
+    /// Hyperparameters of the continuous features
+    pub niw: NIWParams,
+    /// Dirichlet concentration of each categorical feature
+    pub alpha: Vec<DVector<f64>>,
+}
+
+impl MixedParams {
+    /// Uninformative hyperparameters for the given layout.
+    pub fn default(layout: MixedLayout) -> Self {
+        let niw = NIWParams::default(layout.continuous.len());
+        let alpha = layout.categorical.iter().map(|&(_, n)| DVector::from_element(n, 1.0)).collect();
+        Self { layout, niw, alpha }
+    }
+}
+
+/// Sufficient statistics of mixed-type data.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MixedStats {
+    /// Statistics of the continuous features
+    pub gaussian: NIWStats,
+    /// Category counts of each categorical feature
+    pub counts: Vec<DVector<f64>>,
+}
+
+impl MixedStats {
+    /// Collects the statistics of the data points (columns).
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error`] if the data does not match the layout (see [`MixedLayout::validate`]).
+    pub fn from_data(layout: &MixedLayout, data: &DMatrix<f64>) -> Result<Self, Error> {
+        layout.validate(data)?;
+        let gaussian = NIWStats::from_data(&data.select_rows(&layout.continuous));
+        let counts = layout.categorical.iter().map(|&(row, n)| {
+            let mut counts = DVector::zeros(n);
+            for &x in data.row(row).iter() {
+                counts[x as usize] += 1.0;
+            }
+            counts
+        }).collect();
+
+        Ok(Self { gaussian, counts })
+    }
+}
+
+/// Cluster distribution over mixed-type data. Features are conditionally independent given the cluster,
+/// so the log density is the sum of the gaussian and categorical log densities.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MixedDist {
+    pub layout: MixedLayout,
+    /// Distribution of the continuous features
+    pub normal: MultivariateNormal,
+    /// Category probabilities of each categorical feature
+    pub probs: Vec<DVector<f64>>,
+}
+
+impl MixedDist {
+    /// Log density of each point (column) in `data`, dispatching each feature group to its distribution.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error`] if the data does not match the layout (see [`MixedLayout::validate`]).
+    pub fn ln_pdf_batch(&self, data: &DMatrix<f64>) -> Result<DVector<f64>, Error> {
+        self.layout.validate(data)?;
+        let mut ll = if self.layout.continuous.is_empty() {
+            DVector::zeros(data.ncols())
+        } else {
+            self.normal.batchwise_ln_pdf(data.select_rows(&self.layout.continuous))
+        };
+
+        for (&(row, _), probs) in self.layout.categorical.iter().zip(&self.probs) {
+            for (i, &x) in data.row(row).iter().enumerate() {
+                ll[i] += probs[x as usize].ln();
+            }
+        }
+
+        Ok(ll)
+    }
+}
+
+/// Composite conjugate prior for mixed continuous and categorical data.
+/// Continuous features use the [`NIW`] prior while categorical features use a Dirichlet-Multinomial.
+///
+/// The cluster distributions are not multivariate normals, so the prior is used on its own rather than
+/// through [`crate::Model`].
+#[derive(Clone, Debug)]
+pub struct MixedPrior;
+
+impl MixedPrior {
+    /// Compute the posterior hyperparameters given the prior hyperparameters and the sufficient statistics.
+    pub fn posterior(prior: &MixedParams, stats: &MixedStats) -> MixedParams {
+        MixedParams {
+            layout: prior.layout.clone(),
+            niw: NIW::posterior(&prior.niw, &stats.gaussian),
+            alpha: prior.alpha.iter().zip(&stats.counts).map(|(a, c)| a + c).collect(),
+        }
+    }
+
+    /// Compute the marginal log likelihood of the data given the prior and posterior hyperparameters.
+    pub fn marginal_log_likelihood(prior: &MixedParams, post: &MixedParams, stats: &MixedStats) -> f64 {
+        let categorical: f64 = prior.alpha.iter().zip(&post.alpha).map(|(a, a_post)| {
+            ln_gamma(a.sum()) - ln_gamma(a_post.sum())
+                + a_post.iter().zip(a.iter()).map(|(ap, a)| ln_gamma(*ap) - ln_gamma(*a)).sum::<f64>()
+        }).sum();
+
+        let gaussian = if prior.layout.continuous.is_empty() {
+            0.0
+        } else {
+            NIW::marginal_log_likelihood(&prior.niw, &post.niw, &stats.gaussian)
+        };
+
+        gaussian + categorical
+    }
+
+    /// Sample a cluster distribution from the prior distribution.
+    pub fn sample<R: Rng + ?Sized>(prior: &MixedParams, rng: &mut R) -> MixedDist {
+        let normal = if prior.layout.continuous.is_empty() {
+            MultivariateNormal::new(vec![0.0], vec![1.0]).unwrap()
+        } else {
+            NIW::sample(&prior.niw, rng)
+        };
+
+        let probs = prior.alpha.iter().map(|alpha| {
+            if alpha.len() < 2 {
+                DVector::from_element(alpha.len(), 1.0)
+            } else {
+                Dirichlet::new(alpha.as_slice().to_vec()).unwrap().sample(rng)
+            }
+        }).collect();
+
+        MixedDist { layout: prior.layout.clone(), normal, probs }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, RowDVector};
+    use rand::prelude::StdRng;
+    use rand::{Rng, SeedableRng};
+    use crate::Error;
+    use crate::metrics::normalized_mutual_info_score;
+    use crate::params::thin::hard_assignment;
+    use crate::stats::{MixedLayout, MixedParams, MixedPrior, MixedStats};
+    use crate::testing::make_blobs;
+
+    #[test]
+    fn test_mixed_clustering() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let centers = DMatrix::from_column_slice(2, 2, &[0.0, 0.0, 6.0, 6.0]);
+        let (blobs, y) = make_blobs(100, &centers, 1.0, 42);
+
+        // Each blob mostly uses its own category
+        let data = DMatrix::from_fn(3, blobs.ncols(), |i, j| match i {
+            2 if rng.gen_bool(0.8) => (y[j] * 2) as f64,
+            2 => rng.gen_range(0..3) as f64,
+            _ => blobs[(i, j)],
+        });
+
+        let layout = MixedLayout::new(vec![0, 1], vec![(2, 3)]);
+        let prior = MixedParams::default(layout.clone());
+
+        let mut labels = RowDVector::from_fn(data.ncols(), |_, _| rng.gen_range(0..2));
+        for _ in 0..20 {
+            let mut ll = DMatrix::zeros(2, data.ncols());
+            for k in 0..2 {
+                let indices: Vec<usize> = (0..data.ncols()).filter(|&i| labels[i] == k).collect();
+                let stats = MixedStats::from_data(&layout, &data.select_columns(&indices)).unwrap();
+                let dist = MixedPrior::sample(&MixedPrior::posterior(&prior, &stats), &mut rng);
+                ll.row_mut(k).copy_from(&dist.ln_pdf_batch(&data).unwrap().transpose());
+            }
+            hard_assignment(&ll, labels.as_mut_slice());
+        }
+
+        let nmi = normalized_mutual_info_score(y.as_slice(), labels.as_slice());
+        assert!(nmi > 0.9, "nmi {}", nmi);
+    }
+
+    #[test]
+    fn test_marginal_log_likelihood() {
+        let layout = MixedLayout::new(vec![], vec![(0, 2)]);
+        let prior = MixedParams::default(layout.clone());
+        let data = DMatrix::from_row_slice(1, 3, &[0.0, 1.0, 1.0]);
+        let stats = MixedStats::from_data(&layout, &data).unwrap();
+        let post = MixedPrior::posterior(&prior, &stats);
+
+        // Beta-Binomial sequence probability with a uniform prior: 1! * 2! / 4!
+        let expected = (2.0f64 / 24.0).ln();
+        statrs::assert_almost_eq!(MixedPrior::marginal_log_likelihood(&prior, &post, &stats), expected, 1e-10);
+    }
+
+    #[test]
+    fn test_invalid_categories() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let layout = MixedLayout::new(vec![0], vec![(1, 3)]);
+        let prior = MixedParams::default(layout.clone());
+        let dist = MixedPrior::sample(&prior, &mut rng);
+
+        for x in [-1.0, 1.5, 3.0, f64::NAN] {
+            let data = DMatrix::from_row_slice(2, 2, &[0.0, 1.0, 2.0, x]);
+            assert!(matches!(MixedStats::from_data(&layout, &data), Err(Error::InvalidData(_))));
+            assert!(matches!(dist.ln_pdf_batch(&data), Err(Error::InvalidData(_))));
+        }
+
+        // Features missing from the data
+        let data = DMatrix::from_row_slice(1, 2, &[0.0, 1.0]);
+        assert!(matches!(MixedStats::from_data(&layout, &data), Err(Error::InvalidData(_))));
+    }
+}
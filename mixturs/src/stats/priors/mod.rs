@@ -1,13 +1,24 @@
 use std::fmt::Debug;
 use std::iter::Sum;
 use std::ops::{Add, AddAssign};
-use nalgebra::{Dynamic, Matrix, Storage};
+use nalgebra::{DMatrix, DVector, Dynamic, Matrix, Storage};
 use rand::Rng;
+use rayon::prelude::*;
 use statrs::distribution::MultivariateNormal;
+use crate::stats::ContinuousBatchwise;
+use crate::utils::DataScalar;
 
 pub use niw::*;
+pub use factor::*;
+pub use diag::*;
+pub use laplace::*;
+pub use mixed::*;
 
 mod niw;
+mod factor;
+mod diag;
+mod laplace;
+mod mixed;
 
 pub trait ConjugatePrior: Clone {
     /// The hyperparameters of the prior distribution.
@@ -67,6 +78,15 @@ pub trait ConjugatePrior: Clone {
 pub trait PriorHyperParams {
     /// Returns empty distribution parameters.
     fn default(dim: usize) -> Self;
+
+    /// Dimensionality of the distribution the parameters describe.
+    fn dim(&self) -> usize;
+
+    /// Whether the parameters describe a proper distribution that can be sampled from
+    /// (e.g. positive definite scale matrices).
+    fn is_proper(&self) -> bool {
+        true
+    }
 }
 
 pub trait FromData {
@@ -74,6 +94,90 @@ pub trait FromData {
     fn from_data<S: Storage<f64, Dynamic, Dynamic>>(
         data: &Matrix<f64, Dynamic, Dynamic, S>,
     ) -> Self;
+
+    /// Create the distribution parameters from the data where each point (column) carries a weight,
+    /// i.e. a multiplicity of how often it was observed.
+    fn from_weighted_data<S: Storage<f64, Dynamic, Dynamic>>(
+        data: &Matrix<f64, Dynamic, Dynamic, S>,
+        weights: &[f64],
+    ) -> Self;
+
+    /// Create the distribution parameters from the data points (columns) at the given indices.
+    /// Equivalent to `from_data(&data.select_columns(indices))`, implementations can accumulate the
+    /// points in place instead of copying them out first.
+    fn from_indices<S: Storage<f64, Dynamic, Dynamic>>(
+        data: &Matrix<f64, Dynamic, Dynamic, S>,
+        indices: &[usize],
+    ) -> Self where Self: Sized {
+        Self::from_data(&data.select_columns(indices))
+    }
+}
+
+/// Accumulates the weighted mean and scatter matrix of the points (columns) using the weighted
+/// variant of Welford's online algorithm. Unlike summing `weight * point` directly, every update is
+/// relative to the running mean, so the accumulation neither overflows nor loses precision for
+/// weights spanning many orders of magnitude.
+///
+/// # Arguments
+///
+/// * `data`: The data points (n_dim, n_points)
+/// * `weights`: The (non-negative) weight of each point
+///
+/// # Returns
+///
+/// A tuple of the total weight, the weighted mean and the weighted scatter matrix
+/// `sum_i w_i (x_i - mean) (x_i - mean)^T`.
+///
+/// # Example
+/// ```
+/// use nalgebra::DMatrix;
+/// use mixturs::stats::weighted_welford;
+///
+/// let data = DMatrix::from_row_slice(1, 2, &[1.0, 4.0]);
+/// let (total, mean, _) = weighted_welford(&data, &[2.0, 1.0]);
+/// assert_eq!(total, 3.0);
+/// assert_eq!(mean[0], 2.0);
+/// ```
+pub fn weighted_welford<S: Storage<f64, Dynamic, Dynamic>>(
+    data: &Matrix<f64, Dynamic, Dynamic, S>,
+    weights: &[f64],
+) -> (f64, DVector<f64>, DMatrix<f64>) {
+    assert_eq!(data.ncols(), weights.len(), "Number of weights must match the number of points");
+
+    let mut total = 0.0;
+    let mut mean = DVector::zeros(data.nrows());
+    let mut scatter = DMatrix::zeros(data.nrows(), data.nrows());
+    for (point, &weight) in data.column_iter().zip(weights) {
+        if weight <= 0.0 {
+            continue;
+        }
+
+        total += weight;
+        let delta = &point - &mean;
+        mean += &delta * (weight / total);
+        scatter += (&delta * (&point - &mean).transpose()) * weight;
+    }
+
+    (total, mean, scatter.symmetric_part())
+}
+
+/// Collects the statistics of the given points (columns) in parallel by splitting them into chunks of
+/// `chunk_size` points, collecting the statistics of each chunk on its own thread and summing them.
+///
+/// # Arguments
+///
+/// * `data`: The data points (n_dim, n_points)
+/// * `indices`: Indices of the points to collect the statistics of
+/// * `chunk_size`: Number of points per chunk
+pub fn par_stats_from_indices<S: SufficientStats + FromData + Send, T: DataScalar>(
+    data: &DMatrix<T>,
+    indices: &[usize],
+    chunk_size: usize,
+) -> S {
+    indices.par_chunks(chunk_size.max(1))
+        .map(|chunk| T::stats_from_indices::<S>(data, chunk))
+        .reduce_with(|acc, stats| acc + &stats)
+        .unwrap_or_else(|| S::from_data(&DMatrix::<f64>::zeros(data.nrows(), 0)))
 }
 
 pub trait SufficientStats: Sized + Clone
@@ -82,9 +186,28 @@ pub trait SufficientStats: Sized + Clone
 + Sum
 {
     fn n_points(&self) -> usize;
+
+    /// Dimensionality of the data the statistics are collected over.
+    fn dim(&self) -> usize;
+
+    /// Extrapolates the statistics to `n_points` points, assuming the points they were collected
+    /// over are a representative (random) subsample.
+    fn rescaled(&self, n_points: usize) -> Self;
 }
 
 pub trait NormalConjugatePrior: ConjugatePrior {
     /// Sample parameters of a normal distribution from the normal conjugate prior distribution.
     fn sample<R: Rng + ?Sized>(prior: &Self::HyperParams, rng: &mut R) -> MultivariateNormal;
+
+    /// Log density of each data point (column) under a cluster, used to assign points to clusters.
+    /// Defaults to the normal distribution `dist` sampled from the posterior `post`.
+    ///
+    /// # Arguments
+    ///
+    /// * `post`: the hyperparameters of the posterior distribution of the cluster
+    /// * `dist`: the normal distribution sampled from the posterior
+    /// * `data`: the data points (n_dim, n_points)
+    fn predictive_ln_pdf(_post: &Self::HyperParams, dist: &MultivariateNormal, data: DMatrix<f64>) -> DVector<f64> {
+        dist.batchwise_ln_pdf(data)
+    }
 }
\ No newline at end of file
@@ -5,20 +5,52 @@ use rand::distributions::Distribution;
 use rand::Rng;
 use statrs::consts::LN_PI;
 use statrs::distribution::{InverseWishart, MultivariateNormal};
-use statrs::function::gamma::mvlgamma;
+use statrs::function::gamma::{ln_gamma, mvlgamma};
 #[cfg(feature = "serde")]
 use serde::{Serialize, Deserialize};
-use crate::stats::{ConjugatePrior, Covariance, FromData, NormalConjugatePrior, PriorHyperParams, SufficientStats};
+use crate::stats::{ConjugatePrior, ContinuousBatchwise, Covariance, FromData, NormalConjugatePrior, PriorHyperParams, SufficientStats, weighted_welford};
 
 
 /// The sufficient statistics needed to compute the posterior of the
 /// [Normal-Inverse-Wishart](https://en.wikipedia.org/wiki/Normal-inverse-Wishart_distribution) prior distribution.
+///
+/// The points are summarized by their mean and their scatter around that mean (the sum of the outer
+/// products of the centered points) rather than by raw sums, so data far from the origin does not lose
+/// its spread to cancellation.
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
 pub struct NIWStats {
     pub n_points: usize,
-    pub mean_sum: DVector<f64>,
-    pub cov_sum: DMatrix<f64>,
+    pub mean: DVector<f64>,
+    pub scatter: DMatrix<f64>,
+}
+
+impl NIWStats {
+    /// Creates the statistics from the raw sums of the points and of their outer products.
+    pub fn from_sums(n_points: usize, mean_sum: DVector<f64>, cov_sum: DMatrix<f64>) -> Self {
+        if n_points == 0 {
+            return Self::empty(mean_sum.nrows());
+        }
+
+        let n = n_points as f64;
+        let mean = mean_sum / n;
+        let scatter = (cov_sum - &mean * mean.transpose() * n).symmetric_part();
+        Self { n_points, mean, scatter }
+    }
+
+    fn empty(dim: usize) -> Self {
+        Self { n_points: 0, mean: DVector::zeros(dim), scatter: DMatrix::zeros(dim, dim) }
+    }
+
+    /// The sum of the points.
+    pub fn mean_sum(&self) -> DVector<f64> {
+        &self.mean * self.n_points as f64
+    }
+
+    /// The sum of the outer products of the points.
+    pub fn cov_sum(&self) -> DMatrix<f64> {
+        &self.scatter + &self.mean * self.mean.transpose() * self.n_points as f64
+    }
 }
 
 impl Sum for NIWStats {
@@ -37,20 +69,50 @@ impl Sum for NIWStats {
 impl Default for NIWStats {
     #[cfg(not(tarpaulin_include))]
     fn default() -> Self {
-        Self {
-            n_points: 0,
-            mean_sum: DVector::zeros(1),
-            cov_sum: DMatrix::zeros(1, 1),
-        }
+        Self::empty(1)
     }
 }
 
 impl FromData for NIWStats {
     fn from_data<S: Storage<f64, Dynamic, Dynamic>>(data: &Matrix<f64, Dynamic, Dynamic, S>) -> Self {
-        let n_points = data.ncols();
-        let mean_sum = data.column_sum();
-        let cov_sum = (data * data.transpose()).symmetric_part();
-        Self { n_points, mean_sum, cov_sum }
+        if data.ncols() == 0 {
+            return Self::empty(data.nrows());
+        }
+
+        let mean = data.column_mean();
+        let mut centered = data.clone_owned();
+        for mut col in centered.column_iter_mut() {
+            col -= &mean;
+        }
+        let scatter = (&centered * centered.transpose()).symmetric_part();
+        Self { n_points: data.ncols(), mean, scatter }
+    }
+
+    /// The statistics are accumulated around the weighted mean (see [`weighted_welford`]) and the number of
+    /// points is the total weight rounded to the nearest integer.
+    fn from_weighted_data<S: Storage<f64, Dynamic, Dynamic>>(
+        data: &Matrix<f64, Dynamic, Dynamic, S>,
+        weights: &[f64],
+    ) -> Self {
+        let (total, mean, scatter) = weighted_welford(data, weights);
+        Self { n_points: total.round() as usize, mean, scatter }
+    }
+
+    /// Accumulates the points with Welford's algorithm in a single pass over the indices, without copying them.
+    fn from_indices<S: Storage<f64, Dynamic, Dynamic>>(
+        data: &Matrix<f64, Dynamic, Dynamic, S>,
+        indices: &[usize],
+    ) -> Self {
+        let mut stats = Self::empty(data.nrows());
+        for (n, &i) in indices.iter().enumerate() {
+            let x = data.column(i);
+            let delta = &x - &stats.mean;
+            stats.mean += &delta / (n + 1) as f64;
+            stats.scatter.ger(1.0, &delta, &(&x - &stats.mean), 1.0);
+        }
+        stats.n_points = indices.len();
+        stats.scatter = stats.scatter.symmetric_part();
+        stats
     }
 }
 
@@ -58,13 +120,43 @@ impl SufficientStats for NIWStats {
     fn n_points(&self) -> usize {
         self.n_points
     }
+
+    fn dim(&self) -> usize {
+        self.mean.nrows()
+    }
+
+    fn rescaled(&self, n_points: usize) -> Self {
+        if self.n_points == 0 {
+            return self.clone();
+        }
+
+        let factor = n_points as f64 / self.n_points as f64;
+        Self {
+            n_points,
+            mean: self.mean.clone(),
+            scatter: &self.scatter * factor,
+        }
+    }
 }
 
+/// Merges the means and scatters of two groups of points (Chan et al.), without going through raw sums.
 impl<'a> AddAssign<&'a NIWStats> for NIWStats {
     fn add_assign(&mut self, rhs: &'a NIWStats) {
+        if rhs.n_points == 0 {
+            return;
+        }
+        if self.n_points == 0 {
+            *self = rhs.clone();
+            return;
+        }
+
+        let (n_lhs, n_rhs) = (self.n_points as f64, rhs.n_points as f64);
+        let n = n_lhs + n_rhs;
+        let delta = &rhs.mean - &self.mean;
+        self.scatter += &rhs.scatter;
+        self.scatter.ger(n_lhs * n_rhs / n, &delta, &delta, 1.0);
+        self.mean += delta * (n_rhs / n);
         self.n_points += rhs.n_points;
-        self.mean_sum += &rhs.mean_sum;
-        self.cov_sum += &rhs.cov_sum;
     }
 }
 
@@ -77,6 +169,23 @@ impl<'a> Add<&'a NIWStats> for NIWStats {
     }
 }
 
+/// Density used to assign points to a cluster given its posterior.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Predictive {
+    /// The normal distribution sampled from the posterior.
+    PlugIn,
+    /// The posterior predictive [multivariate Student-t](https://en.wikipedia.org/wiki/Multivariate_t-distribution)
+    /// distribution, which accounts for the uncertainty of the parameters of small clusters.
+    StudentT,
+}
+
+impl Default for Predictive {
+    fn default() -> Self {
+        Predictive::PlugIn
+    }
+}
+
 /// The hyperparameters of the [Normal-Inverse-Wishart](https://en.wikipedia.org/wiki/Normal-inverse-Wishart_distribution) prior distribution.
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
@@ -85,6 +194,21 @@ pub struct NIWParams {
     pub mu: DVector<f64>,
     pub nu: f64,
     pub psi: DMatrix<f64>,
+    /// Ridge term added to the covariance diagonal before it is factorized, keeps clusters of (nearly)
+    /// collinear points from producing a singular covariance
+    #[cfg_attr(feature = "serde", serde(default = "default_jitter"))]
+    pub jitter: f64,
+    /// Density used to assign points to clusters with these (posterior) parameters
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub predictive: Predictive,
+}
+
+/// Default [`NIWParams::jitter`], negligible next to the variance of well-conditioned clusters.
+pub const DEFAULT_JITTER: f64 = 1e-10;
+
+#[cfg(feature = "serde")]
+fn default_jitter() -> f64 {
+    DEFAULT_JITTER
 }
 
 impl PriorHyperParams for NIWParams {
@@ -95,13 +219,74 @@ impl PriorHyperParams for NIWParams {
             mu: DVector::zeros(dim),
             nu: dim as f64 + 3.0,
             psi: DMatrix::identity(dim, dim),
+            jitter: DEFAULT_JITTER,
+            predictive: Predictive::default(),
         }
     }
+
+    fn dim(&self) -> usize {
+        self.mu.len()
+    }
+
+    fn is_proper(&self) -> bool {
+        self.kappa > 0.0
+            && self.nu > self.mu.len() as f64 - 1.0
+            && self.mu.iter().all(|x| x.is_finite())
+            && self.psi.clone().cholesky().is_some()
+    }
 }
 
 impl NIWParams {
     pub fn new(kappa: f64, mu: DVector<f64>, nu: f64, psi: DMatrix<f64>) -> Self {
-        NIWParams { kappa, mu, nu, psi }
+        NIWParams { kappa, mu, nu, psi, jitter: DEFAULT_JITTER, predictive: Predictive::default() }
+    }
+
+    /// Sets the ridge term added to the covariance diagonal (see [`NIWParams::jitter`]).
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Sets the density used to assign points to clusters (see [`NIWParams::predictive`]).
+    pub fn with_predictive(mut self, predictive: Predictive) -> Self {
+        self.predictive = predictive;
+        self
+    }
+
+    /// Log density of each point (column) in `data` under the posterior predictive distribution of the
+    /// parameters, a multivariate Student-t with `nu - dim + 1` degrees of freedom centered at `mu`.
+    ///
+    /// # Example
+    /// ```
+    /// use nalgebra::{DMatrix, DVector};
+    /// use mixturs::stats::{NIWParams, PriorHyperParams};
+    ///
+    /// let params = NIWParams::default(2);
+    /// let ll = params.student_t_ln_pdf(&DMatrix::from_column_slice(2, 2, &[0.0, 0.0, 5.0, 5.0]));
+    /// assert!(ll[0] > ll[1]);
+    /// ```
+    pub fn student_t_ln_pdf(&self, data: &DMatrix<f64>) -> DVector<f64> {
+        let dim = self.dim() as f64;
+        let df = self.nu - dim + 1.0;
+        let scale = self.regularize(&self.psi * (self.nu * (self.kappa + 1.0) / (self.kappa * df)));
+        let l = match scale.cholesky() {
+            Some(chol) => chol.l(),
+            None => return DVector::from_element(data.ncols(), f64::NEG_INFINITY),
+        };
+
+        let mut centered = data.clone();
+        for mut col in centered.column_iter_mut() {
+            col -= &self.mu;
+        }
+        let z = l.solve_lower_triangular(&centered).expect("Cholesky factor is invertible");
+
+        let ln_det = 2.0 * l.diagonal().iter().map(|x| x.ln()).sum::<f64>();
+        let norm = ln_gamma((df + dim) / 2.0) - ln_gamma(df / 2.0)
+            - dim / 2.0 * (df * std::f64::consts::PI).ln()
+            - ln_det / 2.0;
+        DVector::from_iterator(data.ncols(), z.column_iter().map(|z| {
+            norm - (df + dim) / 2.0 * (z.norm_squared() / df).ln_1p()
+        }))
     }
 
     pub fn from_data<S: Storage<f64, Dynamic, Dynamic>>(
@@ -111,7 +296,14 @@ impl NIWParams {
     ) -> Self {
         let mu = data.column_mean();
         let psi = data.column_cov();
-        Self { kappa, mu, nu, psi }
+        Self { kappa, mu, nu, psi, jitter: DEFAULT_JITTER, predictive: Predictive::default() }
+    }
+
+    /// Adds the jitter to the diagonal of the given covariance.
+    fn regularize(&self, mut cov: DMatrix<f64>) -> DMatrix<f64> {
+        let diagonal = cov.diagonal().add_scalar(self.jitter);
+        cov.set_diagonal(&diagonal);
+        cov
     }
 }
 
@@ -142,16 +334,16 @@ impl ConjugatePrior for NIW {
         let n_points = stats.n_points as f64;
         let kappa = prior.kappa + n_points;
         let nu = prior.nu + n_points;
-        let mu = (&prior.mu * prior.kappa + &stats.mean_sum) / kappa;
+        let mu = (&prior.mu * prior.kappa + &stats.mean * n_points) / kappa;
+        let diff = &stats.mean - &prior.mu;
         let psi =
             (prior.nu * &prior.psi
-                + prior.kappa * &prior.mu * &prior.mu.transpose()
-                - kappa * &mu * &mu.transpose()
-                + &stats.cov_sum
+                + &stats.scatter
+                + &diff * diff.transpose() * (prior.kappa * n_points / kappa)
             ) / nu;
         let psi = (&psi + &psi.transpose()) / 2.0;
 
-        NIWParams { kappa, mu, nu, psi }
+        NIWParams { kappa, mu, nu, psi, jitter: prior.jitter, predictive: prior.predictive }
     }
 
     /// Compute the marginal log likelihood of the data given the prior and posterior hyperparameters.
@@ -172,7 +364,7 @@ impl ConjugatePrior for NIW {
         post: &Self::HyperParams,
         stats: &Self::SuffStats,
     ) -> f64 {
-        let dim = stats.mean_sum.nrows() as f64;
+        let dim = stats.mean.nrows() as f64;
         -(stats.n_points as f64) * dim * 0.5 * LN_PI
             + mvlgamma(dim as i64, post.nu / 2.0)
             - mvlgamma(dim as i64, prior.nu / 2.0)
@@ -194,10 +386,10 @@ impl ConjugatePrior for NIW {
     /// The posterior predictive log likelihood of the data given the posterior distribution hyperparameters
     ///
     fn posterior_predictive<S: Storage<f64, Dynamic, Dynamic>>(
-        _post: &Self::HyperParams,
-        _data: &Matrix<f64, Dynamic, Dynamic, S>,
+        post: &Self::HyperParams,
+        data: &Matrix<f64, Dynamic, Dynamic, S>,
     ) -> f64 {
-        todo!()
+        post.student_t_ln_pdf(&data.clone_owned()).sum()
     }
 }
 
@@ -205,13 +397,20 @@ impl NormalConjugatePrior for NIW {
     fn sample<R: Rng + ?Sized>(prior: &Self::HyperParams, rng: &mut R) -> MultivariateNormal {
         prior.sample(rng)
     }
+
+    fn predictive_ln_pdf(post: &Self::HyperParams, dist: &MultivariateNormal, data: DMatrix<f64>) -> DVector<f64> {
+        match post.predictive {
+            Predictive::PlugIn => dist.batchwise_ln_pdf(data),
+            Predictive::StudentT => post.student_t_ln_pdf(&data),
+        }
+    }
 }
 
 impl Distribution<MultivariateNormal> for NIWParams {
     /// Sample parameters of a normal distribution from the normal conjugate prior distribution.
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> MultivariateNormal {
-        let w = InverseWishart::new(self.nu, self.nu * &self.psi).unwrap();
-        let sigma = w.sample(rng);
+        let w = InverseWishart::new(self.nu, self.regularize(self.nu * &self.psi)).unwrap();
+        let sigma = self.regularize(w.sample(rng));
         let mv = MultivariateNormal::new(
             self.mu.clone().data.into(),
             (sigma.clone() / self.kappa).data.into(),
@@ -234,7 +433,9 @@ mod tests {
     use rand::prelude::StdRng;
     use rand::SeedableRng;
     use statrs::assert_almost_eq;
-    use crate::stats::{ConjugatePrior, FromData, NIW, NIWParams, NIWStats};
+    use statrs::distribution::{Continuous, MultivariateNormal, StudentsT};
+    use crate::stats::{ConjugatePrior, ContinuousBatchwise, FromData, NIW, NIWParams, NIWStats, NormalConjugatePrior, Predictive, PriorHyperParams, weighted_welford};
+    use crate::testing::make_blobs;
     use crate::stats::tests::{points1, test_almost_mat};
 
     fn points0() -> DMatrix<f64> {
@@ -258,16 +459,89 @@ mod tests {
         let stats = NIWStats::from_data(&points);
 
         assert_eq!(stats.n_points, 10);
-        test_almost_mat(&stats.mean_sum, &DVector::from_row_slice(&[
+        test_almost_mat(&stats.mean_sum(), &DVector::from_row_slice(&[
             4.05119994468987, 3.5643000551499426, 3.878299990668893,
         ]), 1e-4);
-        test_almost_mat(&stats.cov_sum, &DMatrix::from_row_slice(3, 3, &[
+        test_almost_mat(&stats.cov_sum(), &DMatrix::from_row_slice(3, 3, &[
             2.1903512039472037, 1.5586958104640931, 1.5794594489964737,
             1.5586958104640931, 2.331203265432532, 1.498778618943676,
             1.5794594489964737, 1.498778618943676, 2.3294769049360573,
         ]), 1e-4);
     }
 
+    #[test]
+    fn test_stats_from_indices() {
+        let points = points1();
+        let indices = [7, 2, 2, 9, 0];
+
+        let stats = NIWStats::from_indices(&points, &indices);
+        let selected = NIWStats::from_data(&points.select_columns(&indices));
+        assert_eq!(stats.n_points, selected.n_points);
+        test_almost_mat(&stats.mean, &selected.mean, 1e-12);
+        test_almost_mat(&stats.scatter, &selected.scatter, 1e-12);
+
+        // Views of the data work without copying
+        let view = NIWStats::from_indices(&points.columns_range(2..8), &[0, 5]);
+        test_almost_mat(&view.mean, &NIWStats::from_indices(&points, &[2, 7]).mean, 1e-12);
+        assert_eq!(NIWStats::from_indices(&points, &[]).n_points, 0);
+    }
+
+    #[test]
+    fn test_weighted_stats() {
+        let points = points1();
+        let weights = [1.0, 2.0, 1.0, 3.0, 1.0, 1.0, 2.0, 1.0, 1.0, 1.0];
+        let indices: Vec<usize> = weights.iter().enumerate()
+            .flat_map(|(i, &w)| std::iter::repeat(i).take(w as usize))
+            .collect();
+
+        let stats = NIWStats::from_weighted_data(&points, &weights);
+        let expanded = NIWStats::from_data(&points.select_columns(&indices));
+
+        assert_eq!(stats.n_points, expanded.n_points);
+        test_almost_mat(&stats.mean, &expanded.mean, 1e-10);
+        test_almost_mat(&stats.scatter, &expanded.scatter, 1e-10);
+    }
+
+    #[test]
+    fn test_weighted_welford_stable() {
+        // Weights spanning hundreds of orders of magnitude, their products with the points overflow
+        let points = DMatrix::from_row_slice(1, 3, &[1e200, 5.0, 3e200]);
+        let weights = [1e150, 1e-150, 1e150];
+        let exact = 2e200;
+
+        let naive = points.row(0).iter().zip(&weights).map(|(x, w)| x * w).sum::<f64>()
+            / weights.iter().sum::<f64>();
+        let (_, stable, _) = weighted_welford(&points, &weights);
+
+        let error = |value: f64| if value.is_finite() { (value - exact).abs() } else { f64::INFINITY };
+        assert!(error(stable[0]) < error(naive));
+        assert!(error(stable[0]) / exact < 1e-12);
+    }
+
+    #[test]
+    fn test_posterior_large_offset() {
+        // Points with unit spread far from the origin, raw sums of squares lose the spread to cancellation
+        let offset = 1e8;
+        let points = points1().add_scalar(-0.5) * 2.0;
+        let shifted = points.add_scalar(offset);
+        let weights = [1.0, 2.0, 1.0, 3.0, 1.0, 1.0, 2.0, 1.0, 1.0, 1.0];
+
+        let prior = NIWParams::new(1.0, DVector::zeros(3), 6.0, DMatrix::identity(3, 3));
+        let shifted_prior = NIWParams::new(1.0, DVector::from_element(3, offset), 6.0, DMatrix::identity(3, 3));
+        let expected = NIW::posterior(&prior, &NIWStats::from_weighted_data(&points, &weights));
+
+        let indices = [0, 1, 1, 2, 3, 3, 3, 4, 5, 6, 6, 7, 8, 9];
+        let weighted = NIWStats::from_weighted_data(&shifted, &weights);
+        let indexed = NIWStats::from_indices(&shifted, &indices);
+        let merged = NIWStats::from_indices(&shifted, &indices[..5]) + &NIWStats::from_indices(&shifted, &indices[5..]);
+        for stats in [weighted, indexed, merged] {
+            let post = NIW::posterior(&shifted_prior, &stats);
+            assert_eq!(post.nu, expected.nu);
+            test_almost_mat(&post.mu.add_scalar(-offset), &expected.mu, 1e-6);
+            test_almost_mat(&post.psi, &expected.psi, 1e-6);
+        }
+    }
+
     #[test]
     fn test_posterior() {
         let prior = NIWParams::from_data(1.0, 4.0, &points0());
@@ -298,6 +572,61 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_jitter_collinear() {
+        // All points lie on a line, so their scatter matrix is singular
+        let points = DMatrix::from_fn(2, 20, |r, c| (c as f64 / 10.0) * (r as f64 + 1.0));
+        let prior = NIWParams::from_data(1.0, 4.0, &points).with_jitter(1e-6);
+        assert!(prior.psi.determinant().abs() < 1e-12);
+
+        let post = NIW::posterior(&prior, &NIWStats::from_data(&points));
+        assert_eq!(post.jitter, 1e-6);
+
+        let mut rng = StdRng::seed_from_u64(42);
+        for params in [&prior, &post] {
+            let dist = params.sample(&mut rng);
+            assert!(dist.batchwise_ln_pdf(points.clone()).iter().all(|ll| ll.is_finite()));
+        }
+    }
+
+    #[test]
+    fn test_student_t_ln_pdf() {
+        let params = NIWParams::new(2.0, DVector::from_element(1, 0.5), 6.0, DMatrix::from_element(1, 1, 1.5))
+            .with_jitter(0.0);
+        let t = StudentsT::new(0.5, (1.5 * 3.0 / 2.0f64).sqrt(), 6.0).unwrap();
+
+        let data = DMatrix::from_row_slice(1, 3, &[-2.0, 0.5, 4.0]);
+        let ll = params.student_t_ln_pdf(&data);
+        for (i, x) in data.iter().enumerate() {
+            assert_almost_eq!(ll[i], t.ln_pdf(*x), 1e-10);
+        }
+        assert_almost_eq!(NIW::posterior_predictive(&params, &data), ll.sum(), 1e-10);
+    }
+
+    #[test]
+    fn test_student_t_vs_plug_in() {
+        let prior = NIWParams::default(2).with_predictive(Predictive::StudentT);
+        let x = DMatrix::from_column_slice(2, 1, &[4.0, 4.0]);
+
+        // Difference between the Student-t and the plug-in gaussian with the posterior mean covariance
+        let ll_diff = |points: &DMatrix<f64>| {
+            let post = NIW::posterior(&prior, &NIWStats::from_data(points));
+            let cov = &post.psi * (post.nu / (post.nu - 3.0));
+            let plug_in = MultivariateNormal::new(post.mu.as_slice().to_vec(), cov.data.into()).unwrap();
+
+            let student_t = NIW::predictive_ln_pdf(&post, &plug_in, x.clone());
+            assert_eq!(student_t, post.student_t_ln_pdf(&x));
+            let plug_in_post = post.clone().with_predictive(Predictive::PlugIn);
+            student_t[0] - NIW::predictive_ln_pdf(&plug_in_post, &plug_in, x.clone())[0]
+        };
+
+        // The heavier tails matter for a small cluster, but vanish for a large one
+        let small = DMatrix::from_column_slice(2, 3, &[-1.0, 0.0, 1.0, 0.0, 0.0, 1.0]);
+        let (large, _) = make_blobs(1000, &DMatrix::zeros(2, 1), 1.0, 42);
+        assert!(ll_diff(&small) > 2.0, "small cluster difference {}", ll_diff(&small));
+        assert!(ll_diff(&large).abs() < 1.0, "large cluster difference {}", ll_diff(&large));
+    }
+
     #[test]
     fn test_aggregate() {
         let stats1 = NIWStats::from_data(&points0());
@@ -305,10 +634,10 @@ mod tests {
         let stats = stats1.clone() + &stats2;
 
         assert_eq!(stats.n_points, 20);
-        test_almost_mat(&stats.mean_sum, &DVector::from_row_slice(&[
+        test_almost_mat(&stats.mean_sum(), &DVector::from_row_slice(&[
             9.963999958708882, 7.527400041464716, 10.110599996522069
         ]), 1e-4);
-        test_almost_mat(&stats.cov_sum, &DMatrix::from_row_slice(3, 3, &[
+        test_almost_mat(&stats.cov_sum(), &DMatrix::from_row_slice(3, 3, &[
             6.81164, 3.89157, 4.91515,
             3.89157, 4.39323, 3.67878,
             4.91515, 3.67878, 6.77574,
@@ -1,10 +1,85 @@
 use std::marker::PhantomData;
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand::rngs::SmallRng;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
 use statrs::function::gamma::ln_gamma;
 use crate::params::clusters::{ClusterParams, SuperClusterParams};
 use crate::stats::NormalConjugatePrior;
 use crate::utils::each_ref;
 
+/// Checkpointable random stream for the split/merge proposals.
+///
+/// A fresh generator is derived from the seed and a step counter for every proposal step, so a run
+/// resumed from a stored state makes the same draws as an uninterrupted run.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SplitRngState {
+    /// Seed of the split/merge random stream
+    pub seed: u64,
+    /// Number of proposal steps drawn from the stream so far
+    pub counter: u64,
+}
+
+impl SplitRngState {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, counter: 0 }
+    }
+
+    /// Derives the random number generator for the next proposal step and advances the counter.
+    pub fn next_rng(&mut self) -> SmallRng {
+        let rng = SmallRng::seed_from_u64(self.seed ^ self.counter.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        self.counter += 1;
+        rng
+    }
+}
+
+/// Cumulative counts of the proposed and accepted split/merge moves. Low acceptance rates indicate a
+/// poorly tuned sampler (e.g. a too low [`ModelOptions::split_merge_temperature`](crate::ModelOptions)).
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AcceptanceStats {
+    pub splits_proposed: usize,
+    pub splits_accepted: usize,
+    pub merges_proposed: usize,
+    pub merges_accepted: usize,
+}
+
+impl AcceptanceStats {
+    /// Fraction of the proposed splits that were accepted, `None` if no split was proposed.
+    pub fn split_rate(&self) -> Option<f64> {
+        (self.splits_proposed > 0).then(|| self.splits_accepted as f64 / self.splits_proposed as f64)
+    }
+
+    /// Fraction of the proposed merges that were accepted, `None` if no merge was proposed.
+    pub fn merge_rate(&self) -> Option<f64> {
+        (self.merges_proposed > 0).then(|| self.merges_accepted as f64 / self.merges_proposed as f64)
+    }
+}
+
+/// Kind of a split/merge proposal.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitMergeKind {
+    Split,
+    Merge,
+}
+
+/// A single split/merge proposal made during the fit, recorded when
+/// [`FitOptions::record_split_merge_events`](crate::FitOptions) is set.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SplitMergeEvent {
+    /// Iteration the proposal was made in
+    pub iter: usize,
+    pub kind: SplitMergeKind,
+    /// Indices of the proposed cluster (split) or of the two proposed clusters (merge) at the time of the proposal
+    pub clusters: Vec<usize>,
+    /// Log Hastings ratio of the proposal, before it is divided by the temperature
+    pub log_ratio: f64,
+    pub accepted: bool,
+}
+
 pub struct SplitMerge<P: NormalConjugatePrior>(PhantomData<P>);
 
 impl<P: NormalConjugatePrior> SplitMerge<P> {
@@ -27,18 +102,32 @@ impl<P: NormalConjugatePrior> SplitMerge<P> {
             - ln_gamma(prim.n_points() as f64) - ll
     }
 
+    /// Metropolis-Hastings test for splitting the supercluster into its auxiliary clusters.
+    /// The log acceptance ratio is divided by `temperature`, so higher temperatures accept more proposals.
     pub fn should_split<R: Rng>(
         params: &SuperClusterParams<P>,
         alpha: f64,
+        temperature: f64,
         rng: &mut R,
     ) -> bool {
+        matches!(Self::propose_split(params, alpha, temperature, rng), Some((_, true)))
+    }
+
+    /// Like [`SplitMerge::should_split`], but also returns the log acceptance ratio (before it is divided by
+    /// `temperature`). Returns `None` without a test if an auxiliary cluster is empty.
+    pub fn propose_split<R: Rng>(
+        params: &SuperClusterParams<P>,
+        alpha: f64,
+        temperature: f64,
+        rng: &mut R,
+    ) -> Option<(f64, bool)> {
         if params.aux.iter().any(|c| c.n_points() == 0) {
-            return false;
+            return None;
         }
 
         let h_split = Self::compute_log_h_split(&params.prim, each_ref(&params.aux), alpha);
 
-        h_split > rng.gen_range(0.0..1.0_f64).ln()
+        Some((h_split, h_split / temperature > rng.gen_range(0.0..1.0_f64).ln()))
     }
 
 
@@ -56,19 +145,34 @@ impl<P: NormalConjugatePrior> SplitMerge<P> {
             + ln_gamma(aux[1].n_points() as f64 + 0.5 * alpha)
     }
 
+    /// Metropolis-Hastings test for merging two clusters.
+    /// The log acceptance ratio is divided by `temperature`, so higher temperatures accept more proposals.
     pub fn should_merge<R: Rng>(
         prim_l: &ClusterParams<P>,
         prim_r: &ClusterParams<P>,
         alpha: f64,
+        temperature: f64,
         rng: &mut R,
     ) -> bool {
+        Self::propose_merge(prim_l, prim_r, alpha, temperature, rng).1
+    }
+
+    /// Like [`SplitMerge::should_merge`], but also returns the log acceptance ratio (before it is divided by
+    /// `temperature`).
+    pub fn propose_merge<R: Rng>(
+        prim_l: &ClusterParams<P>,
+        prim_r: &ClusterParams<P>,
+        alpha: f64,
+        temperature: f64,
+        rng: &mut R,
+    ) -> (f64, bool) {
         let prim_stats = prim_l.stats.clone() + &prim_r.stats;
         let prim_post = P::posterior(&prim_l.prior, &prim_stats);
         let prim = ClusterParams::new(prim_l.prior.clone(), prim_post, prim_stats, prim_l.dist.clone());
 
         let h_merge = Self::compute_log_h_merge(&prim, [prim_l, prim_r], alpha);
 
-        h_merge > rng.gen_range(0.0..1.0_f64).ln()
+        (h_merge, h_merge / temperature > rng.gen_range(0.0..1.0_f64).ln())
     }
 }
 
@@ -76,10 +180,12 @@ impl<P: NormalConjugatePrior> SplitMerge<P> {
 #[cfg(test)]
 mod tests {
     use nalgebra::{DMatrix, DVector};
+    use rand::prelude::StdRng;
+    use rand::{Rng, SeedableRng};
     use statrs::assert_almost_eq;
     use statrs::distribution::MultivariateNormal;
-    use crate::params::clusters::ClusterParams;
-    use crate::stats::{NIW, NIWParams, NIWStats, PriorHyperParams, SplitMerge};
+    use crate::params::clusters::{ClusterParams, SuperClusterParams};
+    use crate::stats::{FromData, NIW, NIWParams, NIWStats, PriorHyperParams, SplitMerge, SplitRngState};
     use crate::utils::each_ref;
 
     #[test]
@@ -87,11 +193,11 @@ mod tests {
         let prim_params = ClusterParams::<NIW>::new(
             NIWParams::default(2),
             NIWParams::default(2),
-            NIWStats {
-                n_points: 10000,
-                mean_sum: DVector::from_vec(vec![53421.49186074734, 766.8183973431587]),
-                cov_sum: DMatrix::from_vec(2, 2, vec![445925.3699037639, 19567.09899818292, 19567.09899818292, 1146748.4105307986]),
-            },
+            NIWStats::from_sums(
+                10000,
+                DVector::from_vec(vec![53421.49186074734, 766.8183973431587]),
+                DMatrix::from_vec(2, 2, vec![445925.3699037639, 19567.09899818292, 19567.09899818292, 1146748.4105307986]),
+            ),
             MultivariateNormal::new(
                 DVector::zeros(2).data.into(),
                 DMatrix::identity(2, 2).data.into()
@@ -102,11 +208,11 @@ mod tests {
             ClusterParams::<NIW>::new(
                 NIWParams::default(2),
                 NIWParams::default(2),
-                NIWStats {
-                    n_points: 3095,
-                    mean_sum: DVector::from_vec(vec![-953.5522114038467, -7957.599519848824]),
-                    cov_sum: DMatrix::from_vec(2, 2, vec![2135.7392811890277, 1127.9554153340587, 1127.9554153340587, 27002.447292295525]),
-                },
+                NIWStats::from_sums(
+                    3095,
+                    DVector::from_vec(vec![-953.5522114038467, -7957.599519848824]),
+                    DMatrix::from_vec(2, 2, vec![2135.7392811890277, 1127.9554153340587, 1127.9554153340587, 27002.447292295525]),
+                ),
                 MultivariateNormal::new(
                     DVector::zeros(2).data.into(),
                     DMatrix::identity(2, 2).data.into()
@@ -115,11 +221,11 @@ mod tests {
             ClusterParams::<NIW>::new(
                 NIWParams::default(2),
                 NIWParams::default(2),
-                NIWStats {
-                    n_points: 6905,
-                    mean_sum: DVector::from_vec(vec![54375.044072151184, 8724.417917191982]),
-                    cov_sum: DMatrix::from_vec(2, 2, vec![443789.63062257477, 18439.143582848767, 18439.143582848767, 1119745.9632385024]),
-                },
+                NIWStats::from_sums(
+                    6905,
+                    DVector::from_vec(vec![54375.044072151184, 8724.417917191982]),
+                    DMatrix::from_vec(2, 2, vec![443789.63062257477, 18439.143582848767, 18439.143582848767, 1119745.9632385024]),
+                ),
                 MultivariateNormal::new(
                     DVector::zeros(2).data.into(),
                     DMatrix::identity(2, 2).data.into()
@@ -133,4 +239,43 @@ mod tests {
             1e-6
         )
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_split_rng_resume() {
+        let mut state = SplitRngState::new(42);
+        let uninterrupted: Vec<u64> = (0..10).map(|_| state.next_rng().gen()).collect();
+
+        let mut state = SplitRngState::new(42);
+        let mut resumed: Vec<u64> = (0..4).map(|_| state.next_rng().gen()).collect();
+        let mut checkpoint = state;
+        resumed.extend((4..10).map(|_| checkpoint.next_rng().gen::<u64>()));
+
+        assert_eq!(uninterrupted, resumed);
+        assert_eq!(checkpoint.counter, 10);
+    }
+
+    #[test]
+    fn test_split_temperature() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let data = DMatrix::from_fn(2, 10, |_, _| rng.gen_range(-1.0..1.0));
+
+        // Splitting a single blob in two arbitrary halves is unfavourable
+        let prim = ClusterParams::<NIW>::new(
+            NIWParams::default(2),
+            NIWParams::default(2),
+            NIWStats::from_data(&data),
+            MultivariateNormal::new(vec![0.0, 0.0], vec![1.0, 0.0, 0.0, 1.0]).unwrap(),
+        );
+        let mut params = SuperClusterParams::from_split_params(prim, 10.0, 20, &mut rng);
+        params.aux[0].stats = NIWStats::from_data(&data.columns_range(0..5));
+        params.aux[1].stats = NIWStats::from_data(&data.columns_range(5..10));
+
+        let accepted = |temperature: f64| {
+            let mut rng = StdRng::seed_from_u64(7);
+            (0..1000).filter(|_| SplitMerge::should_split(&params, 10.0, temperature, &mut rng)).count()
+        };
+
+        let (cold, hot) = (accepted(1.0), accepted(10.0));
+        assert!(hot > cold, "hot {} <= cold {}", hot, cold);
+    }
+}
@@ -0,0 +1,230 @@
+use nalgebra::{DMatrix, DVector, RowDVector};
+use rand::distributions::Distribution;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use statrs::distribution::Normal;
+
+/// Generates isotropic gaussian blobs around the given centers.
+///
+/// # Arguments
+///
+/// * `n_per`: Number of points per blob.
+/// * `centers`: Centers of the blobs. (n_dim, n_blobs)
+/// * `spread`: Standard deviation of each blob.
+/// * `seed`: Seed for the random number generator.
+///
+/// # Returns
+///
+/// Tuple containing:
+/// * The generated points (n_dim, n_per * n_blobs)
+/// * The blob label of each point (n_per * n_blobs)
+///
+/// # Examples
+///
+/// ```
+/// use nalgebra::DMatrix;
+/// use mixturs::testing::make_blobs;
+///
+/// let centers = DMatrix::from_column_slice(2, 3, &[0.0, 0.0, 10.0, 0.0, 0.0, 10.0]);
+/// let (x, y) = make_blobs(50, &centers, 0.5, 42);
+/// assert_eq!(x.shape(), (2, 150));
+/// assert_eq!(y[149], 2);
+/// ```
+pub fn make_blobs(
+    n_per: usize,
+    centers: &DMatrix<f64>,
+    spread: f64,
+    seed: u64,
+) -> (DMatrix<f64>, RowDVector<usize>) {
+    let mut rng = SmallRng::seed_from_u64(seed);
+    let noise = Normal::new(0.0, spread).unwrap();
+
+    let n_points = n_per * centers.ncols();
+    let labels = RowDVector::from_fn(n_points, |_, i| i / n_per);
+    let data = DMatrix::from_fn(centers.nrows(), n_points, |d, i| {
+        centers[(d, labels[i])] + noise.sample(&mut rng)
+    });
+
+    (data, labels)
+}
+
+/// Minimum distance (in blob standard deviations) between an outlier and any blob center.
+pub const OUTLIER_MIN_SPREADS: f64 = 5.0;
+
+/// Generates isotropic gaussian blobs around the given centers together with uniformly scattered outliers.
+/// Outliers are drawn uniformly from a box of half-width `outlier_spread` around the mean of the centers,
+/// rejecting points within [`OUTLIER_MIN_SPREADS`] standard deviations of any blob center.
+///
+/// # Arguments
+///
+/// * `n_per`: Number of points per blob.
+/// * `centers`: Centers of the blobs. (n_dim, n_blobs)
+/// * `spread`: Standard deviation of each blob.
+/// * `n_outliers`: Number of outliers.
+/// * `outlier_spread`: Half-width of the box the outliers are scattered in.
+/// * `seed`: Seed for the random number generator.
+///
+/// # Returns
+///
+/// Tuple containing:
+/// * The generated points, blobs first followed by the outliers (n_dim, n_per * n_blobs + n_outliers)
+/// * The blob label of each point, outliers are labelled `n_blobs` (n_per * n_blobs + n_outliers)
+///
+/// # Panics
+///
+/// Panics if the box is too small to contain any point far enough from the blobs.
+///
+/// # Examples
+///
+/// ```
+/// use nalgebra::DMatrix;
+/// use mixturs::testing::make_blobs_with_outliers;
+///
+/// let centers = DMatrix::from_column_slice(2, 2, &[0.0, 0.0, 10.0, 0.0]);
+/// let (x, y) = make_blobs_with_outliers(50, &centers, 0.5, 10, 30.0, 42);
+/// assert_eq!(x.shape(), (2, 110));
+/// assert_eq!(y[109], 2);
+/// ```
+pub fn make_blobs_with_outliers(
+    n_per: usize,
+    centers: &DMatrix<f64>,
+    spread: f64,
+    n_outliers: usize,
+    outlier_spread: f64,
+    seed: u64,
+) -> (DMatrix<f64>, RowDVector<usize>) {
+    let (blobs, blob_labels) = make_blobs(n_per, centers, spread, seed);
+    let mut rng = SmallRng::seed_from_u64(seed.wrapping_add(1));
+
+    let center = centers.column_mean();
+    let min_dist = OUTLIER_MIN_SPREADS * spread;
+    let max_center_dist = centers.column_iter().map(|c| (c - &center).norm()).fold(0.0, f64::max);
+    assert!(
+        outlier_spread > min_dist + max_center_dist,
+        "Outlier spread is too small to place outliers away from the blobs"
+    );
+
+    let n_blob_points = blobs.ncols();
+    let mut data = DMatrix::zeros(centers.nrows(), n_blob_points + n_outliers);
+    data.columns_range_mut(0..n_blob_points).copy_from(&blobs);
+    for i in 0..n_outliers {
+        let point = loop {
+            let point = DVector::from_fn(centers.nrows(), |d, _| {
+                center[d] + rng.gen_range(-outlier_spread..=outlier_spread)
+            });
+            if centers.column_iter().all(|c| (&point - c).norm() > min_dist) {
+                break point;
+            }
+        };
+        data.set_column(n_blob_points + i, &point);
+    }
+
+    let labels = RowDVector::from_fn(n_blob_points + n_outliers, |_, i| {
+        if i < n_blob_points { blob_labels[i] } else { centers.ncols() }
+    });
+
+    (data, labels)
+}
+
+/// Creates a model that is fitted to the given mixture parameters (see [`crate::state::GlobalState::from_sklearn_params`]).
+///
+/// # Arguments
+///
+/// * `means`: Mean of each component. (n_components, n_dim)
+/// * `covariances`: Covariance matrix of each component. (n_dim, n_dim)
+/// * `weights`: Mixture weight of each component.
+/// * `model_options`: Options of the model.
+#[cfg(test)]
+pub(crate) fn sklearn_model<P: crate::stats::NormalConjugatePrior>(
+    means: &DMatrix<f64>,
+    covariances: &[DMatrix<f64>],
+    weights: &[f64],
+    model_options: crate::ModelOptions<P>,
+) -> crate::Model<P> {
+    let global = crate::state::GlobalState::from_sklearn_params(
+        means, covariances, weights, &model_options, &mut rand::rngs::StdRng::seed_from_u64(42),
+    ).unwrap();
+    crate::Model::from_global(model_options, global)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use nalgebra::DMatrix;
+    use crate::{FitOptions, Model, ModelOptions, MonitoringCallback, NIW};
+    use crate::state::GlobalState;
+    use crate::testing::{make_blobs, make_blobs_with_outliers, OUTLIER_MIN_SPREADS};
+
+    /// Golden values of the reference fit, relative to the crate root. Set `MIXTURS_UPDATE_GOLDEN=1` to
+    /// (re-)record them after an intended change of the sampler.
+    const GOLDEN_FIT: &str = "tests/golden/fit_blobs.txt";
+
+    /// 64-bit FNV-1a hash, stable across platforms and compiler versions (unlike `DefaultHasher`).
+    fn fnv1a(bytes: impl IntoIterator<Item=u8>) -> u64 {
+        bytes.into_iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+    }
+
+    /// Fits the reference dataset and summarizes the result as the cluster count and a hash of the
+    /// sorted cluster means (rounded to 1e-6).
+    fn reference_fit() -> String {
+        let centers = DMatrix::from_column_slice(2, 4, &[0.0, 0.0, 8.0, 0.0, 0.0, 8.0, 8.0, 8.0]);
+        let (x, _) = make_blobs(100, &centers, 1.0, 42);
+
+        let mut model_options = ModelOptions::<NIW>::default(2);
+        model_options.outlier = None;
+        let mut fit_options = FitOptions::default();
+        fit_options.iters = 50;
+        fit_options.seed = 42;
+
+        let mut model = Model::from_options(model_options);
+        model.fit(x, &fit_options, None::<MonitoringCallback<GlobalState<NIW>>>).unwrap();
+
+        let mut means: Vec<Vec<i64>> = model.cluster_modes().iter()
+            .map(|mu| mu.iter().map(|x| (x * 1e6).round() as i64).collect())
+            .collect();
+        means.sort();
+        let hash = fnv1a(means.iter().flatten().flat_map(|x| x.to_le_bytes()));
+
+        format!("n_clusters={}\nmeans_hash={:016x}\n", means.len(), hash)
+    }
+
+    #[test]
+    fn test_make_blobs_with_outliers() {
+        let centers = DMatrix::from_column_slice(2, 3, &[0.0, 0.0, 10.0, 0.0, 0.0, 10.0]);
+        let (x, y) = make_blobs_with_outliers(100, &centers, 0.5, 20, 40.0, 42);
+
+        assert_eq!(x.ncols(), 320);
+        assert_eq!(y.len(), 320);
+        assert_eq!(y.iter().filter(|&&l| l == 3).count(), 20);
+
+        for point in x.columns_range(300..320).column_iter() {
+            for center in centers.column_iter() {
+                assert!((point - center).norm() > OUTLIER_MIN_SPREADS * 0.5);
+            }
+        }
+    }
+
+    #[test]
+    fn test_golden_fit() {
+        let summary = reference_fit();
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(GOLDEN_FIT);
+
+        if std::env::var_os("MIXTURS_UPDATE_GOLDEN").is_some() {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, &summary).unwrap();
+            return;
+        }
+
+        let golden = fs::read_to_string(&path).unwrap_or_else(|e| panic!(
+            "Missing golden file {} ({}), record it with MIXTURS_UPDATE_GOLDEN=1", path.display(), e
+        ));
+        assert_eq!(summary, golden, "Fit result changed, re-record {} if the change is intended", GOLDEN_FIT);
+    }
+
+    #[test]
+    fn test_fnv1a() {
+        assert_eq!(fnv1a(*b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(*b"a"), 0xaf63dc4c8601ec8c);
+    }
+}
@@ -0,0 +1,112 @@
+use std::io::{BufRead, Lines};
+use nalgebra::DMatrix;
+use crate::Error;
+
+/// Reads numeric CSV data in chunks of at most `batch_rows` rows, so files larger than memory can be
+/// processed. Each row is a data point, so each chunk is a matrix of shape (n_columns, n_rows).
+///
+/// The first line is skipped as a header if it contains a non-numeric value. Empty lines are ignored.
+/// All rows must have the same number of columns, otherwise an [`Error::InvalidData`] is returned.
+///
+/// # Example:
+/// ```
+/// use std::io::Cursor;
+/// use mixturs::utils::CsvChunks;
+///
+/// let csv = "x,y\n1.0,2.0\n3.0,4.0\n5.0,6.0\n";
+/// let chunks: Vec<_> = CsvChunks::new(Cursor::new(csv), 2).collect::<Result<_, _>>().unwrap();
+/// assert_eq!(chunks.len(), 2);
+/// assert_eq!(chunks[0].shape(), (2, 2));
+/// assert_eq!(chunks[1][(1, 0)], 6.0);
+/// ```
+pub struct CsvChunks<R: BufRead> {
+    lines: Lines<R>,
+    batch_rows: usize,
+    n_cols: Option<usize>,
+    line: usize,
+}
+
+impl<R: BufRead> CsvChunks<R> {
+    pub fn new(reader: R, batch_rows: usize) -> Self {
+        assert!(batch_rows > 0, "Batch size must be positive");
+        Self { lines: reader.lines(), batch_rows, n_cols: None, line: 0 }
+    }
+
+    /// Number of columns of the data, known after the first row is read.
+    pub fn n_cols(&self) -> Option<usize> {
+        self.n_cols
+    }
+}
+
+fn parse_row(line: &str) -> Option<Vec<f64>> {
+    line.split(',').map(|value| value.trim().parse().ok()).collect()
+}
+
+impl<R: BufRead> Iterator for CsvChunks<R> {
+    type Item = Result<DMatrix<f64>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut values = Vec::new();
+        let mut n_rows = 0;
+        while n_rows < self.batch_rows {
+            let line = match self.lines.next() {
+                None => break,
+                Some(Err(e)) => return Some(Err(e.into())),
+                Some(Ok(line)) => line,
+            };
+            self.line += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let row = match parse_row(&line) {
+                Some(row) => row,
+                None if self.line == 1 => continue,
+                None => return Some(Err(Error::InvalidData(
+                    format!("line {} contains a non-numeric value", self.line)
+                ))),
+            };
+            match self.n_cols {
+                Some(n_cols) if n_cols != row.len() => return Some(Err(Error::InvalidData(
+                    format!("line {} has {} columns, expected {}", self.line, row.len(), n_cols)
+                ))),
+                _ => self.n_cols = Some(row.len()),
+            }
+
+            values.extend(row);
+            n_rows += 1;
+        }
+
+        if n_rows == 0 {
+            None
+        } else {
+            Some(Ok(DMatrix::from_vec(self.n_cols.unwrap(), n_rows, values)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use crate::Error;
+    use super::CsvChunks;
+
+    #[test]
+    fn test_csv_chunks() {
+        let csv = "1,2\n\n3,4\n5,6\n";
+        let chunks: Vec<_> = CsvChunks::new(Cursor::new(csv), 2).collect::<Result<_, _>>().unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].as_slice(), &[1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(chunks[1].as_slice(), &[5.0, 6.0]);
+    }
+
+    #[test]
+    fn test_csv_chunks_errors() {
+        let mut chunks = CsvChunks::new(Cursor::new("a,b\n1,2\n3\n"), 1);
+        assert!(chunks.next().unwrap().is_ok());
+        assert!(matches!(chunks.next(), Some(Err(Error::InvalidData(_)))));
+
+        let mut chunks = CsvChunks::new(Cursor::new("1,2\nx,3\n"), 10);
+        assert!(matches!(chunks.next(), Some(Err(Error::InvalidData(_)))));
+    }
+}
@@ -1,17 +1,192 @@
+use std::borrow::Cow;
 use std::mem::MaybeUninit;
-use nalgebra::{DefaultAllocator, Dim, DMatrix, Matrix, Scalar, Storage, StorageMut, U1, RealField};
+use std::ops::Range;
+use nalgebra::{DefaultAllocator, Dim, DMatrix, DVector, Matrix, Scalar, Storage, StorageMut, U1, RealField};
 use std::collections::HashMap;
 use std::hash::Hash;
 use nalgebra::allocator::Allocator;
+use crate::error::Error;
+use crate::stats::FromData;
+
+/// Integer type used to store cluster labels. Narrower types (e.g. `u32`) reduce the memory used by the
+/// labels of large datasets.
+pub trait Label: Scalar + Copy + Default + Hash + Eq + Ord + Send + Sync {
+    /// Converts a cluster index to a label. The index must fit in the label type.
+    fn from_usize(value: usize) -> Self;
+
+    /// Converts the label to a cluster index.
+    fn as_usize(self) -> usize;
+}
+
+macro_rules! impl_label {
+    ($($t:ty),*) => {
+        $(
+            impl Label for $t {
+                #[inline]
+                fn from_usize(value: usize) -> Self {
+                    debug_assert!(value <= <$t>::MAX as usize, "label {} does not fit in {}", value, stringify!($t));
+                    value as $t
+                }
+
+                #[inline]
+                fn as_usize(self) -> usize {
+                    self as usize
+                }
+            }
+        )*
+    };
+}
+
+impl_label!(usize, u32, u16);
+
+/// Floating point type used to store the data points. Narrower types (e.g. `f32`) halve the memory used by
+/// large datasets; all computations on the data are done in `f64`.
+pub trait DataScalar: Scalar + Copy + Default + PartialOrd + Send + Sync {
+    /// Converts the value to `f64`.
+    fn to_f64(self) -> f64;
+
+    /// Converts an `f64` to the data type, possibly losing precision.
+    fn from_f64(value: f64) -> Self;
+
+    /// Number of points converted to `f64` at once when the whole data is processed, see [`f64_column_blocks`].
+    const CONVERT_BLOCK: usize;
+
+    /// Returns the data as an `f64` matrix, borrowing it if it already is one.
+    fn as_f64(data: &DMatrix<Self>) -> Cow<'_, DMatrix<f64>>;
+
+    /// Returns the columns in `range` as an `f64` matrix, borrowing the data if it already is one and
+    /// the range covers all of it.
+    fn columns_f64(data: &DMatrix<Self>, range: Range<usize>) -> Cow<'_, DMatrix<f64>>;
+
+    /// Gathers the given columns of the data into an `f64` matrix.
+    fn select_f64(data: &DMatrix<Self>, indices: &[usize]) -> DMatrix<f64>;
+
+    /// Collects the statistics of the given columns of the data, in place with [`FromData::from_indices`]
+    /// if it already is `f64`.
+    fn stats_from_indices<S: FromData>(data: &DMatrix<Self>, indices: &[usize]) -> S;
+}
+
+impl DataScalar for f64 {
+    #[inline]
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    #[inline]
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+
+    const CONVERT_BLOCK: usize = usize::MAX;
+
+    fn as_f64(data: &DMatrix<Self>) -> Cow<'_, DMatrix<f64>> {
+        Cow::Borrowed(data)
+    }
+
+    fn columns_f64(data: &DMatrix<Self>, range: Range<usize>) -> Cow<'_, DMatrix<f64>> {
+        if range.start == 0 && range.end == data.ncols() {
+            Cow::Borrowed(data)
+        } else {
+            Cow::Owned(data.columns_range(range).into_owned())
+        }
+    }
+
+    fn select_f64(data: &DMatrix<Self>, indices: &[usize]) -> DMatrix<f64> {
+        data.select_columns(indices)
+    }
+
+    fn stats_from_indices<S: FromData>(data: &DMatrix<Self>, indices: &[usize]) -> S {
+        S::from_indices(data, indices)
+    }
+}
+
+impl DataScalar for f32 {
+    #[inline]
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    #[inline]
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+
+    const CONVERT_BLOCK: usize = 4096;
+
+    fn as_f64(data: &DMatrix<Self>) -> Cow<'_, DMatrix<f64>> {
+        Cow::Owned(data.map(|x| x as f64))
+    }
+
+    fn columns_f64(data: &DMatrix<Self>, range: Range<usize>) -> Cow<'_, DMatrix<f64>> {
+        Cow::Owned(data.columns_range(range).map(|x| x as f64))
+    }
+
+    fn select_f64(data: &DMatrix<Self>, indices: &[usize]) -> DMatrix<f64> {
+        DMatrix::from_fn(data.nrows(), indices.len(), |r, c| data[(r, indices[c])] as f64)
+    }
+
+    fn stats_from_indices<S: FromData>(data: &DMatrix<Self>, indices: &[usize]) -> S {
+        S::from_data(&Self::select_f64(data, indices))
+    }
+}
+
+/// Splits the data points (columns) into consecutive blocks of at most [`DataScalar::CONVERT_BLOCK`] points
+/// converted to `f64`, so narrower data types are never converted as a whole. `f64` data is returned as
+/// a single borrowed block.
+///
+/// # Returns
+///
+/// An iterator over the index of the first point of each block and the block itself.
+pub fn f64_column_blocks<T: DataScalar>(data: &DMatrix<T>) -> impl Iterator<Item=(usize, Cow<'_, DMatrix<f64>>)> {
+    let n_points = data.ncols();
+    let block = T::CONVERT_BLOCK.max(1);
+    (0..n_points).step_by(block)
+        .map(move |start| (start, T::columns_f64(data, start..start.saturating_add(block).min(n_points))))
+}
+
+/// Checks that the data points (columns) are usable for fitting.
+///
+/// # Errors
+/// * [`Error::DimensionMismatch`] if `dim` is given and the number of features (rows) differs from it.
+/// * [`Error::EmptyData`] if there are no points.
+/// * [`Error::NonFiniteData`] with the first NaN or infinite value found.
+///
+/// # Example
+/// ```
+/// use nalgebra::DMatrix;
+/// use mixturs::Error;
+/// use mixturs::utils::validate_data;
+///
+/// let mut data = DMatrix::<f64>::zeros(2, 3);
+/// assert!(validate_data(&data, Some(2)).is_ok());
+/// assert_eq!(validate_data(&data, Some(3)), Err(Error::DimensionMismatch { expected: 3, got: 2 }));
+///
+/// data[(1, 2)] = f64::INFINITY;
+/// assert_eq!(validate_data(&data, None), Err(Error::NonFiniteData { feature: 1, point: 2 }));
+/// ```
+pub fn validate_data<T: DataScalar>(data: &DMatrix<T>, dim: Option<usize>) -> Result<(), Error> {
+    if let Some(dim) = dim {
+        if data.nrows() != dim {
+            return Err(Error::DimensionMismatch { expected: dim, got: data.nrows() });
+        }
+    }
+    if data.ncols() == 0 {
+        return Err(Error::EmptyData);
+    }
+    if let Some(i) = data.iter().position(|x| !x.to_f64().is_finite()) {
+        return Err(Error::NonFiniteData { feature: i % data.nrows(), point: i / data.nrows() });
+    }
+    Ok(())
+}
 
 pub trait Iterutils : Iterator {
     fn bincounts(self, n_bins: usize) -> Vec<usize>
         where
             Self: Sized,
-            Self::Item: Into<usize>,
+            Self::Item: Label,
     {
         let mut counts = vec![0; n_bins];
-        self.for_each(|item| counts[item.into()] += 1);
+        self.for_each(|item| counts[item.as_usize()] += 1);
         counts
     }
 }
@@ -96,6 +271,49 @@ pub fn unique_with_indices<T: Copy + Hash + Eq + Ord>(data: &[T], sorted: bool)
     (unique, unique_index)
 }
 
+/// Finds the exact-duplicate columns of `data`, where -0.0 and 0.0 compare equal.
+///
+/// # Returns
+///
+/// Tuple containing:
+/// * Index of the first occurrence of each unique column, in order of appearance
+/// * Index of the unique column of each column
+pub fn unique_columns<T: DataScalar>(data: &DMatrix<T>) -> (Vec<usize>, Vec<usize>) {
+    // Adding zero maps -0.0 to 0.0 so both compare equal
+    let bits: Vec<u64> = data.iter().map(|x| (x.to_f64() + 0.0).to_bits()).collect();
+    let columns: Vec<&[u64]> = bits.chunks(data.nrows().max(1)).collect();
+    let (_, indices) = unique_with_indices(&columns, false);
+
+    let mut first = Vec::new();
+    for (i, &u) in indices.iter().enumerate() {
+        if u == first.len() {
+            first.push(i);
+        }
+    }
+    (first, indices)
+}
+
+/// Counts the occurrences of each label into a dense vector indexed by the label. For dense small-integer
+/// labels, such as cluster labels, this is faster than counting into a hash map (e.g. `Itertools::counts`),
+/// which remains the way to count arbitrary values.
+///
+/// # Returns:
+///
+/// The count of each label `0..=max`, empty if there are no labels.
+///
+/// # Example:
+/// ```
+/// use nalgebra::DVector;
+/// use mixturs::utils::bincount_vec;
+///
+/// let labels = DVector::from_vec(vec![0, 2, 2, 3, 0, 2]);
+/// assert_eq!(bincount_vec(&labels), vec![2, 0, 3, 1]);
+/// ```
+pub fn bincount_vec(labels: &DVector<usize>) -> Vec<usize> {
+    let n_bins = labels.iter().max().map_or(0, |&max| max + 1);
+    labels.iter().cloned().bincounts(n_bins)
+}
+
 /// Normalizes the log probabilities in the given matrix in a row-wise manner.
 ///
 /// # Arguments:
@@ -134,6 +352,141 @@ pub fn row_normalize_log_weights(
     weights
 }
 
+/// Vectorized [`row_normalize_log_weights`] with identical output. The row maxima are reduced column by
+/// column, following the column-major storage, instead of scanning one (strided) row at a time.
+pub fn row_normalize_log_weights_batch(
+    mut weights: DMatrix<f64>
+) -> DMatrix<f64> {
+    let mut maxima = DVector::from_element(weights.nrows(), f64::NEG_INFINITY);
+    for col in weights.column_iter() {
+        maxima.zip_apply(&col, |max, x| *max = max.max(x));
+    }
+    for mut col in weights.column_iter_mut() {
+        col.zip_apply(&maxima, |x, max| *x = (*x - max).exp());
+    }
+    weights
+}
+
+/// Index of the largest value.
+///
+/// * Ties resolve to the lowest index, so a row of equal values (e.g. all `-inf`, when every log density
+/// underflows for a far-away point) returns the first one.
+/// * NaN values are skipped, regardless of where they appear.
+///
+/// # Returns:
+///
+/// The index of the largest value, `None` if there are no values or all of them are NaN.
+///
+/// # Example:
+/// ```
+/// use mixturs::utils::argmax;
+///
+/// assert_eq!(argmax([1.0, 3.0, 3.0, 2.0]), Some(1));
+/// assert_eq!(argmax([f64::NAN, 1.0, f64::NAN, 2.0]), Some(3));
+/// assert_eq!(argmax([f64::NEG_INFINITY, f64::NEG_INFINITY]), Some(0));
+/// assert_eq!(argmax([f64::NAN]), None);
+/// ```
+pub fn argmax(values: impl IntoIterator<Item=f64>) -> Option<usize> {
+    argmax_by_ties(values, |_, _| false)
+}
+
+/// Index of the largest value, like [`argmax`] but resolving exact ties to the tied index with the largest
+/// weight before the lowest index.
+///
+/// # Arguments:
+///
+/// * `values`: The values
+/// * `weights`: The weight of each index, at least as many as there are values
+///
+/// # Example:
+/// ```
+/// use mixturs::utils::argmax_weighted_ties;
+///
+/// assert_eq!(argmax_weighted_ties([3.0, 1.0, 3.0], &[0.2, 0.3, 0.5]), Some(2));
+/// assert_eq!(argmax_weighted_ties([3.0, 1.0, 3.0], &[0.5, 0.3, 0.5]), Some(0));
+/// ```
+pub fn argmax_weighted_ties(values: impl IntoIterator<Item=f64>, weights: &[f64]) -> Option<usize> {
+    argmax_by_ties(values, |i, best| weights[i] > weights[best])
+}
+
+/// Index of the largest non-NaN value, replacing a tied best index if `prefer(i, best)` holds.
+fn argmax_by_ties(values: impl IntoIterator<Item=f64>, prefer: impl Fn(usize, usize) -> bool) -> Option<usize> {
+    let mut best: Option<(usize, f64)> = None;
+    for (i, x) in values.into_iter().enumerate() {
+        if x.is_nan() {
+            continue;
+        }
+        let better = match best {
+            Some((b, max)) => x > max || (x == max && prefer(i, b)),
+            None => true,
+        };
+        if better {
+            best = Some((i, x));
+        }
+    }
+    best.map(|(i, _)| i)
+}
+
+/// Computes `ln(sum(exp(x)))` of the given log values without overflow, in a single pass by rescaling the
+/// running sum whenever a new maximum is encountered.
+///
+/// # Returns:
+///
+/// The log of the sum of the exponentials, `-inf` if there are no values or all of them are `-inf`.
+///
+/// # Example:
+/// ```
+/// use statrs::assert_almost_eq;
+/// use mixturs::utils::log_sum_exp;
+///
+/// assert_almost_eq!(log_sum_exp([1000.0, 1000.0]), 1000.0 + 2.0f64.ln(), 1e-12);
+/// assert_eq!(log_sum_exp([f64::NEG_INFINITY]), f64::NEG_INFINITY);
+/// ```
+pub fn log_sum_exp(values: impl IntoIterator<Item=f64>) -> f64 {
+    let mut max = f64::NEG_INFINITY;
+    let mut sum = 0.0;
+    for x in values {
+        if x == f64::NEG_INFINITY {
+            continue;
+        }
+        if x > max {
+            sum = sum * (max - x).exp() + 1.0;
+            max = x;
+        } else {
+            sum += (x - max).exp();
+        }
+    }
+
+    if sum == 0.0 { f64::NEG_INFINITY } else { max + sum.ln() }
+}
+
+/// Converts the log weights in the given matrix to probabilities in a row-wise manner, such that each
+/// row sums to one. Unlike [`row_normalize_log_weights`], the rows are divided by their sum
+/// (computed with [`log_sum_exp`]). Rows without any finite weight become uniform.
+///
+/// # Example:
+/// ```
+/// use nalgebra::DMatrix;
+/// use mixturs::utils::row_softmax;
+///
+/// let probs = row_softmax(DMatrix::from_row_slice(1, 2, &[0.0, 3.0f64.ln()]));
+/// assert!((probs[(0, 0)] - 0.25).abs() < 1e-12);
+/// ```
+pub fn row_softmax(
+    mut weights: DMatrix<f64>
+) -> DMatrix<f64> {
+    let n_cols = weights.ncols();
+    for mut row in weights.row_iter_mut() {
+        let lse = log_sum_exp(row.iter().cloned());
+        if lse == f64::NEG_INFINITY {
+            row.fill(1.0 / n_cols as f64);
+        } else {
+            row.apply(|x| *x = (*x - lse).exp());
+        }
+    }
+    weights
+}
+
 /// Normalizes the log probabilities in the given matrix in a column-wise manner.
 ///
 /// # Arguments:
@@ -172,6 +525,52 @@ pub fn col_normalize_log_weights(
     weights
 }
 
+/// Vectorized [`col_normalize_log_weights`] with identical output. The column maxima are reduced at once
+/// and the whole matrix is exponentiated in a single pass.
+pub fn col_normalize_log_weights_batch(
+    mut weights: DMatrix<f64>
+) -> DMatrix<f64> {
+    let maxima = weights.compress_rows(|col| col.max());
+    for (mut col, max) in weights.column_iter_mut().zip(maxima.iter()) {
+        col.add_scalar_mut(-max);
+    }
+    weights.apply(|x| *x = x.exp());
+    weights
+}
+
+/// Expected number of points assigned to each cluster under soft assignment. The responsibilities of each
+/// point (column) are normalized to sum to one (see [`col_normalize_log_weights`]) and summed over the points.
+/// Points with zero likelihood under every cluster (an entirely `-inf` column) are not counted.
+///
+/// # Arguments:
+///
+/// * `ll`: The log-likelihood of each point under each cluster, e.g. from
+/// [`MixtureParams::log_likelihood`](crate::params::thin::MixtureParams::log_likelihood). (n_clusters, n_points)
+///
+/// # Returns:
+///
+/// The expected number of points of each cluster. (n_clusters)
+///
+/// # Example:
+/// ```
+/// use nalgebra::DMatrix;
+/// use mixturs::utils::expected_cluster_counts;
+///
+/// let ll = DMatrix::from_row_slice(2, 2, &[0.0, 0.0, 0.0, f64::NEG_INFINITY]);
+/// assert_eq!(expected_cluster_counts(&ll).as_slice(), &[1.5, 0.5]);
+/// ```
+pub fn expected_cluster_counts(ll: &DMatrix<f64>) -> DVector<f64> {
+    let weights = col_normalize_log_weights(ll.clone());
+    let mut counts = DVector::zeros(ll.nrows());
+    for col in weights.column_iter() {
+        let sum = col.sum();
+        if sum.is_finite() && sum > 0.0 {
+            counts += col / sum;
+        }
+    }
+    counts
+}
+
 pub fn col_broadcast_add<Real, R, C, SM, SV>(
     arr: Matrix<Real, R, C, SM>,
     vec: &Matrix<Real, C, U1, SV>,
@@ -328,12 +727,73 @@ pub fn group_sort<T>(
     (indices, offsets)
 }
 
+/// Run-length encodes a sequence of labels.
+///
+/// # Arguments
+///
+/// * `labels`: The labels to encode, e.g. the cluster assignments of sequential data.
+///
+/// # Returns
+///
+/// A vector of (label, run_length) pairs in order of appearance.
+///
+/// # Examples
+/// ```
+/// use nalgebra::RowDVector;
+/// use mixturs::utils::rle;
+///
+/// let labels = RowDVector::from_row_slice(&[0usize, 0, 0, 1, 1, 2]);
+/// assert_eq!(rle(labels.as_slice()), vec![(0, 3), (1, 2), (2, 1)]);
+/// ```
+pub fn rle(labels: &[usize]) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for &label in labels {
+        match runs.last_mut() {
+            Some((last, length)) if *last == label => *length += 1,
+            _ => runs.push((label, 1)),
+        }
+    }
+    runs
+}
+
 #[cfg(test)]
 mod tests {
+    use itertools::Itertools;
     use nalgebra::{DMatrix, DVector};
+    use rand::prelude::StdRng;
+    use rand::{Rng, SeedableRng};
     use crate::stats::tests::test_almost_mat;
-    use crate::utils::{col_broadcast_sub, Iterutils};
+    use crate::utils::{argmax, argmax_weighted_ties, bincount_vec, col_broadcast_sub, col_normalize_log_weights, Iterutils, log_sum_exp};
     use crate::utils::data::{col_broadcast_add};
+    use crate::utils::f64_column_blocks;
+
+    #[test]
+    fn test_argmax() {
+        assert_eq!(argmax([0.5, -1.0, 2.0, 1.0]), Some(2));
+        assert_eq!(argmax([2.0, 1.0, 2.0]), Some(0));
+        assert_eq!(argmax(Vec::<f64>::new()), None);
+
+        // Every density underflowed
+        assert_eq!(argmax([f64::NEG_INFINITY; 3]), Some(0));
+        assert_eq!(argmax([f64::NEG_INFINITY, f64::NAN, -1e300]), Some(2));
+
+        // NaN entries are skipped wherever they appear
+        assert_eq!(argmax([f64::NAN, 1.0, 0.0]), Some(1));
+        assert_eq!(argmax([0.0, 1.0, f64::NAN]), Some(1));
+        assert_eq!(argmax([1.0, f64::NAN, 3.0]), Some(2));
+        assert_eq!(argmax([f64::NAN, f64::NAN]), None);
+    }
+
+    #[test]
+    fn test_argmax_weighted_ties() {
+        let weights = [0.3, 0.3, 0.4];
+        assert_eq!(argmax_weighted_ties([-1.0, -2.0, -1.0], &weights), Some(2));
+        assert_eq!(argmax_weighted_ties([-1.0, -1.0, -3.0], &weights), Some(0));
+        assert_eq!(argmax_weighted_ties([-4.0, -3.0, -3.5], &weights), Some(1));
+        assert_eq!(argmax_weighted_ties([f64::NEG_INFINITY; 3], &weights), Some(2));
+        assert_eq!(argmax_weighted_ties([f64::NAN, -2.0, -2.0], &weights), Some(2));
+        assert_eq!(argmax_weighted_ties([f64::NAN; 3], &weights), None);
+    }
 
     #[test]
     fn test_unique_with_indices() {
@@ -343,11 +803,38 @@ mod tests {
         assert_eq!(unique_index, vec![0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 0]);
     }
 
+    #[test]
+    fn test_rle() {
+        assert_eq!(super::rle(&[0, 0, 0, 1, 1, 2]), vec![(0, 3), (1, 2), (2, 1)]);
+        assert_eq!(super::rle(&[1, 0, 1, 1]), vec![(1, 1), (0, 1), (1, 2)]);
+        assert!(super::rle(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_bincount_vec() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let labels = DVector::from_fn(1000, |_, _| rng.gen_range(0..20usize));
+
+        let dense = bincount_vec(&labels);
+        let sparse = labels.iter().cloned().counts();
+        assert_eq!(dense.len(), labels.iter().max().unwrap() + 1);
+        for (label, &count) in dense.iter().enumerate() {
+            assert_eq!(count, sparse.get(&label).cloned().unwrap_or(0));
+        }
+        assert_eq!(dense.iter().sum::<usize>(), 1000);
+
+        assert!(bincount_vec(&DVector::zeros(0)).is_empty());
+    }
+
     #[test]
     fn test_bincount() {
         let data = [1usize, 1, 2, 2, 3, 3, 4, 4, 5, 5];
         let counts = data.into_iter().bincounts(6);
         assert_eq!(counts, vec![0, 2, 2, 2, 2, 2]);
+
+        let data = [1u32, 1, 2, 2, 3, 3, 4, 4, 5, 5];
+        let counts = data.into_iter().bincounts(6);
+        assert_eq!(counts, vec![0, 2, 2, 2, 2, 2]);
     }
 
     #[test]
@@ -379,6 +866,60 @@ mod tests {
         ]), 1e-4);
     }
 
+    #[test]
+    fn test_expected_cluster_counts() {
+        let ll = DMatrix::from_row_slice(3, 3, &[
+            0.0, 1.0f64.ln(), f64::NEG_INFINITY,
+            0.0, 3.0f64.ln(), f64::NEG_INFINITY,
+            0.0, f64::NEG_INFINITY, f64::NEG_INFINITY,
+        ]);
+        let counts = super::expected_cluster_counts(&ll);
+        test_almost_mat(&counts, &DVector::from_row_slice(&[1.0 / 3.0 + 0.25, 1.0 / 3.0 + 0.75, 1.0 / 3.0]), 1e-12);
+        statrs::assert_almost_eq!(counts.sum(), 2.0, 1e-12);
+
+        // Shifting the log-likelihoods of a point does not change its responsibilities
+        let shifted = DMatrix::from_fn(3, 3, |k, i| ll[(k, i)] + 1000.0 * i as f64);
+        test_almost_mat(&super::expected_cluster_counts(&shifted), &counts, 1e-12);
+    }
+
+    #[test]
+    fn test_row_softmax() {
+        let weights = DMatrix::from_row_slice(3, 3, &[
+            1.0f64, 2.0, 4.0,
+            -1000.0, -1001.0, -1002.0,
+            f64::NEG_INFINITY, 0.0, 800.0,
+        ]);
+        let probs = super::row_softmax(weights);
+        for row in probs.row_iter() {
+            statrs::assert_almost_eq!(row.sum(), 1.0, 1e-12);
+        }
+        statrs::assert_almost_eq!(probs[(0, 2)], 4.0f64.exp() / (1.0f64.exp() + 2.0f64.exp() + 4.0f64.exp()), 1e-12);
+        assert_eq!(probs[(2, 0)], 0.0);
+
+        let probs = super::row_softmax(DMatrix::from_element(1, 4, f64::NEG_INFINITY));
+        assert_eq!(probs, DMatrix::from_element(1, 4, 0.25));
+    }
+
+    #[test]
+    fn test_f64_column_blocks() {
+        let data = DMatrix::from_fn(2, 5000, |r, c| (r * 5000 + c) as f64);
+
+        // f64 data is borrowed as a whole
+        let blocks: Vec<_> = f64_column_blocks(&data).collect();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].0, 0);
+        assert_eq!(&*blocks[0].1, &data);
+
+        // f32 data is converted in blocks covering every point once
+        let data_f32 = data.map(|x| x as f32);
+        let blocks: Vec<_> = f64_column_blocks(&data_f32).collect();
+        assert_eq!(blocks.iter().map(|(start, _)| *start).collect::<Vec<_>>(), vec![0, 4096]);
+        assert_eq!(blocks.iter().map(|(_, b)| b.ncols()).sum::<usize>(), 5000);
+        for (start, block) in blocks {
+            assert_eq!(&*block, &data.columns(start, block.ncols()).into_owned());
+        }
+    }
+
     #[test]
     fn test_broadcast_add() {
         let x = DMatrix::<f64>::from_vec(3, 4, vec![
@@ -445,4 +986,26 @@ mod tests {
         println!("{} {}", out, expected);
         test_almost_mat(&out, &expected, 1e-10);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_normalize_log_weights_batch() {
+        let weights = DMatrix::<f64>::new_random(5, 100).map(|x| x * 100.0 - 50.0);
+        assert_eq!(
+            super::row_normalize_log_weights_batch(weights.clone()),
+            super::row_normalize_log_weights(weights.clone()),
+        );
+        assert_eq!(
+            super::col_normalize_log_weights_batch(weights.clone()),
+            col_normalize_log_weights(weights),
+        );
+    }
+
+    #[test]
+    fn test_row_softmax_random() {
+        let weights = DMatrix::<f64>::new_random(5, 100).map(|x| x * 100.0 - 50.0);
+        let expected = DMatrix::from_fn(5, 100, |r, c| {
+            (weights[(r, c)] - log_sum_exp(weights.row(r).iter().cloned())).exp()
+        });
+        test_almost_mat(&super::row_softmax(weights), &expected, 1e-12);
+    }
+}
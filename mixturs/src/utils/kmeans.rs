@@ -0,0 +1,115 @@
+use nalgebra::DMatrix;
+use rand::Rng;
+use crate::utils::{DataScalar, sample_weighted};
+
+/// Seeds cluster centers with [k-means++](https://en.wikipedia.org/wiki/K-means%2B%2B): the first center is
+/// a uniformly random point, each next center is a point sampled with probability proportional to its squared
+/// distance (D²) to the nearest center chosen so far.
+///
+/// # Arguments
+///
+/// * `data`: The data points (n_dim, n_points), each point is converted to `f64` on the fly
+/// * `n_centers`: Number of centers to seed
+/// * `n_trials`: Number of candidate points sampled for each center, the one reducing the total squared
+/// distance the most is kept (greedy k-means++). A single trial gives the standard algorithm.
+/// * `rng`: The random number generator
+///
+/// # Returns
+///
+/// The centers (n_dim, n_centers), empty if there are no points.
+///
+/// # Example
+/// ```
+/// use nalgebra::DMatrix;
+/// use rand::SeedableRng;
+/// use rand::rngs::StdRng;
+/// use mixturs::utils::{kmeans_plusplus, nearest_centers};
+///
+/// let data = DMatrix::from_row_slice(1, 6, &[0.0, 0.1, 0.2, 10.0, 10.1, 10.2]);
+/// let centers = kmeans_plusplus(&data, 2, 1, &mut StdRng::seed_from_u64(42));
+/// let labels = nearest_centers(&data, &centers);
+/// assert_eq!(labels[0], labels[2]);
+/// assert_ne!(labels[0], labels[3]);
+/// ```
+pub fn kmeans_plusplus<T: DataScalar>(
+    data: &DMatrix<T>,
+    n_centers: usize,
+    n_trials: usize,
+    rng: &mut impl Rng,
+) -> DMatrix<f64> {
+    let n_points = data.ncols();
+    if n_points == 0 {
+        return DMatrix::zeros(data.nrows(), 0);
+    }
+
+    let mut centers = DMatrix::zeros(data.nrows(), n_centers);
+    let mut min_dist = vec![f64::INFINITY; n_points];
+    for k in 0..n_centers {
+        let mut best: Option<(f64, usize, Vec<f64>)> = None;
+        for _ in 0..n_trials.max(1) {
+            // Sample uniformly for the first center or if every point coincides with a center
+            let candidate = if k == 0 {
+                rng.gen_range(0..n_points)
+            } else {
+                sample_weighted(rng, min_dist.iter().cloned()).unwrap_or_else(|| rng.gen_range(0..n_points))
+            };
+
+            let center = data.column(candidate).map(|v| v.to_f64());
+            let dist: Vec<f64> = data.column_iter().zip(&min_dist)
+                .map(|(x, &d)| d.min(x.iter().zip(center.iter()).map(|(&v, &c)| (v.to_f64() - c).powi(2)).sum()))
+                .collect();
+            let potential: f64 = dist.iter().sum();
+            let improves = match &best {
+                Some((best_potential, _, _)) => potential < *best_potential,
+                None => true,
+            };
+            if improves {
+                best = Some((potential, candidate, dist));
+            }
+        }
+
+        let (_, candidate, dist) = best.expect("At least one trial is run");
+        centers.set_column(k, &data.column(candidate).map(|v| v.to_f64()));
+        min_dist = dist;
+    }
+
+    centers
+}
+
+/// Index of the nearest (euclidean) center of each data point (column).
+pub fn nearest_centers(data: &DMatrix<f64>, centers: &DMatrix<f64>) -> Vec<usize> {
+    data.column_iter().map(|x| {
+        centers.column_iter()
+            .map(|c| (x - c).norm_squared())
+            .enumerate()
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map_or(0, |(k, _)| k)
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DMatrix;
+    use rand::prelude::StdRng;
+    use rand::SeedableRng;
+    use crate::metrics::adjusted_rand_score;
+    use crate::testing::make_blobs;
+    use crate::utils::{kmeans_plusplus, nearest_centers};
+
+    #[test]
+    fn test_kmeans_plusplus() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let centers = DMatrix::from_column_slice(2, 4, &[0.0, 0.0, 20.0, 0.0, 0.0, 20.0, 20.0, 20.0]);
+        let (x, y) = make_blobs(100, &centers, 1.0, 42);
+
+        let seeds = kmeans_plusplus(&x, 4, 3, &mut rng);
+        assert_eq!(seeds.shape(), (2, 4));
+        let labels = nearest_centers(&x, &seeds);
+        assert!(adjusted_rand_score(y.as_slice(), &labels) > 0.99);
+
+        // Coinciding points can still seed the requested number of centers
+        let same = DMatrix::from_element(2, 3, 1.0);
+        assert_eq!(kmeans_plusplus(&same, 2, 1, &mut rng).ncols(), 2);
+        assert_eq!(kmeans_plusplus(&DMatrix::<f64>::zeros(2, 0), 2, 1, &mut rng).ncols(), 0);
+    }
+}
@@ -1,5 +1,12 @@
+mod csv;
 mod data;
+mod kmeans;
+pub mod preprocess;
 mod sampling;
+mod stats;
 
+pub use csv::*;
 pub use data::*;
-pub use sampling::*;
\ No newline at end of file
+pub use kmeans::*;
+pub use sampling::*;
+pub use stats::*;
\ No newline at end of file
@@ -0,0 +1,74 @@
+use nalgebra::DMatrix;
+
+/// Finds pairs of (near) collinear features, whose absolute Pearson correlation exceeds `threshold`.
+/// Collinear features produce (near) singular covariances and unstable fits, so consider dropping or
+/// whitening them before fitting. Constant features have no defined correlation and are never reported.
+///
+/// # Arguments:
+///
+/// * `data`: The data points. (n_dim, n_points)
+/// * `threshold`: The absolute correlation above which a pair is reported, in [0, 1].
+///
+/// # Returns:
+///
+/// The feature (row) index pairs `(i, j)` with `i < j`, in lexicographic order.
+///
+/// # Example:
+/// ```
+/// use nalgebra::DMatrix;
+/// use mixturs::utils::preprocess::detect_collinear_features;
+///
+/// let data = DMatrix::from_row_slice(3, 4, &[
+///     1.0, 2.0, 3.0, 4.0,
+///     2.0, 4.0, 6.0, 8.1,
+///     1.0, -1.0, 1.0, -1.0,
+/// ]);
+/// assert_eq!(detect_collinear_features(&data, 0.99), vec![(0, 1)]);
+/// ```
+pub fn detect_collinear_features(
+    data: &DMatrix<f64>,
+    threshold: f64,
+) -> Vec<(usize, usize)> {
+    let n_points = data.ncols() as f64;
+    let centered: Vec<_> = data.row_iter().map(|row| row.add_scalar(-row.sum() / n_points)).collect();
+    let norms: Vec<f64> = centered.iter().map(|row| row.norm()).collect();
+
+    let mut pairs = Vec::new();
+    for i in 0..centered.len() {
+        for j in i + 1..centered.len() {
+            if norms[i] == 0.0 || norms[j] == 0.0 {
+                continue;
+            }
+
+            let correlation = centered[i].dot(&centered[j]) / (norms[i] * norms[j]);
+            if correlation.abs() > threshold {
+                pairs.push((i, j));
+            }
+        }
+    }
+
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DMatrix;
+    use rand::prelude::StdRng;
+    use rand::{Rng, SeedableRng};
+    use super::detect_collinear_features;
+
+    #[test]
+    fn test_detect_collinear_features() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut data = DMatrix::from_fn(4, 200, |_, _| rng.gen_range(-1.0..1.0));
+
+        // Feature 3 duplicates feature 1 (negated and scaled)
+        let duplicate = data.row(1) * -2.0;
+        data.row_mut(3).copy_from(&duplicate);
+
+        assert_eq!(detect_collinear_features(&data, 0.95), vec![(1, 3)]);
+
+        data.row_mut(2).fill(1.0);
+        assert_eq!(detect_collinear_features(&data, 0.95), vec![(1, 3)]);
+    }
+}
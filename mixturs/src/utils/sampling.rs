@@ -1,3 +1,4 @@
+use std::cmp::Ordering;
 use nalgebra::{RealField};
 use rand::distributions::uniform::SampleUniform;
 use rand::distributions::{Distribution, WeightedIndex};
@@ -102,6 +103,89 @@ pub fn reservoir_sampling_weighted<
     n
 }
 
+/// Samples a single index with probability proportional to its weight, using weighted reservoir sampling.
+/// Makes a single pass over the weights without allocating. Zero (and NaN) weights are never picked.
+///
+/// # Arguments:
+///
+/// * `rng`: A random number generator.
+/// * `src`: An iterator over the (non-negative) weights.
+///
+/// # Returns:
+///
+/// The sampled index, or `None` if no weight is positive.
+///
+/// # Example:
+/// ```
+/// use mixturs::utils::sample_weighted;
+///
+/// let mut rng = rand::thread_rng();
+/// assert_eq!(sample_weighted(&mut rng, vec![0.0, 2.0, 0.0].into_iter()), Some(1));
+/// assert_eq!(sample_weighted(&mut rng, vec![0.0, 0.0].into_iter()), None);
+/// ```
+pub fn sample_weighted<
+    W: RealField + Copy + SampleUniform, I: Iterator<Item=W>
+>(
+    rng: &mut impl Rng,
+    src: I,
+) -> Option<usize> {
+    let mut w_sum = W::zero();
+    let mut choice = None;
+    for (i, w) in src.enumerate() {
+        if w.partial_cmp(&W::zero()) != Some(Ordering::Greater) {
+            continue;
+        }
+
+        w_sum += w;
+        if rng.gen_range(W::zero()..w_sum) < w {
+            choice = Some(i);
+        }
+    }
+    choice
+}
+
+/// Samples a single index from already normalized probabilities (e.g. the rows of
+/// [`row_softmax`](crate::utils::row_softmax)), drawing one uniform number and walking the cumulative sum.
+/// Unlike [`sample_weighted`], the weights are not summed up first.
+///
+/// # Arguments:
+///
+/// * `rng`: A random number generator.
+/// * `probs`: An iterator over the probabilities, summing to one.
+///
+/// # Returns:
+///
+/// The sampled index, or `None` if no probability is positive. If the probabilities sum to slightly
+/// less than one due to rounding, the last index with a positive probability absorbs the remainder.
+///
+/// # Example:
+/// ```
+/// use mixturs::utils::sample_normalized;
+///
+/// let mut rng = rand::thread_rng();
+/// assert_eq!(sample_normalized(&mut rng, vec![0.0, 1.0, 0.0].into_iter()), Some(1));
+/// ```
+pub fn sample_normalized<I: Iterator<Item=f64>>(
+    rng: &mut impl Rng,
+    probs: I,
+) -> Option<usize> {
+    let u: f64 = rng.gen();
+    let mut cumsum = 0.0;
+    let mut last = None;
+    for (i, p) in probs.enumerate() {
+        if p.partial_cmp(&0.0) != Some(Ordering::Greater) {
+            continue;
+        }
+
+        cumsum += p;
+        last = Some(i);
+        if u < cumsum {
+            break;
+        }
+    }
+    last
+}
+
 /// Random sampling k items with replacement with weighted sampling algorithm.
 ///
 /// # Arguments:
@@ -136,7 +220,9 @@ pub fn replacement_sampling_weighted<
 
 #[cfg(test)]
 mod tests {
-    use crate::utils::reservoir_sampling_weighted;
+    use rand::prelude::StdRng;
+    use rand::SeedableRng;
+    use crate::utils::{reservoir_sampling_weighted, sample_normalized, sample_weighted};
 
     #[test]
     fn test_reservoir_sampling_weighted() {
@@ -152,4 +238,47 @@ mod tests {
         dst.sort();
         assert_eq!(dst, [0, 1, 2, 3]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_sample_weighted() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let weights = [1.0, 2.0, 3.0, 4.0, 0.0];
+
+        let n_samples = 100000;
+        let mut counts = [0usize; 5];
+        for _ in 0..n_samples {
+            counts[sample_weighted(&mut rng, weights.iter().cloned()).unwrap()] += 1;
+        }
+
+        for (count, w) in counts.iter().zip(weights) {
+            let freq = *count as f64 / n_samples as f64;
+            assert!((freq - w / 10.0).abs() < 0.01, "frequency {} for weight {}", freq, w);
+        }
+        assert_eq!(counts[4], 0);
+
+        assert_eq!(sample_weighted(&mut rng, [0.0, 0.0].iter().cloned()), None);
+        assert_eq!(sample_weighted(&mut rng, [f64::NAN, 0.0].iter().cloned()), None);
+    }
+
+    #[test]
+    fn test_sample_normalized() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let probs = [0.1, 0.2, 0.3, 0.4, 0.0];
+
+        let n_samples = 100000;
+        let mut counts = [0usize; 5];
+        for _ in 0..n_samples {
+            counts[sample_normalized(&mut rng, probs.iter().cloned()).unwrap()] += 1;
+        }
+
+        for (count, p) in counts.iter().zip(probs) {
+            let freq = *count as f64 / n_samples as f64;
+            assert!((freq - p).abs() < 0.01, "frequency {} for probability {}", freq, p);
+        }
+        assert_eq!(counts[4], 0);
+
+        // Rounding shortfalls fall to the last positive probability
+        assert_eq!(sample_normalized(&mut rng, [0.0, 1e-300, 0.0].iter().cloned()), Some(1));
+        assert_eq!(sample_normalized(&mut rng, [0.0, 0.0].iter().cloned()), None);
+    }
+}
@@ -0,0 +1,172 @@
+use nalgebra::DMatrix;
+
+/// Computes exact quantiles of the given values with linear interpolation between the closest ranks.
+/// Each quantile is found by a partial sort (selection) rather than sorting all of the values.
+///
+/// # Arguments:
+///
+/// * `values`: The values to compute the quantiles of.
+/// * `qs`: The quantiles to compute, each in the range [0, 1].
+///
+/// # Returns:
+///
+/// The quantile values in the same order as `qs`. Empty input yields NaN for each quantile.
+///
+/// # Example:
+/// ```
+/// use mixturs::utils::quantiles;
+///
+/// let values = [4.0, 1.0, 3.0, 2.0];
+/// assert_eq!(quantiles(&values, &[0.0, 0.5, 1.0]), vec![1.0, 2.5, 4.0]);
+/// ```
+pub fn quantiles(values: &[f64], qs: &[f64]) -> Vec<f64> {
+    if values.is_empty() {
+        return vec![f64::NAN; qs.len()];
+    }
+
+    let mut buffer = values.to_vec();
+    qs.iter().map(|&q| {
+        assert!((0.0..=1.0).contains(&q), "Quantile must be in range [0, 1]");
+
+        let pos = q * (buffer.len() - 1) as f64;
+        let lo = pos.floor() as usize;
+        let (_, &mut lo_value, rest) = buffer.select_nth_unstable_by(lo, f64::total_cmp);
+
+        let frac = pos - lo as f64;
+        if frac > 0.0 {
+            let hi_value = rest.iter().cloned().fold(f64::INFINITY, f64::min);
+            lo_value + frac * (hi_value - lo_value)
+        } else {
+            lo_value
+        }
+    }).collect()
+}
+
+/// Computes the (sample size adjusted) bimodality coefficient `(g^2 + 1) / (k + 3 (n-1)^2 / ((n-2)(n-3)))`
+/// of the values, where `g` is the skewness and `k` the excess kurtosis.
+/// Values above `5/9` (the coefficient of a uniform distribution) suggest a bimodal distribution.
+///
+/// # Arguments:
+///
+/// * `values`: The values to compute the coefficient of. At least 4 values are required.
+///
+/// # Returns:
+///
+/// The bimodality coefficient, or NaN if there are fewer than 4 values or they are all equal.
+///
+/// # Example:
+/// ```
+/// use mixturs::utils::bimodality_coefficient;
+///
+/// let values: Vec<f64> = (0..20).map(|i| if i % 2 == 0 { -1.0 } else { 1.0 }).collect();
+/// assert!(bimodality_coefficient(&values) > 5.0 / 9.0);
+/// ```
+pub fn bimodality_coefficient(values: &[f64]) -> f64 {
+    let n = values.len() as f64;
+    if values.len() < 4 {
+        return f64::NAN;
+    }
+
+    let mean = values.iter().sum::<f64>() / n;
+    let moment = |p: i32| values.iter().map(|x| (x - mean).powi(p)).sum::<f64>() / n;
+    let (m2, m3, m4) = (moment(2), moment(3), moment(4));
+    if m2 <= 0.0 {
+        return f64::NAN;
+    }
+
+    let skew = m3 / m2.powf(1.5) * (n * (n - 1.0)).sqrt() / (n - 2.0);
+    let kurt = ((n + 1.0) * (m4 / (m2 * m2) - 3.0) + 6.0) * (n - 1.0) / ((n - 2.0) * (n - 3.0));
+
+    (skew * skew + 1.0) / (kurt + 3.0 * (n - 1.0).powi(2) / ((n - 2.0) * (n - 3.0)))
+}
+
+/// Computes the quantiles of each feature over the points of each cluster.
+///
+/// # Arguments:
+///
+/// * `data`: The data points. (n_dim, n_points)
+/// * `labels`: The cluster label of each point. (n_points)
+/// * `n_clusters`: The number of clusters.
+/// * `qs`: The quantiles to compute, each in the range [0, 1].
+///
+/// # Returns:
+///
+/// A matrix of quantiles for each cluster. (n_dim, n_quantiles)
+///
+/// # Example:
+/// ```
+/// use nalgebra::DMatrix;
+/// use mixturs::utils::cluster_feature_quantiles;
+///
+/// let data = DMatrix::from_row_slice(1, 4, &[1.0, 2.0, 10.0, 20.0]);
+/// let quantiles = cluster_feature_quantiles(&data, &[0, 0, 1, 1], 2, &[0.5]);
+/// assert_eq!(quantiles[0][(0, 0)], 1.5);
+/// assert_eq!(quantiles[1][(0, 0)], 15.0);
+/// ```
+pub fn cluster_feature_quantiles(
+    data: &DMatrix<f64>,
+    labels: &[usize],
+    n_clusters: usize,
+    qs: &[f64],
+) -> Vec<DMatrix<f64>> {
+    assert_eq!(data.ncols(), labels.len());
+
+    let mut members = vec![Vec::new(); n_clusters];
+    for (i, &label) in labels.iter().enumerate() {
+        members[label].push(i);
+    }
+
+    members.iter().map(|indices| {
+        let mut out = DMatrix::zeros(data.nrows(), qs.len());
+        for d in 0..data.nrows() {
+            let values: Vec<f64> = indices.iter().map(|&i| data[(d, i)]).collect();
+            for (j, value) in quantiles(&values, qs).into_iter().enumerate() {
+                out[(d, j)] = value;
+            }
+        }
+        out
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use statrs::assert_almost_eq;
+    use rand::distributions::Distribution;
+    use rand::prelude::StdRng;
+    use rand::SeedableRng;
+    use statrs::distribution::Normal;
+    use super::{bimodality_coefficient, quantiles};
+
+    #[test]
+    fn test_quantiles() {
+        let odd = [7.0, 1.0, 5.0, 3.0, 9.0];
+        assert_eq!(quantiles(&odd, &[0.5]), vec![5.0]);
+
+        let even = [8.0, 2.0, 6.0, 4.0];
+        assert_eq!(quantiles(&even, &[0.5]), vec![5.0]);
+
+        let q = quantiles(&even, &[0.0, 0.25, 1.0]);
+        assert_almost_eq!(q[0], 2.0, 1e-12);
+        assert_almost_eq!(q[1], 3.5, 1e-12);
+        assert_almost_eq!(q[2], 8.0, 1e-12);
+
+        assert!(quantiles(&[], &[0.5])[0].is_nan());
+    }
+
+    #[test]
+    fn test_bimodality_coefficient() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let normal = Normal::new(0.0, 1.0).unwrap();
+
+        let unimodal: Vec<f64> = (0..500).map(|_| normal.sample(&mut rng)).collect();
+        assert!(bimodality_coefficient(&unimodal) < 5.0 / 9.0);
+
+        let bimodal: Vec<f64> = (0..500)
+            .map(|i| normal.sample(&mut rng) + if i % 2 == 0 { -4.0 } else { 4.0 })
+            .collect();
+        assert!(bimodality_coefficient(&bimodal) > 5.0 / 9.0);
+
+        assert!(bimodality_coefficient(&[1.0, 2.0]).is_nan());
+        assert!(bimodality_coefficient(&[1.0; 5]).is_nan());
+    }
+}